@@ -39,7 +39,7 @@ fn raw_enforce(r: [&str; 3]) -> bool {
             return true;
         }
     }
-    return false;
+    false
 }
 
 fn b_benchmark_raw(c: &mut Criterion) {
@@ -142,7 +142,7 @@ fn b_benchmark_role_manager_small(c: &mut Criterion) {
     c.bench_function("benchmark_role_manager_small", |b| {
         b.iter(|| {
             (0..100_u64).for_each(|i| {
-                rm.write().has_link("user501", &format!("group{}", i), None);
+                rm.write().has_link("user501", &format!("group{}", i), &[]);
             })
         })
     });
@@ -264,8 +264,7 @@ fn b_benchmark_role_manager_medium(c: &mut Criterion) {
     c.bench_function("benchmark_role_manager_medium", |b| {
         b.iter(|| {
             (0..1000_u64).for_each(|i| {
-                rm.write()
-                    .has_link("user5001", &format!("group{}", i), None);
+                rm.write().has_link("user5001", &format!("group{}", i), &[]);
             })
         })
     });
@@ -388,7 +387,7 @@ fn b_benchmark_role_manager_large(c: &mut Criterion) {
         b.iter(|| {
             (0..10000_u64).for_each(|i| {
                 rm.write()
-                    .has_link("user50001", &format!("group{}", i), None);
+                    .has_link("user50001", &format!("group{}", i), &[]);
             })
         })
     });
@@ -0,0 +1,660 @@
+use crate::{
+    adapter::{Adapter, Filter, PolicyFilter, UpdatableAdapter},
+    error::{AdapterError, ModelError},
+    model::Model,
+    Result,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::adapter::file_adapter::with_locked_file;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "runtime-async-std"))]
+use async_std::fs::read_to_string;
+#[cfg(all(not(target_arch = "wasm32"), feature = "runtime-tokio"))]
+use tokio::fs::read_to_string;
+
+use async_trait::async_trait;
+use serde::{
+    de, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use std::{collections::HashMap, marker::PhantomData};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+/// A policy rule as stored by a [`DocumentAdapter`], serialized as a
+/// `{"ptype": "p", "v0": "alice", "v1": "data1", "v2": "read"}` record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyRecord {
+    pub ptype: String,
+    pub values: Vec<String>,
+}
+
+impl PolicyRecord {
+    fn is(&self, ptype: &str, rule: &[String]) -> bool {
+        self.ptype == ptype && self.values == rule
+    }
+}
+
+impl Serialize for PolicyRecord {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.values.len() + 1))?;
+        map.serialize_entry("ptype", &self.ptype)?;
+        for (i, value) in self.values.iter().enumerate() {
+            map.serialize_entry(&format!("v{}", i), value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for PolicyRecord {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut fields = HashMap::<String, String>::deserialize(deserializer)?;
+        let ptype = fields
+            .remove("ptype")
+            .ok_or_else(|| de::Error::missing_field("ptype"))?;
+        let mut values = Vec::new();
+        while let Some(value) = fields.remove(&format!("v{}", values.len())) {
+            values.push(value);
+        }
+        if let Some(key) = fields.keys().next() {
+            return Err(de::Error::custom(format!(
+                "unexpected field `{}` after `v{}`",
+                key,
+                values.len()
+            )));
+        }
+
+        Ok(PolicyRecord { ptype, values })
+    }
+}
+
+/// A document format of a [`DocumentAdapter`], holding the policy as a
+/// sequence of [`PolicyRecord`]s.
+pub trait DocumentFormat: Send + Sync + 'static {
+    fn parse(document: &str) -> Result<Vec<PolicyRecord>>;
+    fn render(records: &[PolicyRecord]) -> Result<String>;
+}
+
+/// JSON array of records.
+pub struct JsonFormat;
+
+impl DocumentFormat for JsonFormat {
+    fn parse(document: &str) -> Result<Vec<PolicyRecord>> {
+        serde_json::from_str(document)
+            .map_err(|e| AdapterError(e.into()).into())
+    }
+
+    fn render(records: &[PolicyRecord]) -> Result<String> {
+        serde_json::to_string_pretty(records)
+            .map_err(|e| AdapterError(e.into()).into())
+    }
+}
+
+/// YAML sequence of records.
+#[cfg(feature = "yaml")]
+pub struct YamlFormat;
+
+#[cfg(feature = "yaml")]
+impl DocumentFormat for YamlFormat {
+    fn parse(document: &str) -> Result<Vec<PolicyRecord>> {
+        serde_yaml::from_str(document)
+            .map_err(|e| AdapterError(e.into()).into())
+    }
+
+    fn render(records: &[PolicyRecord]) -> Result<String> {
+        serde_yaml::to_string(records)
+            .map_err(|e| AdapterError(e.into()).into())
+    }
+}
+
+/// Adapter storing the policy as a [`JsonAdapter`] or a [`YamlAdapter`]
+/// document of `{ptype, v0..vN}` records, read from a file or a string.
+///
+/// Changes are saved to the file like [`FileAdapter`] does, or to the string
+/// returned by [`document`].
+///
+/// ```
+/// use casbin::{DocumentAdapter, JsonAdapter};
+///
+/// let adapter: JsonAdapter = DocumentAdapter::from_string(
+///     r#"[
+///         {"ptype": "p", "v0": "alice", "v1": "data1", "v2": "read"},
+///         {"ptype": "g", "v0": "bob", "v1": "admin"}
+///     ]"#,
+/// );
+/// ```
+///
+/// [`FileAdapter`]: crate::FileAdapter
+/// [`document`]: DocumentAdapter::document
+pub struct DocumentAdapter<F> {
+    source: Source,
+    is_filtered: bool,
+    format: PhantomData<fn() -> F>,
+}
+
+pub type JsonAdapter = DocumentAdapter<JsonFormat>;
+#[cfg(feature = "yaml")]
+pub type YamlAdapter = DocumentAdapter<YamlFormat>;
+
+enum Source {
+    #[cfg(not(target_arch = "wasm32"))]
+    File(PathBuf),
+    Text(String),
+}
+
+impl<F> DocumentAdapter<F>
+where
+    F: DocumentFormat,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self::with_source(Source::File(path.into()))
+    }
+
+    pub fn from_string<S: Into<String>>(document: S) -> Self {
+        Self::with_source(Source::Text(document.into()))
+    }
+
+    fn with_source(source: Source) -> Self {
+        DocumentAdapter {
+            source,
+            is_filtered: false,
+            format: PhantomData,
+        }
+    }
+
+    /// The in-memory document, `None` when the adapter reads a file.
+    pub fn document(&self) -> Option<&str> {
+        match &self.source {
+            Source::Text(text) => Some(text),
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::File(_) => None,
+        }
+    }
+
+    async fn read(&self) -> Result<Vec<PolicyRecord>> {
+        match &self.source {
+            Source::Text(text) => parse::<F>(text),
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::File(path) => parse::<F>(&read_to_string(path).await?),
+        }
+    }
+
+    async fn write(&mut self, records: Vec<PolicyRecord>) -> Result<()> {
+        let document = F::render(&records)?;
+        match &mut self.source {
+            Source::Text(text) => *text = document,
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::File(path) => {
+                with_locked_file(path.clone(), move |file| {
+                    Ok(file.replace(&document)?)
+                })
+                .await?
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `edit` to the stored records, which are saved back when it
+    /// returns true.
+    async fn edit<E>(&mut self, edit: E) -> Result<bool>
+    where
+        E: FnOnce(&mut Vec<PolicyRecord>) -> bool + Send + 'static,
+    {
+        match &mut self.source {
+            Source::Text(text) => {
+                let mut records = parse::<F>(text)?;
+                let edited = edit(&mut records);
+                if edited {
+                    *text = F::render(&records)?;
+                }
+                Ok(edited)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Source::File(path) => {
+                with_locked_file(path.clone(), move |file| {
+                    let mut records = parse::<F>(&file.read()?)?;
+                    let edited = edit(&mut records);
+                    if edited {
+                        file.replace(&F::render(&records)?)?;
+                    }
+                    Ok(edited)
+                })
+                .await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<F> Adapter for DocumentAdapter<F>
+where
+    F: DocumentFormat,
+{
+    async fn load_policy(&mut self, m: &mut dyn Model) -> Result<()> {
+        self.is_filtered = false;
+        let filter = PolicyFilter::new();
+        let filter = filter.compile(m)?;
+        for record in self.read().await? {
+            filter.load_rule(m, &record.ptype, record.values);
+        }
+
+        Ok(())
+    }
+
+    async fn load_filtered_policy<'a>(
+        &mut self,
+        m: &mut dyn Model,
+        f: Filter<'a>,
+    ) -> Result<()> {
+        self.load_policy_with_filter(m, f.into()).await
+    }
+
+    async fn load_policy_with_filter(
+        &mut self,
+        m: &mut dyn Model,
+        f: PolicyFilter,
+    ) -> Result<()> {
+        let filter = f.compile(m)?;
+        self.is_filtered = false;
+        for record in self.read().await? {
+            if filter.load_rule(m, &record.ptype, record.values) {
+                self.is_filtered = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn save_policy(&mut self, m: &mut dyn Model) -> Result<()> {
+        let model = m.get_model();
+        let ast_map = model.get("p").ok_or_else(|| {
+            ModelError::P("Missing policy definition in conf file".to_owned())
+        })?;
+
+        let mut records = Vec::new();
+        for ast_map in [Some(ast_map), model.get("g")].into_iter().flatten() {
+            for (ptype, ast) in ast_map {
+                records.extend(ast.get_policy().iter().map(|rule| {
+                    PolicyRecord {
+                        ptype: ptype.clone(),
+                        values: rule.clone(),
+                    }
+                }));
+            }
+        }
+
+        self.write(records).await
+    }
+
+    async fn clear_policy(&mut self) -> Result<()> {
+        self.is_filtered = false;
+        self.write(Vec::new()).await
+    }
+
+    fn is_filtered(&self) -> bool {
+        self.is_filtered
+    }
+
+    fn as_updatable_mut(&mut self) -> Option<&mut dyn UpdatableAdapter> {
+        Some(self)
+    }
+
+    async fn add_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> Result<bool> {
+        self.add_policies(sec, ptype, vec![rule]).await
+    }
+
+    async fn add_policies(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        let ptype = ptype.to_owned();
+        self.edit(move |records| add_records(records, &ptype, rules))
+            .await
+    }
+
+    async fn remove_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> Result<bool> {
+        self.remove_policies(sec, ptype, vec![rule]).await
+    }
+
+    async fn remove_policies(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        let ptype = ptype.to_owned();
+        self.edit(move |records| {
+            if !rules
+                .iter()
+                .all(|rule| records.iter().any(|x| x.is(&ptype, rule)))
+            {
+                return false;
+            }
+
+            records.retain(|x| !rules.iter().any(|rule| x.is(&ptype, rule)));
+            true
+        })
+        .await
+    }
+
+    async fn remove_filtered_policy(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        let ptype = ptype.to_owned();
+        self.edit(move |records| {
+            remove_filtered_records(records, &ptype, field_index, &field_values)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl<F> UpdatableAdapter for DocumentAdapter<F>
+where
+    F: DocumentFormat,
+{
+    async fn update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> Result<bool> {
+        self.update_policies(sec, ptype, vec![old_rule], vec![new_rule])
+            .await
+    }
+
+    async fn update_policies(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        if old_rules.len() != new_rules.len() {
+            return Ok(false);
+        }
+
+        let ptype = ptype.to_owned();
+        self.edit(move |records| {
+            let positions: Option<Vec<usize>> = old_rules
+                .iter()
+                .map(|rule| records.iter().position(|x| x.is(&ptype, rule)))
+                .collect();
+            let Some(positions) = positions else {
+                return false;
+            };
+            // refuse to duplicate a rule which is not replaced
+            let duplicated = new_rules.iter().any(|rule| {
+                records
+                    .iter()
+                    .enumerate()
+                    .any(|(i, x)| x.is(&ptype, rule) && !positions.contains(&i))
+            });
+            if duplicated {
+                return false;
+            }
+
+            for (i, rule) in positions.into_iter().zip(new_rules) {
+                records[i].values = rule;
+            }
+            true
+        })
+        .await
+    }
+
+    async fn update_filtered_policies(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        new_rules: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        let ptype = ptype.to_owned();
+        self.edit(move |records| {
+            remove_filtered_records(records, &ptype, field_index, &field_values)
+                && add_records(records, &ptype, new_rules)
+        })
+        .await
+    }
+}
+
+fn parse<F: DocumentFormat>(document: &str) -> Result<Vec<PolicyRecord>> {
+    if document.trim().is_empty() {
+        Ok(Vec::new())
+    } else {
+        F::parse(document)
+    }
+}
+
+/// Appends `rules` unless one of them is already stored.
+fn add_records(
+    records: &mut Vec<PolicyRecord>,
+    ptype: &str,
+    rules: Vec<Vec<String>>,
+) -> bool {
+    if rules
+        .iter()
+        .any(|rule| records.iter().any(|x| x.is(ptype, rule)))
+    {
+        return false;
+    }
+
+    records.extend(rules.into_iter().map(|values| PolicyRecord {
+        ptype: ptype.to_owned(),
+        values,
+    }));
+    true
+}
+
+fn remove_filtered_records(
+    records: &mut Vec<PolicyRecord>,
+    ptype: &str,
+    field_index: usize,
+    field_values: &[String],
+) -> bool {
+    if field_values.is_empty() {
+        return false;
+    }
+
+    let len = records.len();
+    records.retain(|x| {
+        x.ptype != ptype
+            || !field_values.iter().enumerate().all(|(i, value)| {
+                value.is_empty() || x.values.get(field_index + i) == Some(value)
+            })
+    });
+    records.len() != len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, FieldMatch};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn rule(values: &[&str]) -> Vec<String> {
+        values.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[cfg_attr(
+        all(not(target_arch = "wasm32"), feature = "runtime-async-std"),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(not(target_arch = "wasm32"), feature = "runtime-tokio"),
+        tokio::test
+    )]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    async fn test_json_adapter_from_string() {
+        let adapter = JsonAdapter::from_string(include_str!(
+            "../../examples/rbac_policy.json"
+        ));
+        let mut e = Enforcer::new(
+            DefaultModel::from_str(include_str!(
+                "../../examples/rbac_model.conf"
+            ))
+            .await
+            .unwrap(),
+            adapter,
+        )
+        .await
+        .unwrap();
+
+        assert!(e.enforce(("alice", "data2", "read")).unwrap());
+        assert!(!e.enforce(("bob", "data1", "read")).unwrap());
+
+        assert!(e.add_policy(rule(&["eve", "data3", "read"])).await.unwrap());
+        assert!(e
+            .remove_grouping_policy(rule(&["alice", "data2_admin"]))
+            .await
+            .unwrap());
+        e.load_policy().await.unwrap();
+        assert!(e.enforce(("eve", "data3", "read")).unwrap());
+        assert!(!e.enforce(("alice", "data2", "read")).unwrap());
+
+        let mut m = DefaultModel::from_str(include_str!(
+            "../../examples/rbac_model.conf"
+        ))
+        .await
+        .unwrap();
+        let filter = PolicyFilter::new().ptypes(["p"]).field(
+            "p",
+            "sub",
+            FieldMatch::one_of(["alice", "eve"]),
+        );
+        let mut adapter = JsonAdapter::from_string(include_str!(
+            "../../examples/rbac_policy.json"
+        ));
+        adapter
+            .load_policy_with_filter(&mut m, filter)
+            .await
+            .unwrap();
+        assert!(adapter.is_filtered());
+        assert_eq!(
+            m.get_policy("p", "p"),
+            vec![rule(&["alice", "data1", "read"])]
+        );
+        assert!(m.get_policy("g", "g").is_empty());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(feature = "runtime-async-std", async_std::test)]
+    #[cfg_attr(feature = "runtime-tokio", tokio::test)]
+    async fn test_json_adapter_file() {
+        use crate::adapter::file_adapter::TempPolicy;
+
+        let policy = TempPolicy::copy("examples/rbac_policy.json");
+        let mut e = Enforcer::new(
+            "examples/rbac_model.conf",
+            JsonAdapter::new(policy.path()),
+        )
+        .await
+        .unwrap();
+
+        assert!(e
+            .update_policy(
+                rule(&["bob", "data2", "write"]),
+                rule(&["bob", "data2", "read"]),
+            )
+            .await
+            .unwrap());
+        assert!(e
+            .remove_filtered_policy(0, rule(&["data2_admin"]))
+            .await
+            .unwrap());
+
+        let records =
+            JsonFormat::parse(&std::fs::read_to_string(policy.path()).unwrap())
+                .unwrap();
+        assert_eq!(
+            records,
+            vec![
+                PolicyRecord {
+                    ptype: "p".to_owned(),
+                    values: rule(&["alice", "data1", "read"]),
+                },
+                PolicyRecord {
+                    ptype: "p".to_owned(),
+                    values: rule(&["bob", "data2", "read"]),
+                },
+                PolicyRecord {
+                    ptype: "g".to_owned(),
+                    values: rule(&["alice", "data2_admin"]),
+                },
+            ]
+        );
+
+        e.save_policy().await.unwrap();
+        let e2 = Enforcer::new(
+            "examples/rbac_model.conf",
+            JsonAdapter::new(policy.path()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(e.get_policy(), e2.get_policy());
+        assert_eq!(e.get_grouping_policy(), e2.get_grouping_policy());
+    }
+
+    #[test]
+    fn test_invalid_record() {
+        assert!(JsonFormat::parse(r#"[{"v0": "alice"}]"#).is_err());
+        assert!(
+            JsonFormat::parse(r#"[{"ptype": "p", "v0": "a", "v2": "b"}]"#)
+                .is_err()
+        );
+    }
+
+    #[cfg(all(feature = "yaml", not(target_arch = "wasm32")))]
+    #[cfg_attr(feature = "runtime-async-std", async_std::test)]
+    #[cfg_attr(feature = "runtime-tokio", tokio::test)]
+    async fn test_yaml_adapter() {
+        let mut adapter = YamlAdapter::new("examples/rbac_policy.yaml");
+        let mut m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+        adapter.load_policy(&mut m).await.unwrap();
+
+        let mut memory = YamlAdapter::from_string("");
+        memory.save_policy(&mut m).await.unwrap();
+        assert_eq!(
+            YamlFormat::parse(memory.document().unwrap()).unwrap(),
+            YamlFormat::parse(include_str!("../../examples/rbac_policy.yaml"))
+                .unwrap()
+        );
+
+        let e = Enforcer::new(m, memory).await.unwrap();
+        assert!(e.enforce(("alice", "data2", "read")).unwrap());
+    }
+}
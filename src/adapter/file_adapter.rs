@@ -1,5 +1,7 @@
 use crate::{
-    adapter::{Adapter, Filter},
+    adapter::{
+        filter::CompiledFilter, Adapter, Filter, PolicyFilter, UpdatableAdapter,
+    },
     error::{AdapterError, ModelError},
     model::Model,
     util::parse_csv_line,
@@ -8,13 +10,8 @@ use crate::{
 
 #[cfg(feature = "runtime-async-std")]
 use async_std::{
-    fs::File as file,
-    io::prelude::*,
-    io::{
-        BufReader as ioBufReader, Error as ioError, ErrorKind as ioErrorKind,
-    },
-    path::Path as ioPath,
-    prelude::*,
+    fs::File as file, io::prelude::*, io::BufReader as ioBufReader,
+    path::Path as ioPath, prelude::*,
 };
 
 #[cfg(feature = "runtime-tokio")]
@@ -22,20 +19,33 @@ use std::path::Path as ioPath;
 #[cfg(feature = "runtime-tokio")]
 use tokio::{
     fs::File as file,
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader as ioBufReader},
+    io::{AsyncBufReadExt, BufReader as ioBufReader},
 };
 
 use async_trait::async_trait;
-use std::fmt::Write;
+use fs4::fs_std::FileExt;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
 
+/// Adapter storing the policy in a CSV file.
+///
+/// With auto-save enabled every change is persisted right away: added rules
+/// are appended while removed and updated ones are written to a temporary
+/// file renamed over the policy. Writers hold an advisory lock on the
+/// sibling `<file>.lock` so that several processes can share the file.
 pub struct FileAdapter<P> {
     file_path: P,
     is_filtered: bool,
 }
 
 type LoadPolicyFileHandler = fn(String, &mut dyn Model);
-type LoadFilteredPolicyFileHandler<'a> =
-    fn(String, &mut dyn Model, f: &Filter<'a>) -> bool;
+type LoadFilteredPolicyFileHandler =
+    fn(String, &mut dyn Model, f: &CompiledFilter<'_>) -> bool;
 
 impl<P> FileAdapter<P>
 where
@@ -75,12 +85,13 @@ where
         Ok(())
     }
 
-    async fn load_filtered_policy_file<'a>(
+    async fn load_filtered_policy_file(
         &self,
         m: &mut dyn Model,
-        filter: Filter<'a>,
-        handler: LoadFilteredPolicyFileHandler<'a>,
+        filter: PolicyFilter,
+        handler: LoadFilteredPolicyFileHandler,
     ) -> Result<bool> {
+        let filter = filter.compile(m)?;
         let f = file::open(&self.file_path).await?;
         let mut lines = ioBufReader::new(f).lines();
 
@@ -102,10 +113,18 @@ where
         Ok(is_filtered)
     }
 
+    async fn with_locked_file<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(LockedFile) -> Result<T> + Send + 'static,
+    {
+        let path: &ioPath = self.file_path.as_ref();
+        with_locked_file(Path::new(path.as_os_str()).to_path_buf(), f).await
+    }
+
     async fn save_policy_file(&self, text: String) -> Result<()> {
-        let mut file = file::create(&self.file_path).await?;
-        file.write_all(text.as_bytes()).await?;
-        Ok(())
+        self.with_locked_file(move |file| Ok(file.replace(&text)?))
+            .await
     }
 }
 
@@ -124,6 +143,14 @@ where
         &mut self,
         m: &mut dyn Model,
         f: Filter<'a>,
+    ) -> Result<()> {
+        self.load_policy_with_filter(m, f.into()).await
+    }
+
+    async fn load_policy_with_filter(
+        &mut self,
+        m: &mut dyn Model,
+        f: PolicyFilter,
     ) -> Result<()> {
         self.is_filtered = self
             .load_filtered_policy_file(m, f, load_filtered_policy_line)
@@ -147,7 +174,7 @@ where
 
         for (ptype, ast) in ast_map {
             for rule in ast.get_policy() {
-                writeln!(policies, "{}, {}", ptype, rule.join(", "))
+                writeln!(policies, "{}", policy_line(ptype, rule))
                     .map_err(|e| AdapterError(e.into()))?;
             }
         }
@@ -155,7 +182,7 @@ where
         if let Some(ast_map) = m.get_model().get("g") {
             for (ptype, ast) in ast_map {
                 for rule in ast.get_policy() {
-                    writeln!(policies, "{}, {}", ptype, rule.join(", "))
+                    writeln!(policies, "{}", policy_line(ptype, rule))
                         .map_err(|e| AdapterError(e.into()))?;
                 }
             }
@@ -177,58 +204,362 @@ where
 
     async fn add_policy(
         &mut self,
-        _sec: &str,
-        _ptype: &str,
-        _rule: Vec<String>,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
     ) -> Result<bool> {
-        // this api shouldn't implement, just for convenience
-        Ok(true)
+        self.add_policies(sec, ptype, vec![rule]).await
     }
 
     async fn add_policies(
         &mut self,
         _sec: &str,
-        _ptype: &str,
-        _rules: Vec<Vec<String>>,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
     ) -> Result<bool> {
-        // this api shouldn't implement, just for convenience
-        Ok(true)
+        let ptype = ptype.to_owned();
+        self.with_locked_file(move |file| {
+            let text = file.read()?;
+            let targets: HashSet<&[String]> =
+                rules.iter().map(Vec::as_slice).collect();
+            let exists = text
+                .lines()
+                .filter_map(parse_csv_line)
+                .any(|x| x[0] == ptype && targets.contains(&x[1..]));
+            if exists {
+                return Ok(false);
+            }
+
+            let lines: Vec<String> =
+                rules.iter().map(|x| policy_line(&ptype, x)).collect();
+            file.append(&text, &lines)?;
+            Ok(true)
+        })
+        .await
     }
 
     async fn remove_policy(
         &mut self,
-        _sec: &str,
-        _ptype: &str,
-        _rule: Vec<String>,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
     ) -> Result<bool> {
-        // this api shouldn't implement, just for convenience
-        Ok(true)
+        self.remove_policies(sec, ptype, vec![rule]).await
     }
 
     async fn remove_policies(
         &mut self,
         _sec: &str,
-        _ptype: &str,
-        _rule: Vec<Vec<String>>,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
     ) -> Result<bool> {
-        // this api shouldn't implement, just for convenience
-        Ok(true)
+        let ptype = ptype.to_owned();
+        self.with_locked_file(move |file| {
+            let text = file.read()?;
+            let targets: HashSet<&[String]> =
+                rules.iter().map(Vec::as_slice).collect();
+            let mut found = HashSet::new();
+            let rewritten = rewrite(&text, |p, rule| {
+                if p == ptype && targets.contains(rule) {
+                    found.insert(rule.to_vec());
+                    LineEdit::Remove
+                } else {
+                    LineEdit::Keep
+                }
+            });
+
+            // like MemoryAdapter, either every rule is removed or none
+            if found.len() < targets.len() {
+                return Ok(false);
+            }
+            if let Some(text) = rewritten {
+                file.replace(&text)?;
+            }
+            Ok(true)
+        })
+        .await
     }
 
     async fn remove_filtered_policy(
         &mut self,
         _sec: &str,
-        _ptype: &str,
-        _field_index: usize,
-        _field_values: Vec<String>,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
     ) -> Result<bool> {
-        // this api shouldn't implement, just for convenience
-        Ok(true)
+        if field_values.is_empty() {
+            return Ok(false);
+        }
+
+        let ptype = ptype.to_owned();
+        self.with_locked_file(move |file| {
+            let rewritten = rewrite(&file.read()?, |p, rule| {
+                let matched = p == ptype
+                    && field_values.iter().enumerate().all(|(i, value)| {
+                        value.is_empty()
+                            || rule.get(field_index + i) == Some(value)
+                    });
+                if matched {
+                    LineEdit::Remove
+                } else {
+                    LineEdit::Keep
+                }
+            });
+
+            match rewritten {
+                Some(text) => {
+                    file.replace(&text)?;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        })
+        .await
     }
 
     fn is_filtered(&self) -> bool {
         self.is_filtered
     }
+
+    fn as_updatable_mut(&mut self) -> Option<&mut dyn UpdatableAdapter> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl<P> UpdatableAdapter for FileAdapter<P>
+where
+    P: AsRef<ioPath> + Send + Sync,
+{
+    async fn update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> Result<bool> {
+        self.update_policies(sec, ptype, vec![old_rule], vec![new_rule])
+            .await
+    }
+
+    async fn update_policies(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        if old_rules.len() != new_rules.len() {
+            return Ok(false);
+        }
+
+        let ptype = ptype.to_owned();
+        self.with_locked_file(move |file| {
+            let text = file.read()?;
+            let replacements: HashMap<&[String], &[String]> = old_rules
+                .iter()
+                .map(Vec::as_slice)
+                .zip(new_rules.iter().map(Vec::as_slice))
+                .collect();
+            let mut found = HashSet::new();
+            let mut kept = HashSet::new();
+            let rewritten = rewrite(&text, |p, rule| {
+                if p != ptype {
+                    return LineEdit::Keep;
+                }
+                match replacements.get(rule) {
+                    Some(new_rule) => {
+                        found.insert(rule.to_vec());
+                        LineEdit::Replace(policy_line(&ptype, new_rule))
+                    }
+                    None => {
+                        kept.insert(rule.to_vec());
+                        LineEdit::Keep
+                    }
+                }
+            });
+
+            // refuse to update missing rules or to duplicate existing ones
+            if found.len() < replacements.len()
+                || new_rules.iter().any(|x| kept.contains(x))
+            {
+                return Ok(false);
+            }
+            if let Some(text) = rewritten {
+                file.replace(&text)?;
+            }
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn update_filtered_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        new_rules: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        if !self
+            .remove_filtered_policy(sec, ptype, field_index, field_values)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        self.add_policies(sec, ptype, new_rules).await
+    }
+}
+
+/// Runs `f` on the policy file at `path` once locked, on the blocking
+/// thread pool.
+pub(crate) async fn with_locked_file<T, F>(path: PathBuf, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(LockedFile) -> Result<T> + Send + 'static,
+{
+    let task = move || f(LockedFile::lock(path)?);
+
+    #[cfg(feature = "runtime-tokio")]
+    {
+        tokio::task::spawn_blocking(task)
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    #[cfg(feature = "runtime-async-std")]
+    {
+        async_std::task::spawn_blocking(task).await
+    }
+}
+
+/// A policy file held under the advisory lock of its sibling `.lock` file,
+/// released when dropped.
+pub(crate) struct LockedFile {
+    path: PathBuf,
+    _lock: File,
+}
+
+impl LockedFile {
+    fn lock(path: PathBuf) -> io::Result<Self> {
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(sibling(&path, "lock")?)?;
+        lock.lock_exclusive()?;
+
+        Ok(LockedFile { path, _lock: lock })
+    }
+
+    pub(crate) fn read(&self) -> io::Result<String> {
+        match fs::read_to_string(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            res => res,
+        }
+    }
+
+    /// Appends `lines` to the file whose current contents are `text`.
+    fn append(&self, text: &str, lines: &[String]) -> io::Result<()> {
+        let mut buf = String::new();
+        for line in lines {
+            if !buf.is_empty() || !(text.is_empty() || text.ends_with('\n')) {
+                buf.push('\n');
+            }
+            buf.push_str(line);
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(buf.as_bytes())
+    }
+
+    /// Replaces the contents by renaming a temporary file over the policy,
+    /// so that readers never see a partially written file.
+    pub(crate) fn replace(&self, contents: &str) -> io::Result<()> {
+        let tmp = sibling(&self.path, "tmp")?;
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+enum LineEdit {
+    Keep,
+    Remove,
+    Replace(String),
+}
+
+/// Applies `edit` to the rule of every line of `text`, returns `None` when
+/// nothing changed.
+fn rewrite(
+    text: &str,
+    mut edit: impl FnMut(&str, &[String]) -> LineEdit,
+) -> Option<String> {
+    let mut changed = false;
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let edit = match parse_csv_line(line) {
+            Some(tokens) => edit(&tokens[0], &tokens[1..]),
+            None => LineEdit::Keep,
+        };
+        match edit {
+            LineEdit::Keep => lines.push(line.to_owned()),
+            LineEdit::Remove => changed = true,
+            LineEdit::Replace(line) => {
+                changed = true;
+                lines.push(line);
+            }
+        }
+    }
+
+    changed.then(|| {
+        let mut out = lines.join("\n");
+        if text.ends_with('\n') && !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    })
+}
+
+fn sibling(path: &Path, extension: &str) -> io::Result<PathBuf> {
+    let mut name = path
+        .file_name()
+        .ok_or_else(|| {
+            io::Error::other(format!(
+                "invalid policy file path `{}`",
+                path.display()
+            ))
+        })?
+        .to_os_string();
+    name.push(".");
+    name.push(extension);
+
+    Ok(path.with_file_name(name))
+}
+
+/// Formats a rule as a CSV line, quoting the values containing commas.
+fn policy_line(ptype: &str, rule: &[String]) -> String {
+    let mut line = ptype.to_owned();
+    for value in rule {
+        line.push_str(", ");
+        if value.contains(',') {
+            line.push('"');
+            line.push_str(value);
+            line.push('"');
+        } else {
+            line.push_str(value);
+        }
+    }
+    line
 }
 
 fn load_policy_line(line: String, m: &mut dyn Model) {
@@ -252,42 +583,181 @@ fn load_policy_line(line: String, m: &mut dyn Model) {
 fn load_filtered_policy_line(
     line: String,
     m: &mut dyn Model,
-    f: &Filter<'_>,
+    f: &CompiledFilter<'_>,
 ) -> bool {
     if line.is_empty() || line.starts_with('#') {
         return false;
     }
 
     if let Some(tokens) = parse_csv_line(line) {
-        let key = &tokens[0];
+        f.load_rule(m, &tokens[0], tokens[1..].to_vec())
+    } else {
+        false
+    }
+}
 
-        let mut is_filtered = false;
-        if let Some(ref sec) = key.chars().next().map(|x| x.to_string()) {
-            if sec == "p" {
-                for (i, rule) in f.p.iter().enumerate() {
-                    if !rule.is_empty() && rule != &tokens[i + 1] {
-                        is_filtered = true;
-                    }
-                }
-            }
-            if sec == "g" {
-                for (i, rule) in f.g.iter().enumerate() {
-                    if !rule.is_empty() && rule != &tokens[i + 1] {
-                        is_filtered = true;
-                    }
-                }
-            }
-            if !is_filtered {
-                if let Some(ast_map) = m.get_mut_model().get_mut(sec) {
-                    if let Some(ast) = ast_map.get_mut(key) {
-                        ast.policy.insert(tokens[1..].to_vec());
-                    }
-                }
-            }
+/// Copy of an example policy in the temporary directory, removed on drop,
+/// for the tests persisting changes through a `FileAdapter`.
+#[cfg(test)]
+pub(crate) struct TempPolicy(String);
+
+#[cfg(test)]
+impl TempPolicy {
+    pub(crate) fn copy(example: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "casbin-{}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed),
+            Path::new(example).file_name().unwrap().to_string_lossy()
+        ));
+        fs::copy(example, &path).unwrap();
+
+        TempPolicy(path.to_string_lossy().into_owned())
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.0
+    }
+
+    pub(crate) fn adapter(&self) -> FileAdapter<String> {
+        FileAdapter::new(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPolicy {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+        if let Ok(lock) = sibling(Path::new(&self.0), "lock") {
+            let _ = fs::remove_file(lock);
         }
+    }
+}
 
-        is_filtered
-    } else {
-        false
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn rule(values: &[&str]) -> Vec<String> {
+        values.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[cfg_attr(feature = "runtime-async-std", async_std::test)]
+    #[cfg_attr(feature = "runtime-tokio", tokio::test)]
+    async fn test_auto_save() {
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let mut e = Enforcer::new("examples/rbac_model.conf", policy.adapter())
+            .await
+            .unwrap();
+
+        assert!(e.add_policy(rule(&["eve", "data3", "read"])).await.unwrap());
+        assert!(!e.add_policy(rule(&["eve", "data3", "read"])).await.unwrap());
+        assert!(e
+            .remove_policy(rule(&["bob", "data2", "write"]))
+            .await
+            .unwrap());
+        assert!(e
+            .update_policy(
+                rule(&["alice", "data1", "read"]),
+                rule(&["alice", "data1", "write"]),
+            )
+            .await
+            .unwrap());
+        assert!(e
+            .remove_filtered_grouping_policy(1, rule(&["data2_admin"]))
+            .await
+            .unwrap());
+
+        assert_eq!(
+            fs::read_to_string(policy.path()).unwrap(),
+            "p, alice, data1, write\n\
+             p, data2_admin, data2, read\n\
+             p, data2_admin, data2, write\n\
+             p, eve, data3, read"
+        );
+
+        let e2 = Enforcer::new("examples/rbac_model.conf", policy.adapter())
+            .await
+            .unwrap();
+        assert_eq!(e.get_policy(), e2.get_policy());
+        assert!(e2.get_grouping_policy().is_empty());
+    }
+
+    #[cfg_attr(feature = "runtime-async-std", async_std::test)]
+    #[cfg_attr(feature = "runtime-tokio", tokio::test)]
+    async fn test_remove_keeps_layout() {
+        let policy = TempPolicy::copy("examples/basic_policy.csv");
+        fs::write(
+            policy.path(),
+            "# users\np, alice, data1, read\n\np, \"bob, jr\", data2, write\n",
+        )
+        .unwrap();
+        let mut adapter = policy.adapter();
+
+        assert!(adapter
+            .remove_policy("p", "p", rule(&["alice", "data1", "read"]))
+            .await
+            .unwrap());
+        assert!(!adapter
+            .remove_policy("p", "p", rule(&["alice", "data1", "read"]))
+            .await
+            .unwrap());
+        assert!(adapter
+            .add_policy("p", "p", rule(&["carol, sr", "data3", "read"]))
+            .await
+            .unwrap());
+
+        assert_eq!(
+            fs::read_to_string(policy.path()).unwrap(),
+            "# users\n\np, \"bob, jr\", data2, write\n\
+             p, \"carol, sr\", data3, read"
+        );
+    }
+
+    #[cfg_attr(feature = "runtime-async-std", async_std::test)]
+    #[cfg_attr(feature = "runtime-tokio", tokio::test)]
+    async fn test_concurrent_writers() {
+        let policy = TempPolicy::copy("examples/basic_policy.csv");
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let mut adapter = policy.adapter();
+                std::thread::spawn(move || {
+                    #[cfg(feature = "runtime-async-std")]
+                    let rt = async_std::task::block_on;
+                    #[cfg(feature = "runtime-tokio")]
+                    let rt = |f| {
+                        tokio::runtime::Builder::new_current_thread()
+                            .build()
+                            .unwrap()
+                            .block_on(f)
+                    };
+                    rt(async move {
+                        let user = format!("user{}", i);
+                        adapter
+                            .add_policy(
+                                "p",
+                                "p",
+                                rule(&[&user, "data", "read"]),
+                            )
+                            .await
+                            .unwrap()
+                    })
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+
+        let mut m = DefaultModel::from_file("examples/basic_model.conf")
+            .await
+            .unwrap();
+        policy.adapter().load_policy(&mut m).await.unwrap();
+        assert_eq!(m.get_policy("p", "p").len(), 10);
     }
 }
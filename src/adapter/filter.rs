@@ -0,0 +1,518 @@
+use crate::{error::PolicyError, model::Model, Result};
+
+use std::collections::{HashMap, HashSet};
+
+/// Compatibility filter matching the rules of `p` and `g` positionally,
+/// an empty value matches anything. Prefer [`PolicyFilter`].
+#[derive(Clone)]
+pub struct Filter<'a> {
+    pub p: Vec<&'a str>,
+    pub g: Vec<&'a str>,
+}
+
+/// A field of a policy definition, by its position or by its name in
+/// `policy_definition`, e.g. `dom`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Field {
+    Index(usize),
+    Name(String),
+}
+
+impl From<usize> for Field {
+    fn from(i: usize) -> Self {
+        Field::Index(i)
+    }
+}
+
+impl From<&str> for Field {
+    fn from(name: &str) -> Self {
+        Field::Name(name.to_owned())
+    }
+}
+
+/// How the value of a field is matched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldMatch {
+    /// Equal to one of the values.
+    In(HashSet<String>),
+    Prefix(String),
+    /// A glob where `*` matches any characters and `?` a single one.
+    Glob(String),
+}
+
+impl FieldMatch {
+    pub fn one_of<I, S>(values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        FieldMatch::In(values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        match self {
+            FieldMatch::In(values) => values.contains(value),
+            FieldMatch::Prefix(prefix) => value.starts_with(prefix.as_str()),
+            FieldMatch::Glob(pattern) => glob_match(pattern, value),
+        }
+    }
+}
+
+/// Selects the rules loaded by `load_filtered_policy`.
+///
+/// A rule is loaded when its `ptype` is one of [`ptypes`] (any by default)
+/// and it satisfies every condition given for its `ptype` with [`field`].
+///
+/// ```
+/// use casbin::{FieldMatch, PolicyFilter};
+///
+/// let filter = PolicyFilter::new()
+///     .ptypes(["p", "p2", "g"])
+///     .field("p", "dom", FieldMatch::one_of(["t1", "t2"]))
+///     .field("p", "obj", FieldMatch::Prefix("/api/".to_owned()))
+///     .field("g", 2, FieldMatch::one_of(["t1", "t2"]));
+/// ```
+///
+/// [`ptypes`]: PolicyFilter::ptypes
+/// [`field`]: PolicyFilter::field
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PolicyFilter {
+    ptypes: Option<HashSet<String>>,
+    conditions: HashMap<String, Vec<(Field, FieldMatch)>>,
+    // positional conditions of a [`Filter`], applying to every ptype of
+    // their section
+    sections: HashMap<String, Vec<(usize, FieldMatch)>>,
+}
+
+impl PolicyFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only loads the rules of these `ptype`s.
+    pub fn ptypes<I, S>(mut self, ptypes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ptypes = Some(ptypes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only loads the `ptype` rules whose `field` satisfies `matcher`.
+    pub fn field(
+        mut self,
+        ptype: &str,
+        field: impl Into<Field>,
+        matcher: FieldMatch,
+    ) -> Self {
+        self.conditions
+            .entry(ptype.to_owned())
+            .or_default()
+            .push((field.into(), matcher));
+        self
+    }
+
+    /// The `ptype`s whose rules are loaded, `None` for any.
+    pub fn get_ptypes(&self) -> Option<&HashSet<String>> {
+        self.ptypes.as_ref()
+    }
+
+    /// The conditions given with [`field`](PolicyFilter::field), by `ptype`.
+    pub fn get_conditions(&self) -> &HashMap<String, Vec<(Field, FieldMatch)>> {
+        &self.conditions
+    }
+
+    /// The conditions of a positional [`Filter`] this filter was made from,
+    /// by section. They hold for every `ptype` of their section.
+    pub fn get_section_conditions(
+        &self,
+    ) -> &HashMap<String, Vec<(usize, FieldMatch)>> {
+        &self.sections
+    }
+
+    /// Whether the `ptype` rule `rule` is selected, with field names
+    /// resolved against the definitions of `m`.
+    pub fn matches(
+        &self,
+        m: &dyn Model,
+        ptype: &str,
+        rule: &[String],
+    ) -> Result<bool> {
+        Ok(self.compile(m)?.matches(ptype, rule))
+    }
+
+    // The values every loadable `ptype` of `sec` must have, by position,
+    // which is what a positional `Filter` can tell apart. Other conditions
+    // are left out, so it selects at least the rules of `self`.
+    pub(crate) fn to_positional(
+        &self,
+        m: &dyn Model,
+        sec: &str,
+    ) -> Result<Vec<String>> {
+        let compiled = self.compile(m)?;
+        let mut shared: Option<HashMap<usize, &str>> = None;
+
+        let ptypes = m.get_model().get(sec).into_iter().flat_map(|x| x.keys());
+        for ptype in ptypes {
+            if compiled.ptypes.is_some_and(|x| !x.contains(ptype)) {
+                continue;
+            }
+            let exact = compiled.exact(ptype);
+            shared = Some(match shared {
+                None => exact,
+                Some(values) => values
+                    .into_iter()
+                    .filter(|(i, value)| exact.get(i) == Some(value))
+                    .collect(),
+            });
+        }
+
+        let shared = shared.unwrap_or_default();
+        let len = shared.keys().max().map_or(0, |i| i + 1);
+        Ok((0..len)
+            .map(|i| shared.get(&i).copied().unwrap_or_default().to_owned())
+            .collect())
+    }
+
+    // Drops the rules of `m` that aren't selected
+    pub(crate) fn retain(&self, m: &mut dyn Model) -> Result<()> {
+        let compiled = self.compile(m)?;
+        for sec in ["p", "g"] {
+            let Some(ast_map) = m.get_mut_model().get_mut(sec) else {
+                continue;
+            };
+            for (ptype, ast) in ast_map.iter_mut() {
+                ast.get_mut_policy()
+                    .retain(|rule| compiled.matches(ptype, rule));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the field names against the definitions of `m`.
+    pub(crate) fn compile(&self, m: &dyn Model) -> Result<CompiledFilter<'_>> {
+        let mut conditions = HashMap::new();
+        for (ptype, fields) in self.conditions.iter() {
+            let resolved = fields
+                .iter()
+                .map(|(field, matcher)| {
+                    Ok((field_index(m, ptype, field)?, matcher))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            conditions.insert(ptype.as_str(), resolved);
+        }
+
+        Ok(CompiledFilter {
+            ptypes: self.ptypes.as_ref(),
+            conditions,
+            sections: &self.sections,
+        })
+    }
+}
+
+impl From<Filter<'_>> for PolicyFilter {
+    fn from(f: Filter<'_>) -> Self {
+        let mut filter = PolicyFilter::new();
+        for (sec, values) in [("p", f.p), ("g", f.g)] {
+            let fields = values
+                .into_iter()
+                .enumerate()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(i, value)| (i, FieldMatch::one_of([value])))
+                .collect();
+            filter.sections.insert(sec.to_owned(), fields);
+        }
+        filter
+    }
+}
+
+/// A [`PolicyFilter`] with its fields resolved to positions.
+pub(crate) struct CompiledFilter<'a> {
+    ptypes: Option<&'a HashSet<String>>,
+    conditions: HashMap<&'a str, Vec<(usize, &'a FieldMatch)>>,
+    sections: &'a HashMap<String, Vec<(usize, FieldMatch)>>,
+}
+
+impl CompiledFilter<'_> {
+    pub(crate) fn matches(&self, ptype: &str, rule: &[String]) -> bool {
+        if self.ptypes.is_some_and(|ptypes| !ptypes.contains(ptype)) {
+            return false;
+        }
+
+        let satisfied = |i: usize, matcher: &FieldMatch| {
+            rule.get(i).is_some_and(|value| matcher.is_match(value))
+        };
+        let sec = section(ptype);
+
+        self.conditions.get(ptype).is_none_or(|fields| {
+            fields.iter().all(|(i, matcher)| satisfied(*i, matcher))
+        }) && self.sections.get(sec).is_none_or(|fields| {
+            fields.iter().all(|(i, matcher)| satisfied(*i, matcher))
+        })
+    }
+
+    // the fields of `ptype` that must equal a single value
+    fn exact(&self, ptype: &str) -> HashMap<usize, &str> {
+        let conditions = self.conditions.get(ptype).into_iter().flatten();
+        let sections = self.sections.get(section(ptype)).into_iter().flatten();
+
+        conditions
+            .map(|(i, matcher)| (*i, *matcher))
+            .chain(sections.map(|(i, matcher)| (*i, matcher)))
+            .filter_map(|(i, matcher)| match matcher {
+                FieldMatch::In(values) if values.len() == 1 => {
+                    values.iter().next().map(|value| (i, value.as_str()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Adds `rule` to the model if it matches, returns whether it was
+    /// filtered out.
+    pub(crate) fn load_rule(
+        &self,
+        m: &mut dyn Model,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> bool {
+        if !self.matches(ptype, &rule) {
+            return true;
+        }
+
+        let sec = section(ptype);
+        if let Some(ast) = m
+            .get_mut_model()
+            .get_mut(sec)
+            .and_then(|ast_map| ast_map.get_mut(ptype))
+        {
+            ast.get_mut_policy().insert(rule);
+        }
+        false
+    }
+}
+
+fn field_index(m: &dyn Model, ptype: &str, field: &Field) -> Result<usize> {
+    match field {
+        Field::Index(i) => Ok(*i),
+        Field::Name(name) => {
+            let token = format!("{}_{}", ptype, name);
+            m.get_model()
+                .get(section(ptype))
+                .and_then(|ast_map| ast_map.get(ptype))
+                .and_then(|ast| ast.tokens.iter().position(|x| *x == token))
+                .ok_or_else(|| {
+                    PolicyError::FieldNotFound(ptype.to_owned(), name.clone())
+                        .into()
+                })
+        }
+    }
+}
+
+fn section(ptype: &str) -> &str {
+    ptype.get(..1).unwrap_or_default()
+}
+
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let (pattern, value): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), value.chars().collect());
+    let (mut p, mut v) = (0, 0);
+    // position of the last `*` and of the value when it was reached
+    let mut star = None;
+
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, v));
+            p += 1;
+        } else if let Some((sp, sv)) = star {
+            p = sp + 1;
+            v = sv + 1;
+            star = Some((sp, sv + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, Adapter};
+
+    use async_trait::async_trait;
+
+    // an adapter only knowing positional filters, keeping the last one
+    struct PositionalAdapter(FileAdapter<&'static str>, Vec<Vec<String>>);
+
+    #[async_trait]
+    impl Adapter for PositionalAdapter {
+        async fn load_policy(&mut self, m: &mut dyn Model) -> Result<()> {
+            self.0.load_policy(m).await
+        }
+
+        async fn load_filtered_policy<'a>(
+            &mut self,
+            m: &mut dyn Model,
+            f: Filter<'a>,
+        ) -> Result<()> {
+            self.1 = [&f.p, &f.g]
+                .iter()
+                .map(|x| x.iter().map(|v| v.to_string()).collect())
+                .collect();
+            self.0.load_filtered_policy(m, f).await
+        }
+
+        async fn save_policy(&mut self, m: &mut dyn Model) -> Result<()> {
+            self.0.save_policy(m).await
+        }
+
+        async fn clear_policy(&mut self) -> Result<()> {
+            self.0.clear_policy().await
+        }
+
+        fn is_filtered(&self) -> bool {
+            self.0.is_filtered()
+        }
+
+        async fn add_policy(
+            &mut self,
+            sec: &str,
+            ptype: &str,
+            rule: Vec<String>,
+        ) -> Result<bool> {
+            self.0.add_policy(sec, ptype, rule).await
+        }
+
+        async fn add_policies(
+            &mut self,
+            sec: &str,
+            ptype: &str,
+            rules: Vec<Vec<String>>,
+        ) -> Result<bool> {
+            self.0.add_policies(sec, ptype, rules).await
+        }
+
+        async fn remove_policy(
+            &mut self,
+            sec: &str,
+            ptype: &str,
+            rule: Vec<String>,
+        ) -> Result<bool> {
+            self.0.remove_policy(sec, ptype, rule).await
+        }
+
+        async fn remove_policies(
+            &mut self,
+            sec: &str,
+            ptype: &str,
+            rules: Vec<Vec<String>>,
+        ) -> Result<bool> {
+            self.0.remove_policies(sec, ptype, rules).await
+        }
+
+        async fn remove_filtered_policy(
+            &mut self,
+            sec: &str,
+            ptype: &str,
+            field_index: usize,
+            field_values: Vec<String>,
+        ) -> Result<bool> {
+            self.0
+                .remove_filtered_policy(sec, ptype, field_index, field_values)
+                .await
+        }
+    }
+
+    fn domain_filter() -> PolicyFilter {
+        PolicyFilter::new()
+            .field("p", "dom", FieldMatch::one_of(["domain1"]))
+            .field("p", "act", FieldMatch::Prefix("wr".to_owned()))
+            .field("g", 2, FieldMatch::one_of(["domain2"]))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_matches() {
+        let m =
+            DefaultModel::from_file("examples/rbac_with_domains_model.conf")
+                .await
+                .unwrap();
+        let rule = |v: &[&str]| -> Vec<String> {
+            v.iter().map(|x| x.to_string()).collect()
+        };
+
+        let f = domain_filter();
+        assert!(f
+            .matches(&m, "p", &rule(&["admin", "domain1", "data1", "write"]))
+            .unwrap());
+        assert!(!f
+            .matches(&m, "p", &rule(&["admin", "domain1", "data1", "read"]))
+            .unwrap());
+        assert!(f
+            .matches(&m, "g", &rule(&["bob", "admin", "domain2"]))
+            .unwrap());
+        assert!(PolicyFilter::new()
+            .field("p", "tenant", FieldMatch::one_of(["domain1"]))
+            .matches(&m, "p", &rule(&["admin", "domain1", "data1", "read"]))
+            .is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_default_load_policy_with_filter() {
+        let mut m =
+            DefaultModel::from_file("examples/rbac_with_domains_model.conf")
+                .await
+                .unwrap();
+        let mut a = PositionalAdapter(
+            FileAdapter::new("examples/rbac_with_domains_policy.csv"),
+            vec![],
+        );
+
+        a.load_policy_with_filter(&mut m, domain_filter())
+            .await
+            .unwrap();
+
+        assert_eq!(vec![vec!["", "domain1"], vec!["", "", "domain2"]], a.1);
+        assert!(a.is_filtered());
+        assert_eq!(
+            vec![vec!["admin", "domain1", "data1", "write"]],
+            m.get_policy("p", "p")
+        );
+        assert_eq!(
+            vec![vec!["bob", "admin", "domain2"]],
+            m.get_policy("g", "g")
+        );
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("/api/*", "/api/users/1"));
+        assert!(glob_match("*.csv", "policy.csv"));
+        assert!(glob_match("t?", "t1"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("t?", "t10"));
+        assert!(!glob_match("/api/*/edit", "/api/users"));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    adapter::{Adapter, Filter},
+    adapter::{Adapter, Filter, PolicyFilter, UpdatableAdapter},
     model::Model,
     Result,
 };
@@ -7,6 +7,8 @@ use crate::{
 use async_trait::async_trait;
 use hashlink::LinkedHashSet;
 
+use std::collections::HashMap;
+
 #[derive(Default)]
 pub struct MemoryAdapter {
     policy: LinkedHashSet<Vec<String>>,
@@ -37,34 +39,18 @@ impl Adapter for MemoryAdapter {
         m: &mut dyn Model,
         f: Filter<'a>,
     ) -> Result<()> {
-        for line in self.policy.iter() {
-            let sec = &line[0];
-            let ptype = &line[1];
-            let rule = line[1..].to_vec().clone();
-            let mut is_filtered = false;
-
-            if sec == "p" {
-                for (i, r) in f.p.iter().enumerate() {
-                    if !r.is_empty() && r != &rule[i + 1] {
-                        is_filtered = true;
-                    }
-                }
-            }
-            if sec == "g" {
-                for (i, r) in f.g.iter().enumerate() {
-                    if !r.is_empty() && r != &rule[i + 1] {
-                        is_filtered = true;
-                    }
-                }
-            }
+        self.load_policy_with_filter(m, f.into()).await
+    }
 
-            if !is_filtered {
-                if let Some(ast_map) = m.get_mut_model().get_mut(sec) {
-                    if let Some(ast) = ast_map.get_mut(ptype) {
-                        ast.get_mut_policy().insert(rule);
-                    }
-                }
-            } else {
+    async fn load_policy_with_filter(
+        &mut self,
+        m: &mut dyn Model,
+        f: PolicyFilter,
+    ) -> Result<()> {
+        let filter = f.compile(m)?;
+        self.is_filtered = false;
+        for line in self.policy.iter() {
+            if filter.load_rule(m, &line[1], line[2..].to_vec()) {
                 self.is_filtered = true;
             }
         }
@@ -231,4 +217,82 @@ impl Adapter for MemoryAdapter {
     fn is_filtered(&self) -> bool {
         self.is_filtered
     }
+
+    fn as_updatable_mut(&mut self) -> Option<&mut dyn UpdatableAdapter> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl UpdatableAdapter for MemoryAdapter {
+    async fn update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> Result<bool> {
+        self.update_policies(sec, ptype, vec![old_rule], vec![new_rule])
+            .await
+    }
+
+    async fn update_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        if old_rules.len() != new_rules.len() {
+            return Ok(false);
+        }
+
+        let prefix = |mut rule: Vec<String>| {
+            rule.insert(0, ptype.to_owned());
+            rule.insert(0, sec.to_owned());
+            rule
+        };
+        let replacements: HashMap<Vec<String>, Vec<String>> = old_rules
+            .into_iter()
+            .map(prefix)
+            .zip(new_rules.into_iter().map(prefix))
+            .collect();
+
+        for rule in replacements.keys() {
+            if !self.policy.contains(rule) {
+                return Ok(false);
+            }
+        }
+
+        // rebuild the set so that updated rules keep their position
+        let policy: LinkedHashSet<Vec<String>> = self
+            .policy
+            .iter()
+            .map(|rule| replacements.get(rule).unwrap_or(rule).clone())
+            .collect();
+        if policy.len() != self.policy.len() {
+            return Ok(false);
+        }
+        self.policy = policy;
+
+        Ok(true)
+    }
+
+    async fn update_filtered_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        new_rules: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        if !self
+            .remove_filtered_policy(sec, ptype, field_index, field_values)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        self.add_policies(sec, ptype, new_rules).await
+    }
 }
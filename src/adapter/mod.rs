@@ -1,12 +1,20 @@
 use async_trait::async_trait;
 
+pub mod document_adapter;
+pub mod filter;
 pub mod memory_adapter;
 pub mod null_adapter;
 pub mod string_adapter;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub mod file_adapter;
+#[cfg(test)]
+pub(crate) mod testing;
 
+pub use document_adapter::{
+    DocumentAdapter, DocumentFormat, JsonAdapter, JsonFormat, PolicyRecord,
+};
+pub use filter::{Field, FieldMatch, Filter, PolicyFilter};
 pub use memory_adapter::MemoryAdapter;
 pub use null_adapter::NullAdapter;
 pub use string_adapter::StringAdapter;
@@ -14,13 +22,10 @@ pub use string_adapter::StringAdapter;
 #[cfg(not(target_arch = "wasm32"))]
 pub use file_adapter::FileAdapter;
 
-use crate::{model::Model, Result};
+#[cfg(feature = "yaml")]
+pub use document_adapter::{YamlAdapter, YamlFormat};
 
-#[derive(Clone)]
-pub struct Filter<'a> {
-    pub p: Vec<&'a str>,
-    pub g: Vec<&'a str>,
-}
+use crate::{model::Model, Result};
 
 #[async_trait]
 pub trait Adapter: Send + Sync {
@@ -30,9 +35,35 @@ pub trait Adapter: Send + Sync {
         m: &mut dyn Model,
         f: Filter<'a>,
     ) -> Result<()>;
+    /// Loads the rules selected by `f`.
+    ///
+    /// By default the values `f` requires at fixed positions are given to
+    /// [`load_filtered_policy`](Adapter::load_filtered_policy) as a
+    /// positional [`Filter`], and the loaded rules `f` doesn't select are
+    /// dropped afterwards.
+    async fn load_policy_with_filter(
+        &mut self,
+        m: &mut dyn Model,
+        f: PolicyFilter,
+    ) -> Result<()> {
+        let p = f.to_positional(m, "p")?;
+        let g = f.to_positional(m, "g")?;
+        let positional = Filter {
+            p: p.iter().map(String::as_str).collect(),
+            g: g.iter().map(String::as_str).collect(),
+        };
+        self.load_filtered_policy(m, positional).await?;
+
+        f.retain(m)
+    }
     async fn save_policy(&mut self, m: &mut dyn Model) -> Result<()>;
     async fn clear_policy(&mut self) -> Result<()>;
     fn is_filtered(&self) -> bool;
+    /// Returns the adapter as an [`UpdatableAdapter`] if it supports
+    /// updating rules in place.
+    fn as_updatable_mut(&mut self) -> Option<&mut dyn UpdatableAdapter> {
+        None
+    }
     async fn add_policy(
         &mut self,
         sec: &str,
@@ -65,3 +96,31 @@ pub trait Adapter: Send + Sync {
         field_values: Vec<String>,
     ) -> Result<bool>;
 }
+
+/// UpdatableAdapter is implemented by adapters which are able to replace
+/// stored rules in a single operation instead of a remove followed by an add.
+#[async_trait]
+pub trait UpdatableAdapter: Adapter {
+    async fn update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> Result<bool>;
+    async fn update_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> Result<bool>;
+    async fn update_filtered_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        new_rules: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool>;
+}
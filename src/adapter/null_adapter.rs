@@ -1,5 +1,5 @@
 use crate::{
-    adapter::{Adapter, Filter},
+    adapter::{Adapter, Filter, PolicyFilter, UpdatableAdapter},
     model::Model,
     Result,
 };
@@ -22,6 +22,14 @@ impl Adapter for NullAdapter {
         Ok(())
     }
 
+    async fn load_policy_with_filter(
+        &mut self,
+        _m: &mut dyn Model,
+        _f: PolicyFilter,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     async fn save_policy(&mut self, _m: &mut dyn Model) -> Result<()> {
         Ok(())
     }
@@ -79,4 +87,42 @@ impl Adapter for NullAdapter {
     fn is_filtered(&self) -> bool {
         false
     }
+
+    fn as_updatable_mut(&mut self) -> Option<&mut dyn UpdatableAdapter> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl UpdatableAdapter for NullAdapter {
+    async fn update_policy(
+        &mut self,
+        _sec: &str,
+        _ptype: &str,
+        _old_rule: Vec<String>,
+        _new_rule: Vec<String>,
+    ) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn update_policies(
+        &mut self,
+        _sec: &str,
+        _ptype: &str,
+        _old_rules: Vec<Vec<String>>,
+        _new_rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn update_filtered_policies(
+        &mut self,
+        _sec: &str,
+        _ptype: &str,
+        _new_rules: Vec<Vec<String>>,
+        _field_index: usize,
+        _field_values: Vec<String>,
+    ) -> Result<bool> {
+        Ok(true)
+    }
 }
@@ -15,7 +15,7 @@
 use crate::{
     error::{AdapterError, ModelError},
     util::parse_csv_line,
-    Adapter, Filter, Model, Result,
+    Adapter, Filter, Model, PolicyFilter, Result,
 };
 use async_trait::async_trait;
 use std::fmt::Write;
@@ -77,35 +77,22 @@ impl Adapter for StringAdapter {
         m: &mut dyn Model,
         f: Filter<'a>,
     ) -> Result<()> {
-        let policies = self.policy.split("\n");
-        for line in policies {
+        self.load_policy_with_filter(m, f.into()).await
+    }
+
+    async fn load_policy_with_filter(
+        &mut self,
+        m: &mut dyn Model,
+        f: PolicyFilter,
+    ) -> Result<()> {
+        let filter = f.compile(m)?;
+        self.is_filtered = false;
+        for line in self.policy.split("\n") {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
             if let Some(tokens) = parse_csv_line(line) {
-                let sec = &tokens[0];
-                let ptype = &tokens[1];
-                let rule = tokens[1..].to_vec().clone();
-                let mut is_filtered = false;
-
-                if sec == "p" {
-                    for (i, r) in f.p.iter().enumerate() {
-                        if !r.is_empty() && r != &rule[i + 1] {
-                            is_filtered = true;
-                        }
-                    }
-                }
-                if sec == "g" {
-                    for (i, r) in f.g.iter().enumerate() {
-                        if !r.is_empty() && r != &rule[i + 1] {
-                            is_filtered = true;
-                        }
-                    }
-                }
-                if !is_filtered {
-                    if let Some(ast_map) = m.get_mut_model().get_mut(sec) {
-                        if let Some(ast) = ast_map.get_mut(ptype) {
-                            ast.get_mut_policy().insert(rule);
-                        }
-                    }
-                } else {
+                if filter.load_rule(m, &tokens[0], tokens[1..].to_vec()) {
                     self.is_filtered = true;
                 }
             }
@@ -232,7 +219,7 @@ fn load_policy_line(line: &str, m: &mut dyn Model) {
 #[cfg(test)]
 mod tests {
     use super::StringAdapter;
-    use crate::{Adapter, CoreApi, Filter};
+    use crate::{Adapter, CoreApi, Filter, Model};
     use crate::{DefaultModel, Enforcer};
 
     #[cfg(target_arch = "wasm32")]
@@ -340,10 +327,14 @@ mod tests {
         };
 
         adapter
-            .load_filtered_policy(&mut model, filter)
+            .load_policy_with_filter(&mut model, filter.into())
             .await
             .unwrap();
 
         assert!(adapter.is_filtered());
+        assert_eq!(
+            model.get_policy("p", "p"),
+            vec![vec!["alice", "data1", "read"]]
+        );
     }
 }
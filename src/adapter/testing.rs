@@ -0,0 +1,132 @@
+//! Fixtures shared by the tests of the enforcer APIs.
+
+use crate::{
+    adapter::{Adapter, Filter, MemoryAdapter, PolicyFilter},
+    model::Model,
+    Result,
+};
+
+use async_trait::async_trait;
+
+#[cfg(feature = "watcher")]
+use crate::{emitter::EventData, watcher::Watcher};
+#[cfg(feature = "watcher")]
+use std::sync::{Arc, Mutex};
+
+pub(crate) fn to_owned(v: Vec<&str>) -> Vec<String> {
+    v.into_iter().map(|x| x.to_owned()).collect()
+}
+
+/// A memory adapter without in-place updates, refusing to store any rule
+/// holding the value it was made with.
+pub(crate) struct PickyAdapter(MemoryAdapter, &'static str);
+
+impl PickyAdapter {
+    pub(crate) fn refusing(value: &'static str) -> Self {
+        PickyAdapter(MemoryAdapter::default(), value)
+    }
+}
+
+#[async_trait]
+impl Adapter for PickyAdapter {
+    async fn load_policy(&mut self, m: &mut dyn Model) -> Result<()> {
+        self.0.load_policy(m).await
+    }
+
+    async fn load_filtered_policy<'a>(
+        &mut self,
+        m: &mut dyn Model,
+        f: Filter<'a>,
+    ) -> Result<()> {
+        self.0.load_filtered_policy(m, f).await
+    }
+
+    async fn load_policy_with_filter(
+        &mut self,
+        m: &mut dyn Model,
+        f: PolicyFilter,
+    ) -> Result<()> {
+        self.0.load_policy_with_filter(m, f).await
+    }
+
+    async fn save_policy(&mut self, m: &mut dyn Model) -> Result<()> {
+        self.0.save_policy(m).await
+    }
+
+    async fn clear_policy(&mut self) -> Result<()> {
+        self.0.clear_policy().await
+    }
+
+    fn is_filtered(&self) -> bool {
+        self.0.is_filtered()
+    }
+
+    async fn add_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> Result<bool> {
+        self.add_policies(sec, ptype, vec![rule]).await
+    }
+
+    async fn add_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        if rules.iter().flatten().any(|x| x == self.1) {
+            return Ok(false);
+        }
+        self.0.add_policies(sec, ptype, rules).await
+    }
+
+    async fn remove_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> Result<bool> {
+        self.0.remove_policy(sec, ptype, rule).await
+    }
+
+    async fn remove_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        self.0.remove_policies(sec, ptype, rules).await
+    }
+
+    async fn remove_filtered_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        self.0
+            .remove_filtered_policy(sec, ptype, field_index, field_values)
+            .await
+    }
+}
+
+/// A watcher keeping every update it is sent.
+#[cfg(feature = "watcher")]
+#[derive(Default)]
+pub(crate) struct SampleWatcher(pub(crate) Arc<Mutex<Vec<EventData>>>);
+
+#[cfg(feature = "watcher")]
+impl Watcher for SampleWatcher {
+    fn set_update_callback(
+        &mut self,
+        _cb: Box<dyn FnMut(String) + Send + Sync>,
+    ) {
+    }
+
+    fn update(&mut self, d: EventData) {
+        self.0.lock().unwrap().push(d);
+    }
+}
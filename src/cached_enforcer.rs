@@ -1,13 +1,15 @@
 use crate::{
-    adapter::{Adapter, Filter},
+    adapter::{Adapter, PolicyFilter},
     cache::{Cache, DefaultCache},
     cached_api::CachedApi,
     convert::{EnforceArgs, TryIntoAdapter, TryIntoModel},
     core_api::CoreApi,
     effector::Effector,
     emitter::{clear_cache, Event, EventData, EventEmitter},
-    enforcer::EnforceContext,
-    enforcer::Enforcer,
+    enforcer::{
+        has_conditional_roles, EnforceContext, Enforcer,
+        DEFAULT_ENFORCE_CONTEXT,
+    },
     model::{Model, OperatorFunction},
     rbac::RoleManager,
     Result,
@@ -23,7 +25,7 @@ use crate::watcher::Watcher;
 use crate::logger::Logger;
 
 #[cfg(feature = "explain")]
-use crate::{error::ModelError, get_or_err};
+use crate::explanation::Explanation;
 
 use async_trait::async_trait;
 use parking_lot::RwLock;
@@ -32,16 +34,25 @@ use rhai::Dynamic;
 use std::{collections::HashMap, sync::Arc};
 
 type EventCallback = fn(&mut CachedEnforcer, EventData);
+type BatchEnforceFn = fn(&Enforcer, &[Vec<Dynamic>]) -> Result<Vec<bool>>;
+
+const DEFAULT_CAPACITY: usize = 200;
 
 pub struct CachedEnforcer {
     enforcer: Enforcer,
     cache: Box<dyn Cache<u64, bool>>,
+    // explanations asked for with `enforce_ex`, of decisions held by `cache`
+    #[cfg(feature = "explain")]
+    explanations: Box<dyn Cache<u64, Explanation>>,
+    // the size given to `explanations`, following `set_capacity`
+    #[cfg(feature = "explain")]
+    capacity: usize,
     events: HashMap<Event, Vec<EventCallback>>,
 }
 
 impl EventEmitter<Event> for CachedEnforcer {
     fn on(&mut self, e: Event, f: fn(&mut Self, EventData)) {
-        self.events.entry(e).or_insert_with(Vec::new).push(f)
+        self.events.entry(e).or_default().push(f)
     }
 
     fn off(&mut self, e: Event) {
@@ -58,35 +69,142 @@ impl EventEmitter<Event> for CachedEnforcer {
 }
 
 impl CachedEnforcer {
+    /// See [`Enforcer::export_state`].
+    pub fn export_state(&self) -> Result<String> {
+        self.enforcer.export_state()
+    }
+
+    /// Replaces the cache by a [`DefaultCache`] holding up to `cap`
+    /// decisions, and as many explanations.
+    pub fn set_capacity(&mut self, cap: usize) {
+        #[cfg(feature = "explain")]
+        {
+            self.capacity = cap;
+        }
+        self.set_cache(Box::new(DefaultCache::new(cap)));
+    }
+
+    /// See [`Enforcer::import_state`]. Cached decisions are dropped.
+    pub fn import_state(&mut self, state: &str) -> Result<()> {
+        self.enforcer.import_state(state)?;
+        self.emit(Event::ClearCache, EventData::ClearCache);
+
+        Ok(())
+    }
+
     pub(crate) fn private_enforce(
         &self,
         rvals: &[Dynamic],
         cache_key: u64,
     ) -> Result<(bool, bool, Option<Vec<usize>>)> {
-        Ok(if let Some(authorized) = self.cache.get(&cache_key) {
-            (authorized, true, None)
-        } else {
-            let (authorized, indices) =
-                self.enforcer.private_enforce(&rvals)?;
-            self.cache.set(cache_key, authorized);
-            (authorized, false, indices)
-        })
+        self.private_enforce_with_context(
+            &DEFAULT_ENFORCE_CONTEXT,
+            rvals,
+            cache_key,
+        )
     }
+
+    // Conditional role links, such as time windows, can change a decision
+    // without any change to the policy, so their decisions aren't cached
+    fn is_cacheable(&self) -> bool {
+        !has_conditional_roles(self.enforcer.get_model())
+    }
+
     pub(crate) fn private_enforce_with_context(
         &self,
-        ctx: EnforceContext,
+        ctx: &EnforceContext,
         rvals: &[Dynamic],
         cache_key: u64,
     ) -> Result<(bool, bool, Option<Vec<usize>>)> {
+        if !self.is_cacheable() {
+            let (authorized, indices) =
+                self.enforcer.private_enforce_with_context(ctx, rvals)?;
+            return Ok((authorized, false, indices));
+        }
+
         Ok(if let Some(authorized) = self.cache.get(&cache_key) {
             (authorized, true, None)
         } else {
             let (authorized, indices) =
-                self.enforcer.private_enforce_with_context(ctx, &rvals)?;
+                self.enforcer.private_enforce_with_context(ctx, rvals)?;
             self.cache.set(cache_key, authorized);
             (authorized, false, indices)
         })
     }
+
+    // The cached explanation of a request, as long as it still agrees with
+    // the cached decision
+    #[cfg(feature = "explain")]
+    fn cached_explanation(
+        &self,
+        ctx: &EnforceContext,
+        cache_key: u64,
+    ) -> Option<Explanation> {
+        if !self.is_cacheable() {
+            return None;
+        }
+        let authorized = self.cache.get(&cache_key)?;
+        self.explanations
+            .get(&cache_key)
+            .filter(|x| x.allowed == authorized && x.context == *ctx)
+            .map(|x| Explanation { cached: true, ..x })
+    }
+
+    // Answers what it can from the cache and hands the remaining requests
+    // to `enforce_misses` as a single batch
+    fn private_batch_enforce<ARGS: EnforceArgs>(
+        &self,
+        rvals: Vec<ARGS>,
+        enforce_misses: BatchEnforceFn,
+    ) -> Result<Vec<bool>> {
+        let cacheable = self.is_cacheable();
+        let mut results = vec![false; rvals.len()];
+        let mut misses = vec![];
+        let mut miss_rvals = vec![];
+
+        for (idx, args) in rvals.into_iter().enumerate() {
+            let cache_key = args.cache_key();
+            let rvals = args.try_into_vec()?;
+
+            let cached = cacheable.then(|| self.cache.get(&cache_key));
+            if let Some(authorized) = cached.flatten() {
+                #[cfg(feature = "logging")]
+                self.enforcer.get_logger().print_enforce_log(
+                    rvals.iter().map(|x| x.to_string()).collect(),
+                    authorized,
+                    true,
+                );
+
+                results[idx] = authorized;
+            } else {
+                misses.push((idx, cache_key));
+                miss_rvals.push(rvals);
+            }
+        }
+
+        if miss_rvals.is_empty() {
+            return Ok(results);
+        }
+
+        let authorized = enforce_misses(&self.enforcer, &miss_rvals)?;
+        for (((idx, cache_key), authorized), _rvals) in
+            misses.into_iter().zip(authorized).zip(miss_rvals.iter())
+        {
+            #[cfg(feature = "logging")]
+            self.enforcer.get_logger().print_enforce_log(
+                _rvals.iter().map(|x| x.to_string()).collect(),
+                authorized,
+                false,
+            );
+
+            if cacheable {
+                self.cache.set(cache_key, authorized);
+            }
+            results[idx] = authorized;
+        }
+
+        Ok(results)
+    }
 }
 
 #[async_trait]
@@ -96,15 +214,21 @@ impl CoreApi for CachedEnforcer {
         a: A,
     ) -> Result<CachedEnforcer> {
         let enforcer = Enforcer::new_raw(m, a).await?;
-        let cache = Box::new(DefaultCache::new(200));
+        let cache = Box::new(DefaultCache::new(DEFAULT_CAPACITY));
 
         let mut cached_enforcer = CachedEnforcer {
             enforcer,
             cache,
+            #[cfg(feature = "explain")]
+            explanations: Box::new(DefaultCache::new(DEFAULT_CAPACITY)),
+            #[cfg(feature = "explain")]
+            capacity: DEFAULT_CAPACITY,
             events: HashMap::new(),
         };
 
         cached_enforcer.on(Event::ClearCache, clear_cache);
+        #[cfg(feature = "explain")]
+        cached_enforcer.on(Event::ClearCache, |ce, _| ce.explanations.clear());
 
         #[cfg(any(feature = "logging", feature = "watcher"))]
         cached_enforcer.on(Event::PolicyChange, notify_logger_and_watcher);
@@ -219,19 +343,28 @@ impl CoreApi for CachedEnforcer {
                 cached,
             );
 
+            // a decision just made is explained from its rules, a cached
+            // one only if `enforce_ex` explained it before
             #[cfg(feature = "explain")]
-            if let Some(indices) = indices {
-                let all_rules = get_or_err!(self, "p", ModelError::P, "policy")
-                    .get_policy();
-
-                let rules: Vec<String> = indices
-                    .into_iter()
-                    .filter_map(|y| {
-                        all_rules.iter().nth(y).map(|x| x.join(", "))
-                    })
-                    .collect();
-
-                self.enforcer.get_logger().print_explain_log(rules);
+            if let Some(explanation) = match indices {
+                Some(_) => Some(self.enforcer.explain(
+                    &DEFAULT_ENFORCE_CONTEXT,
+                    authorized,
+                    indices,
+                )?),
+                None => {
+                    self.cached_explanation(&DEFAULT_ENFORCE_CONTEXT, cache_key)
+                }
+            } {
+                if !explanation.rules.is_empty() {
+                    let rules = explanation
+                        .rules
+                        .into_iter()
+                        .map(|x| x.rule.join(", "))
+                        .collect();
+
+                    self.enforcer.get_logger().print_explain_log(rules);
+                }
             }
         }
 
@@ -247,7 +380,7 @@ impl CoreApi for CachedEnforcer {
         let rvals = rvals.try_into_vec()?;
         #[allow(unused_variables)]
         let (authorized, cached, indices) =
-            self.private_enforce_with_context(ctx, &rvals, cache_key)?;
+            self.private_enforce_with_context(&ctx, &rvals, cache_key)?;
 
         #[cfg(feature = "logging")]
         {
@@ -257,19 +390,24 @@ impl CoreApi for CachedEnforcer {
                 cached,
             );
 
+            // a decision just made is explained from its rules, a cached
+            // one only if `enforce_ex` explained it before
             #[cfg(feature = "explain")]
-            if let Some(indices) = indices {
-                let all_rules = get_or_err!(self, "p", ModelError::P, "policy")
-                    .get_policy();
-
-                let rules: Vec<String> = indices
-                    .into_iter()
-                    .filter_map(|y| {
-                        all_rules.iter().nth(y).map(|x| x.join(", "))
-                    })
-                    .collect();
-
-                self.enforcer.get_logger().print_explain_log(rules);
+            if let Some(explanation) = match indices {
+                Some(_) => {
+                    Some(self.enforcer.explain(&ctx, authorized, indices)?)
+                }
+                None => self.cached_explanation(&ctx, cache_key),
+            } {
+                if !explanation.rules.is_empty() {
+                    let rules = explanation
+                        .rules
+                        .into_iter()
+                        .map(|x| x.rule.join(", "))
+                        .collect();
+
+                    self.enforcer.get_logger().print_explain_log(rules);
+                }
             }
         }
 
@@ -281,30 +419,53 @@ impl CoreApi for CachedEnforcer {
         self.enforce(rvals)
     }
 
+    #[inline]
+    fn batch_enforce<ARGS: EnforceArgs>(
+        &self,
+        rvals: Vec<ARGS>,
+    ) -> Result<Vec<bool>> {
+        self.private_batch_enforce(rvals, Enforcer::private_batch_enforce)
+    }
+
+    #[cfg(feature = "parallel")]
+    #[inline]
+    fn par_batch_enforce<ARGS: EnforceArgs>(
+        &self,
+        rvals: Vec<ARGS>,
+    ) -> Result<Vec<bool>> {
+        self.private_batch_enforce(rvals, Enforcer::private_par_batch_enforce)
+    }
+
     #[cfg(feature = "explain")]
+    #[inline]
     fn enforce_ex<ARGS: EnforceArgs>(
         &self,
         rvals: ARGS,
-    ) -> Result<(bool, Vec<Vec<String>>)> {
+    ) -> Result<Explanation> {
+        self.enforce_ex_with_context(DEFAULT_ENFORCE_CONTEXT.clone(), rvals)
+    }
+
+    #[cfg(feature = "explain")]
+    fn enforce_ex_with_context<ARGS: EnforceArgs>(
+        &self,
+        ctx: EnforceContext,
+        rvals: ARGS,
+    ) -> Result<Explanation> {
         let cache_key = rvals.cache_key();
-        let rvals = rvals.try_into_vec()?;
-        #[allow(unused_variables)]
-        let (authorized, cached, indices) =
-            self.private_enforce(&rvals, cache_key)?;
+        if let Some(explanation) = self.cached_explanation(&ctx, cache_key) {
+            return Ok(explanation);
+        }
 
-        let rules = match indices {
-            Some(indices) => {
-                let all_rules = get_or_err!(self, "p", ModelError::P, "policy")
-                    .get_policy();
+        let rvals = rvals.try_into_vec()?;
+        let (authorized, indices) =
+            self.enforcer.private_enforce_with_context(&ctx, &rvals)?;
+        let explanation = self.enforcer.explain(&ctx, authorized, indices)?;
+        if self.is_cacheable() {
+            self.cache.set(cache_key, authorized);
+            self.explanations.set(cache_key, explanation.clone());
+        }
 
-                indices
-                    .into_iter()
-                    .filter_map(|y| all_rules.iter().nth(y).cloned())
-                    .collect::<Vec<_>>()
-            }
-            None => vec![],
-        };
-        Ok((authorized, rules))
+        Ok(explanation)
     }
 
     #[inline]
@@ -324,7 +485,10 @@ impl CoreApi for CachedEnforcer {
     }
 
     #[inline]
-    async fn load_filtered_policy<'a>(&mut self, f: Filter<'a>) -> Result<()> {
+    async fn load_filtered_policy<F>(&mut self, f: F) -> Result<()>
+    where
+        F: Into<PolicyFilter> + Send,
+    {
         self.enforcer.load_filtered_policy(f).await
     }
 
@@ -401,10 +565,16 @@ impl CachedApi<u64, bool> for CachedEnforcer {
 
     fn set_cache(&mut self, cache: Box<dyn Cache<u64, bool>>) {
         self.cache = cache;
+        // the explanations went with the decisions of the old cache
+        #[cfg(feature = "explain")]
+        {
+            self.explanations = Box::new(DefaultCache::new(self.capacity));
+        }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::extra_unused_type_parameters)]
 mod tests {
     use super::*;
 
@@ -421,4 +591,174 @@ mod tests {
         assert!(is_send::<CachedEnforcer>());
         assert!(is_sync::<CachedEnforcer>());
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_batch_enforce() {
+        let mut e = CachedEnforcer::new(
+            "examples/basic_model.conf",
+            "examples/basic_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        assert!(e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(e
+            .get_mut_cache()
+            .has(&("alice", "data1", "read").cache_key()));
+
+        let requests = vec![
+            ("alice", "data1", "read"),
+            ("bob", "data2", "write"),
+            ("bob", "data1", "read"),
+        ];
+        assert_eq!(
+            vec![true, true, false],
+            e.batch_enforce(requests.clone()).unwrap()
+        );
+        for r in requests.iter() {
+            assert!(e.get_mut_cache().has(&r.cache_key()));
+        }
+
+        #[cfg(feature = "parallel")]
+        assert_eq!(
+            vec![true, true, false],
+            e.par_batch_enforce(requests).unwrap()
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_conditional_roles_not_cached() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        static ON_CALL: AtomicBool = AtomicBool::new(false);
+
+        let mut e = CachedEnforcer::new(
+            "examples/rbac_with_conditions_model.conf",
+            "examples/rbac_with_conditions_policy.csv",
+        )
+        .await
+        .unwrap();
+        e.get_role_manager()
+            .write()
+            .add_link_condition_fn("onCall", |_| ON_CALL.load(Ordering::SeqCst))
+            .unwrap();
+
+        let request = ("carol", "data1", "read");
+        assert!(!e.enforce(request).unwrap());
+        ON_CALL.store(true, Ordering::SeqCst);
+        assert!(e.enforce(request).unwrap());
+        assert_eq!(vec![true], e.batch_enforce(vec![request]).unwrap());
+        assert!(!e.get_mut_cache().has(&request.cache_key()));
+    }
+
+    #[cfg(feature = "explain")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_expired_link_not_explained_from_cache() {
+        use crate::MgmtApi;
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let mut e = CachedEnforcer::new(
+            "examples/rbac_with_conditions_model.conf",
+            "examples/rbac_with_conditions_policy.csv",
+        )
+        .await
+        .unwrap();
+        e.enable_auto_save(false);
+
+        // frank is a contractor for the next second
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let end = (now.as_secs() + 1).to_string();
+        e.add_grouping_policy(vec![
+            "frank".to_owned(),
+            "contractor".to_owned(),
+            String::new(),
+            end,
+        ])
+        .await
+        .unwrap();
+
+        let request = ("frank", "data2", "read");
+        assert!(e.enforce_ex(request).unwrap().allowed);
+        assert!(!e.enforce_ex(request).unwrap().cached);
+        assert!(!e.explanations.has(&request.cache_key()));
+
+        std::thread::sleep(Duration::from_millis(2100));
+        let explanation = e.enforce_ex(request).unwrap();
+        assert!(!explanation.allowed && !explanation.cached);
+        assert!(!e.enforce(request).unwrap());
+    }
+
+    #[cfg(feature = "explain")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_cached_explanation() {
+        use crate::{adapter::file_adapter::TempPolicy, MgmtApi};
+
+        let policy = TempPolicy::copy("examples/basic_policy.csv");
+        let mut e =
+            CachedEnforcer::new("examples/basic_model.conf", policy.adapter())
+                .await
+                .unwrap();
+
+        // explanations are only made when asked for
+        let request = ("alice", "data1", "read");
+        assert!(e.enforce(request).unwrap());
+        assert!(!e.explanations.has(&request.cache_key()));
+        assert!(!e.enforce_ex(request).unwrap().cached);
+        let explanation = e.enforce_ex(request).unwrap();
+        assert!(explanation.allowed && explanation.cached);
+        assert_eq!(
+            vec![vec!["alice", "data1", "read"]],
+            explanation
+                .rules
+                .iter()
+                .map(|x| x.rule.clone())
+                .collect::<Vec<_>>()
+        );
+
+        let explanation = e.enforce_ex(("bob", "data2", "write")).unwrap();
+        assert!(explanation.allowed && !explanation.cached);
+        assert_eq!(1, explanation.rules[0].index);
+        assert!(e.enforce_ex(("bob", "data2", "write")).unwrap().cached);
+
+        e.add_policy(vec!["bob".into(), "data3".into(), "read".into()])
+            .await
+            .unwrap();
+        assert!(!e.enforce_ex(("bob", "data2", "write")).unwrap().cached);
+
+        e.set_capacity(1);
+        assert!(!e.enforce_ex(request).unwrap().cached);
+        assert!(e.enforce_ex(request).unwrap().cached);
+    }
 }
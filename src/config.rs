@@ -1,150 +1,361 @@
-use crate::Result;
-
-#[cfg(feature = "runtime-async-std")]
-use async_std::{
-    io::prelude::*,
-    io::{BufReader as ioBufReader, Cursor as ioCursor},
-};
+use crate::{error::ConfigError, Result};
 
 #[cfg(all(feature = "runtime-async-std", not(target_arch = "wasm32")))]
-use async_std::{fs::File as file, path::Path as ioPath};
+use async_std::{fs, path::Path as ioPath};
 
-#[cfg(feature = "runtime-tokio")]
-use std::io::Cursor as ioCursor;
-#[cfg(feature = "runtime-tokio")]
+#[cfg(all(feature = "runtime-tokio", not(target_arch = "wasm32")))]
 use std::path::Path as ioPath;
-#[cfg(feature = "runtime-tokio")]
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader as ioBufReader};
-
 #[cfg(all(feature = "runtime-tokio", not(target_arch = "wasm32")))]
-use tokio::fs::File as file;
+use tokio::fs;
 
-use std::collections::HashMap;
+use hashlink::LinkedHashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Component, Path, PathBuf},
+};
 
 const DEFAULT_SECTION: &str = "default";
 const DEFAULT_COMMENT: &str = "#";
 const DEFAULT_COMMENT_SEM: &str = ";";
 const DEFAULT_MULTI_LINE_SEPARATOR: &str = "\\";
+const INCLUDE_OPTION: &str = "include";
+const MAX_INCLUDE_DEPTH: usize = 16;
+const STRING_ORIGIN: &str = "<string>";
+
+/// An INI style configuration, as used by model files.
+///
+/// Options are `option = value` lines, grouped under `[section]` headers,
+/// and a line ending with `\\` continues on the next one. Lines starting
+/// with `#` or `;` are comments. Includes and environment variables are
+/// only read when enabled with [`ConfigOptions`].
+///
+/// Besides its values, a config remembers the text it was parsed from.
+/// Writing it back with [`to_string`](ToString::to_string) or
+/// [`save`](Config::save) keeps comments, blank lines, continued lines and
+/// includes as they were, only rewriting the options that changed.
+#[derive(Clone, Default)]
+pub struct Config {
+    data: LinkedHashMap<String, LinkedHashMap<String, String>>,
+    lines: Vec<Line>,
+}
+
+/// What a [`Config`] reads besides plain options, nothing by default.
+///
+/// ```
+/// use casbin::{Config, ConfigOptions};
+///
+/// # async fn load() -> casbin::Result<Config> {
+/// let options = ConfigOptions::new().includes(true).interpolation(true);
+/// Config::from_file_with_options("model.conf", options).await
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConfigOptions {
+    includes: bool,
+    interpolation: bool,
+}
+
+impl ConfigOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the config named by an `include = path` line in its place,
+    /// starting in the current section. Relative paths are resolved from
+    /// the including file, or from the working directory for a config
+    /// parsed from a string.
+    pub fn includes(mut self, enabled: bool) -> Self {
+        self.includes = enabled;
+        self
+    }
 
-pub(crate) struct Config {
-    data: HashMap<String, HashMap<String, String>>,
+    /// Replaces `${NAME}` in values with the environment variable `NAME`,
+    /// `$${` standing for a literal `${`.
+    pub fn interpolation(mut self, enabled: bool) -> Self {
+        self.interpolation = enabled;
+        self
+    }
+}
+
+// A line of the parsed text, with its line ending
+#[derive(Clone)]
+enum Line {
+    // blank lines and comments
+    Text(String),
+    Section {
+        name: String,
+        raw: String,
+    },
+    // `raw` holds every line of a continued option
+    Option {
+        section: String,
+        option: String,
+        value: String,
+        raw: String,
+    },
+    // the options read from the included file, as (section, option, value)
+    Include {
+        section: String,
+        raw: String,
+        options: Vec<(String, String, String)>,
+    },
+}
+
+// The texts of included files by normalized path, or why they couldn't be
+// read
+type Sources = HashMap<PathBuf, std::result::Result<String, String>>;
+
+// What the parser needs to know about the text being parsed
+struct Origin<'a> {
+    name: String,
+    options: ConfigOptions,
+    dir: PathBuf,
+    sources: &'a Sources,
+    // files being parsed, to detect include cycles
+    stack: Vec<PathBuf>,
+}
+
+impl Origin<'_> {
+    fn error(&self, line: usize, message: impl Into<String>) -> ConfigError {
+        ConfigError::Parse(self.name.clone(), line, message.into())
+    }
 }
 
 impl Config {
     #[cfg(not(target_arch = "wasm32"))]
-    pub(crate) async fn from_file<P: AsRef<ioPath>>(p: P) -> Result<Self> {
-        let mut c = Config {
-            data: HashMap::new(),
-        };
+    pub async fn from_file<P: AsRef<ioPath>>(p: P) -> Result<Self> {
+        Self::from_file_with_options(p, ConfigOptions::default()).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn from_file_with_options<P: AsRef<ioPath>>(
+        p: P,
+        options: ConfigOptions,
+    ) -> Result<Self> {
+        let path = normalize(Path::new(p.as_ref().as_os_str()));
+        let text = fs::read_to_string(path.as_os_str()).await?;
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let sources = read_includes(&text, &dir, options).await;
 
-        c.parse(p).await?;
+        let mut c = Config::default();
+        c.parse(
+            &text,
+            Origin {
+                name: path.display().to_string(),
+                options,
+                dir,
+                sources: &sources,
+                stack: vec![path],
+            },
+        )?;
         Ok(c)
     }
 
-    pub(crate) async fn from_str<S: AsRef<str>>(s: S) -> Result<Self> {
-        let mut c = Config {
-            data: HashMap::new(),
-        };
+    pub async fn from_str<S: AsRef<str>>(s: S) -> Result<Self> {
+        Self::from_str_with_options(s, ConfigOptions::default()).await
+    }
+
+    pub async fn from_str_with_options<S: AsRef<str>>(
+        s: S,
+        options: ConfigOptions,
+    ) -> Result<Self> {
+        let text = s.as_ref();
+        #[cfg(not(target_arch = "wasm32"))]
+        let sources = read_includes(text, Path::new(""), options).await;
+        #[cfg(target_arch = "wasm32")]
+        let sources = Sources::new();
 
-        c.parse_buffer(&mut ioBufReader::new(ioCursor::new(
-            s.as_ref().as_bytes(),
-        )))
-        .await?;
+        let mut c = Config::default();
+        c.parse(
+            text,
+            Origin {
+                name: STRING_ORIGIN.to_owned(),
+                options,
+                dir: PathBuf::new(),
+                sources: &sources,
+                stack: vec![],
+            },
+        )?;
         Ok(c)
     }
 
+    /// Writes the config to `p`, through a temporary file renamed over it.
     #[cfg(not(target_arch = "wasm32"))]
-    async fn parse<P: AsRef<ioPath>>(&mut self, p: P) -> Result<()> {
-        let mut f = file::open(p).await?;
-        let mut c = Vec::new();
-        f.read_to_end(&mut c).await?;
+    pub async fn save<P: AsRef<ioPath>>(&self, p: P) -> Result<()> {
+        let mut tmp = p.as_ref().as_os_str().to_owned();
+        tmp.push(".tmp");
 
-        let mut reader: ioBufReader<ioCursor<&[u8]>> =
-            ioBufReader::new(ioCursor::new(&c));
-        self.parse_buffer(&mut reader).await
+        fs::write(&tmp, self.to_string()).await?;
+        fs::rename(&tmp, p.as_ref()).await?;
+        Ok(())
     }
 
-    async fn parse_buffer(
-        &mut self,
-        reader: &mut ioBufReader<ioCursor<&[u8]>>,
-    ) -> Result<()> {
+    fn parse(&mut self, text: &str, origin: Origin) -> Result<()> {
         let mut section = String::new();
+        self.lines = self.parse_lines(text, &mut section, origin)?;
+        Ok(())
+    }
 
-        loop {
-            let mut line = String::new();
-            let bytes = reader.read_line(&mut line).await?;
-            if bytes == 0 {
-                // EOF reached
-                break Ok(());
-            }
-            line = line.trim().to_string();
+    fn parse_lines(
+        &mut self,
+        text: &str,
+        section: &mut String,
+        mut origin: Origin,
+    ) -> Result<Vec<Line>> {
+        let mut res = vec![];
+        let mut lines = text.split_inclusive('\n').enumerate().peekable();
+
+        while let Some((idx, raw)) = lines.next() {
+            let lineno = idx + 1;
+            let mut raw = raw.to_owned();
+            let mut line = raw.trim().to_string();
             if line.is_empty()
                 || line.starts_with(DEFAULT_COMMENT)
                 || line.starts_with(DEFAULT_COMMENT_SEM)
             {
+                res.push(Line::Text(raw));
                 continue;
-            } else if line.starts_with('[') && line.ends_with(']') {
-                section = line[1..line.len() - 1].to_string();
-            } else {
-                let mut next_section = String::new();
-                while line.ends_with(DEFAULT_MULTI_LINE_SEPARATOR) {
-                    line = line[..line.len() - 1].trim_end().to_string();
-
-                    let mut inner_line = String::new();
-                    let inner_bytes = reader.read_line(&mut inner_line).await?;
-                    if inner_bytes == 0 {
-                        break;
-                    }
-
-                    let inner_line = inner_line.trim().to_string();
-                    if inner_line.is_empty()
-                        || inner_line.starts_with(DEFAULT_COMMENT)
-                        || inner_line.starts_with(DEFAULT_COMMENT_SEM)
-                    {
-                        continue;
-                    }
+            } else if line.starts_with('[') {
+                *section = parse_section(&line).ok_or_else(|| {
+                    origin.error(lineno, "invalid section header")
+                })?;
+                res.push(Line::Section {
+                    name: section.clone(),
+                    raw,
+                });
+                continue;
+            }
 
-                    if inner_line.starts_with('[') && inner_line.ends_with(']')
-                    {
-                        next_section =
-                            inner_line[1..inner_line.len() - 1].to_string();
-                    } else {
-                        line.push_str(&inner_line);
-                    }
-                }
+            // the line ending a continuation without being part of it
+            let next_line =
+                continue_line(&mut lines, &mut line, &mut raw, &origin)?;
 
-                let option_val: Vec<&str> = line
-                    .trim_end_matches(|c| {
-                        char::is_whitespace(c)
-                            || char::to_string(&c)
-                                == DEFAULT_MULTI_LINE_SEPARATOR
-                    })
-                    .splitn(2, '=')
-                    .map(|e| e.trim())
-                    .collect();
-
-                if option_val.len() != 2 {
-                    return Err(std::io::Error::other(format!(
-                        "parse content error, line={}",
-                        line
-                    ))
+            let Some((option, value)) = line
+                .trim_end_matches(|c| {
+                    char::is_whitespace(c)
+                        || char::to_string(&c) == DEFAULT_MULTI_LINE_SEPARATOR
+                })
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+            else {
+                // the line itself is left out, it may hold a secret
+                return Err(origin
+                    .error(lineno, "expected `option = value`")
                     .into());
-                }
+            };
+            if option.is_empty() {
+                return Err(origin.error(lineno, "missing option name").into());
+            }
+            let value = if origin.options.interpolation {
+                interpolate(value).map_err(|e| origin.error(lineno, e))?
+            } else {
+                value.to_owned()
+            };
 
+            if origin.options.includes && option == INCLUDE_OPTION {
+                let current = section.clone();
+                let options =
+                    self.include(&value, section, &mut origin, lineno)?;
+                *section = current;
+                res.push(Line::Include {
+                    section: section_or_default(section).to_owned(),
+                    raw,
+                    options,
+                });
+            } else {
                 self.add_config(
                     section.clone(),
-                    option_val[0].to_string(),
-                    option_val[1].to_string(),
+                    option.to_owned(),
+                    value.clone(),
                 );
+                res.push(Line::Option {
+                    section: section_or_default(section).to_owned(),
+                    option: option.to_owned(),
+                    value,
+                    raw,
+                });
+            }
 
-                if !next_section.is_empty() {
-                    section = next_section;
+            if let Some(next_line) = next_line {
+                if let Line::Section { name, .. } = &next_line {
+                    *section = name.clone();
                 }
+                res.push(next_line);
             }
         }
+
+        Ok(res)
     }
 
-    pub(crate) fn add_config(
+    // Parses the file included by `value` in place, returning its options
+    fn include(
+        &mut self,
+        value: &str,
+        section: &mut String,
+        origin: &mut Origin,
+        lineno: usize,
+    ) -> Result<Vec<(String, String, String)>> {
+        if value.is_empty() {
+            return Err(origin.error(lineno, "missing include path").into());
+        }
+        let path = normalize(&origin.dir.join(value));
+        if origin.stack.contains(&path) {
+            return Err(origin
+                .error(lineno, format!("`{}` includes itself", value))
+                .into());
+        }
+        if origin.stack.len() >= MAX_INCLUDE_DEPTH {
+            return Err(origin
+                .error(lineno, "includes nested too deeply")
+                .into());
+        }
+
+        let text = match origin.sources.get(&path) {
+            Some(Ok(text)) => text,
+            Some(Err(e)) => {
+                return Err(origin
+                    .error(lineno, format!("cannot read `{}`: {}", value, e))
+                    .into());
+            }
+            None => {
+                return Err(origin
+                    .error(lineno, format!("cannot read `{}`", value))
+                    .into());
+            }
+        };
+
+        let mut stack = origin.stack.clone();
+        stack.push(path.clone());
+        let lines = self.parse_lines(
+            text,
+            section,
+            Origin {
+                name: path.display().to_string(),
+                options: origin.options,
+                dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+                sources: origin.sources,
+                stack,
+            },
+        )?;
+
+        let mut options = vec![];
+        for line in lines {
+            match line {
+                Line::Option {
+                    section,
+                    option,
+                    value,
+                    ..
+                } => options.push((section, option, value)),
+                Line::Include { options: x, .. } => options.extend(x),
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+
+    fn add_config(
         &mut self,
         mut section: String,
         option: String,
@@ -153,7 +364,8 @@ impl Config {
         if section.is_empty() {
             section = DEFAULT_SECTION.to_owned();
         }
-        let section_value = self.data.entry(section).or_default();
+        let section_value =
+            self.data.entry(section).or_insert_with(LinkedHashMap::new);
 
         // if key not exists then insert, else update
         let key_value = section_value.get_mut(&option);
@@ -167,6 +379,8 @@ impl Config {
         }
     }
 
+    /// Reads the option `key`, written `section::option` or just `option`
+    /// for the default section.
     pub fn get(&self, key: &str) -> Option<&str> {
         let keys: Vec<String> =
             key.to_lowercase().split("::").map(String::from).collect();
@@ -185,8 +399,8 @@ impl Config {
         }
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn set(&mut self, key: &str, value: &str) {
+    /// Sets the option `key`, named as for [`get`](Config::get).
+    pub fn set(&mut self, key: &str, value: &str) {
         assert!(!key.is_empty(), "key can't be empty");
         let keys: Vec<String> =
             key.to_lowercase().split("::").map(String::from).collect();
@@ -209,34 +423,348 @@ impl Config {
         }
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn get_bool(&self, key: &str) -> Option<bool> {
+    /// Removes the option `key`, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let key = key.to_lowercase();
+        let (section, option) =
+            key.split_once("::").unwrap_or((DEFAULT_SECTION, &key));
+        self.data.get_mut(section)?.remove(option)
+    }
+
+    /// Reads the option `key` as a boolean.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
         self.get(key).and_then(|v| v.parse::<bool>().ok())
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn get_string(&self, key: &str) -> Option<String> {
+    pub fn get_string(&self, key: &str) -> Option<String> {
         self.get_str(key).map(|v| v.to_string())
     }
 
-    pub(crate) fn get_str(&self, key: &str) -> Option<&str> {
+    pub fn get_str(&self, key: &str) -> Option<&str> {
         self.get(key)
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn get_int(&self, key: &str) -> Option<i64> {
+    /// Reads the option `key` as an integer.
+    pub fn get_int(&self, key: &str) -> Option<i64> {
         self.get(key).and_then(|v| v.parse::<i64>().ok())
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn get_float(&self, key: &str) -> Option<f64> {
+    /// Reads the option `key` as a float.
+    pub fn get_float(&self, key: &str) -> Option<f64> {
         self.get(key).and_then(|v| v.parse::<f64>().ok())
     }
 }
 
+fn parse_section(line: &str) -> Option<String> {
+    line.strip_prefix('[')?
+        .strip_suffix(']')
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+}
+
+// Joins the lines continued by a trailing separator onto `line`, returning
+// the line that ended the continuation without being part of it
+fn continue_line<'a, I>(
+    lines: &mut I,
+    line: &mut String,
+    raw: &mut String,
+    origin: &Origin,
+) -> Result<Option<Line>>
+where
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    let mut next_line = None;
+    while line.ends_with(DEFAULT_MULTI_LINE_SEPARATOR) {
+        *line = line[..line.len() - 1].trim_end().to_string();
+
+        let Some((idx, inner_raw)) = lines.next() else {
+            break;
+        };
+
+        let inner_line = inner_raw.trim();
+        if inner_line.is_empty()
+            || inner_line.starts_with(DEFAULT_COMMENT)
+            || inner_line.starts_with(DEFAULT_COMMENT_SEM)
+        {
+            next_line = Some(Line::Text(inner_raw.to_owned()));
+            continue;
+        }
+
+        if inner_line.starts_with('[') {
+            let name = parse_section(inner_line).ok_or_else(|| {
+                origin.error(idx + 1, "invalid section header")
+            })?;
+            next_line = Some(Line::Section {
+                name,
+                raw: inner_raw.to_owned(),
+            });
+        } else {
+            line.push_str(inner_line);
+            raw.push_str(inner_raw);
+        }
+    }
+
+    Ok(next_line)
+}
+
+// Replaces `${NAME}` with the environment variable `NAME`
+fn interpolate(value: &str) -> std::result::Result<String, String> {
+    let mut res = String::new();
+    let mut rest = value;
+
+    while let Some(idx) = rest.find('$') {
+        res.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        if let Some(tail) = rest.strip_prefix("$${") {
+            res.push_str("${");
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("${") {
+            let end = tail.find('}').ok_or("unterminated `${`")?;
+            let name = &tail[..end];
+            let var = std::env::var(name).map_err(|_| {
+                format!("environment variable `{}` is not set", name)
+            })?;
+            res.push_str(&var);
+            rest = &tail[end + 1..];
+        } else {
+            res.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    res.push_str(rest);
+    Ok(res)
+}
+
+// Resolves `.` and `..` without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    res.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                res.pop();
+            }
+            component => res.push(component),
+        }
+    }
+    res
+}
+
+// Reads every file `text` includes, directly or not, ahead of parsing
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_includes(
+    text: &str,
+    dir: &Path,
+    options: ConfigOptions,
+) -> Sources {
+    let mut sources = Sources::new();
+    if !options.includes {
+        return sources;
+    }
+    let mut pending: Vec<_> = include_paths(text, dir, options)
+        .map(|path| (path, 1))
+        .collect();
+
+    while let Some((path, depth)) = pending.pop() {
+        if depth > MAX_INCLUDE_DEPTH || sources.contains_key(&path) {
+            continue;
+        }
+
+        let text = fs::read_to_string(path.as_os_str())
+            .await
+            .map_err(|e| e.to_string());
+        if let Ok(text) = &text {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            pending.extend(
+                include_paths(text, dir, options).map(|x| (x, depth + 1)),
+            );
+        }
+        sources.insert(path, text);
+    }
+
+    sources
+}
+
+// The files `text` includes, as found without parsing it
+#[cfg(not(target_arch = "wasm32"))]
+fn include_paths<'a>(
+    text: &'a str,
+    dir: &'a Path,
+    options: ConfigOptions,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    text.lines().filter_map(move |line| {
+        let (option, value) = line.split_once('=')?;
+        if option.trim() != INCLUDE_OPTION {
+            return None;
+        }
+        let value = if options.interpolation {
+            interpolate(value.trim()).ok()?
+        } else {
+            value.trim().to_owned()
+        };
+        Some(normalize(&dir.join(value)))
+    })
+}
+
+fn section_or_default(section: &str) -> &str {
+    if section.is_empty() {
+        DEFAULT_SECTION
+    } else {
+        section
+    }
+}
+
+// appends a line, ending the previous one if the text lacked a final newline
+fn push_line(out: &mut String, line: &str, newline: &str) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push_str(newline);
+    }
+    out.push_str(line);
+    out.push_str(newline);
+}
+
+impl Config {
+    // the line ending of the parsed text, `\n` when it had none
+    fn newline(&self) -> &'static str {
+        let raw = self.lines.iter().find_map(|line| match line {
+            Line::Text(raw)
+            | Line::Section { raw, .. }
+            | Line::Option { raw, .. }
+            | Line::Include { raw, .. } => raw.find('\n').map(|i| &raw[..i]),
+        });
+        match raw {
+            Some(raw) if raw.ends_with('\r') => "\r\n",
+            _ => "\n",
+        }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let newline = self.newline();
+        let mut parsed = HashSet::new();
+        let mut included = HashMap::new();
+        // options added since parsing go after the last line of their section
+        let mut anchors = HashMap::new();
+        for (idx, line) in self.lines.iter().enumerate() {
+            match line {
+                Line::Section { name, .. } => {
+                    anchors.entry(section_or_default(name)).or_insert(idx);
+                }
+                Line::Option {
+                    section, option, ..
+                } => {
+                    parsed.insert((section.as_str(), option.as_str()));
+                    anchors.insert(section.as_str(), idx);
+                }
+                Line::Include {
+                    section, options, ..
+                } => {
+                    for (section, option, value) in options {
+                        included
+                            .insert((section.as_str(), option.as_str()), value);
+                    }
+                    anchors.insert(section.as_str(), idx);
+                }
+                Line::Text(_) => {}
+            }
+        }
+
+        // included options are only written when overridden
+        let new_options = |section: &str| {
+            self.data
+                .get(section)
+                .into_iter()
+                .flatten()
+                .filter(|(option, value)| {
+                    let key = (section, option.as_str());
+                    !parsed.contains(&key) && included.get(&key) != Some(value)
+                })
+                .map(|(option, value)| format!("{} = {}", option, value))
+                .collect::<Vec<_>>()
+        };
+        let write_new = |out: &mut String, section: &str| {
+            for line in new_options(section) {
+                push_line(out, &line, newline);
+            }
+        };
+
+        let mut out = String::new();
+        if !anchors.contains_key(DEFAULT_SECTION) {
+            write_new(&mut out, DEFAULT_SECTION);
+        }
+
+        for (idx, line) in self.lines.iter().enumerate() {
+            let section = match line {
+                Line::Text(raw) => {
+                    out.push_str(raw);
+                    continue;
+                }
+                Line::Section { name, raw } => {
+                    out.push_str(raw);
+                    section_or_default(name)
+                }
+                Line::Include { section, raw, .. } => {
+                    out.push_str(raw);
+                    section
+                }
+                Line::Option {
+                    section,
+                    option,
+                    value,
+                    raw,
+                } => {
+                    match self.data.get(section).and_then(|x| x.get(option)) {
+                        Some(current) if current == value => out.push_str(raw),
+                        Some(current) => push_line(
+                            &mut out,
+                            &format!("{} = {}", option, current),
+                            newline,
+                        ),
+                        None => {}
+                    }
+                    section
+                }
+            };
+
+            if anchors.get(section) == Some(&idx) {
+                write_new(&mut out, section);
+            }
+        }
+
+        for section in self.data.keys() {
+            if section == DEFAULT_SECTION
+                || anchors.contains_key(section.as_str())
+            {
+                continue;
+            }
+            let lines = new_options(section);
+            if !lines.is_empty() {
+                if !out.is_empty() {
+                    push_line(&mut out, "", newline);
+                }
+                push_line(&mut out, &format!("[{}]", section), newline);
+                for line in lines {
+                    push_line(&mut out, &line, newline);
+                }
+            }
+        }
+
+        f.write_str(&out)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{error::Error, DefaultModel, Model};
 
     #[cfg(not(target_arch = "wasm32"))]
     #[cfg_attr(
@@ -349,4 +877,250 @@ mod tests {
             config.get_string("other::key1")
         );
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_write_unchanged() {
+        let text = std::fs::read_to_string("examples/testini.ini").unwrap();
+        let config = Config::from_str(&text).await.unwrap();
+
+        assert_eq!(text, config.to_string());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_write_changes() {
+        let text = "# top\r\n\
+                    debug = true\r\n\
+                    \r\n\
+                    ; redis config\r\n\
+                    [redis]\r\n\
+                    redis.key = push1, \\\r\n\
+                    \x20   push2\r\n\
+                    redis.db = 0 ; inline\r\n\
+                    \r\n\
+                    ; math config\r\n\
+                    [math]\r\n\
+                    math.i64 = 64";
+        let mut config = Config::from_str(text).await.unwrap();
+
+        config.set("redis::redis.key", "push3");
+        config.set("redis::redis.port", "6379");
+        config.set("url", "act.wiki");
+        config.set("math::math.f64", "64.1");
+        config.set("other::key1", "test key");
+        assert_eq!(
+            config.remove("redis::redis.db").as_deref(),
+            Some("0 ; inline")
+        );
+        assert_eq!(config.remove("redis::redis.db"), None);
+
+        assert_eq!(
+            config.to_string(),
+            "# top\r\n\
+             debug = true\r\n\
+             url = act.wiki\r\n\
+             \r\n\
+             ; redis config\r\n\
+             [redis]\r\n\
+             redis.key = push3\r\n\
+             redis.port = 6379\r\n\
+             \r\n\
+             ; math config\r\n\
+             [math]\r\n\
+             math.i64 = 64\r\n\
+             math.f64 = 64.1\r\n\
+             \r\n\
+             [other]\r\n\
+             key1 = test key\r\n"
+        );
+
+        let config = Config::from_str(config.to_string()).await.unwrap();
+        assert_eq!(config.get("redis::redis.key"), Some("push3"));
+        assert_eq!(config.get("redis::redis.db"), None);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_interpolation() {
+        std::env::set_var("CASBIN_TEST_CONFIG_HOST", "10.0.0.1");
+        let text = "host = ${CASBIN_TEST_CONFIG_HOST}:3306\n\
+                    literal = $${CASBIN_TEST_CONFIG_HOST}\n\
+                    price = $5\n";
+        let options = ConfigOptions::new().interpolation(true);
+        let config =
+            Config::from_str_with_options(text, options).await.unwrap();
+
+        assert_eq!(config.get("host"), Some("10.0.0.1:3306"));
+        assert_eq!(config.get("literal"), Some("${CASBIN_TEST_CONFIG_HOST}"));
+        assert_eq!(config.get("price"), Some("$5"));
+        assert_eq!(config.to_string(), text);
+
+        // values are taken as they are unless asked otherwise
+        let config = Config::from_str(text).await.unwrap();
+        assert_eq!(config.get("host"), Some("${CASBIN_TEST_CONFIG_HOST}:3306"));
+        assert_eq!(config.get("literal"), Some("$${CASBIN_TEST_CONFIG_HOST}"));
+        let m = DefaultModel::from_str(
+            "[request_definition]\n\
+             r = sub, obj\n\
+             [policy_definition]\n\
+             p = sub, obj\n\
+             [policy_effect]\n\
+             e = some(where (p.eft == allow))\n\
+             [matchers]\n\
+             m = r.sub == p.sub && r.obj == \"${CASBIN_TEST_CONFIG_UNSET}\"\n",
+        )
+        .await
+        .unwrap();
+        assert!(m.get_model()["m"]["m"]
+            .value
+            .contains("\"${CASBIN_TEST_CONFIG_UNSET}\""));
+
+        let err = Config::from_str_with_options(
+            "a = 1\nb = ${CASBIN_TEST_CONFIG_UNSET}",
+            options,
+        )
+        .await
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(err.contains("<string>"), "{}", err);
+        assert!(err.contains("CASBIN_TEST_CONFIG_UNSET"), "{}", err);
+        assert!(err.contains(", 2, "), "{}", err);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_parse_errors() {
+        for (text, line, message) in [
+            ("a = 1\nbogus\n", 2, "expected `option = value`"),
+            ("# ok\n[abc\n", 2, "invalid section header"),
+            ("[]\n", 1, "invalid section header"),
+            ("a = 1\n\n = 2\n", 3, "missing option name"),
+            ("a = ${UNTERMINATED\n", 1, "unterminated"),
+            ("include =\n", 1, "missing include path"),
+        ] {
+            let options =
+                ConfigOptions::new().includes(true).interpolation(true);
+            match Config::from_str_with_options(text, options).await {
+                Err(Error::ConfigError(ConfigError::Parse(origin, n, msg))) => {
+                    assert_eq!(origin, "<string>");
+                    assert_eq!(n, line, "{}", text);
+                    assert!(msg.contains(message), "{}", msg);
+                    assert!(!msg.contains("bogus"), "{}", msg);
+                }
+                _ => panic!("`{}` should not parse", text),
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_include() {
+        let dir = std::env::temp_dir()
+            .join(format!("casbin_config_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        std::fs::write(
+            dir.join("shared/matchers.conf"),
+            "# shared matcher\n\
+             m = r.sub == p.sub\n\
+             [policy_effect]\n\
+             e = some(where (p.eft == allow))\n",
+        )
+        .unwrap();
+        let text = "[request_definition]\n\
+                    r = sub, obj, act\n\
+                    [policy_definition]\n\
+                    p = sub, obj, act\n\
+                    [matchers]\n\
+                    include = ./shared/../shared/matchers.conf\n\
+                    m2 = r.obj == p.obj\n";
+        std::fs::write(dir.join("model.conf"), text).unwrap();
+        std::fs::write(dir.join("a.conf"), "include = shared/b.conf\n")
+            .unwrap();
+        std::fs::write(dir.join("shared/b.conf"), "include = ../a.conf\n")
+            .unwrap();
+
+        let plain = Config::from_file(dir.join("model.conf")).await.unwrap();
+        assert_eq!(plain.get("matchers::m"), None);
+        assert_eq!(
+            plain.get("matchers::include"),
+            Some("./shared/../shared/matchers.conf")
+        );
+
+        let options = ConfigOptions::new().includes(true);
+        let mut config =
+            Config::from_file_with_options(dir.join("model.conf"), options)
+                .await
+                .unwrap();
+        assert_eq!(config.get("matchers::m"), Some("r.sub == p.sub"));
+        assert_eq!(config.get("matchers::m2"), Some("r.obj == p.obj"));
+        assert_eq!(
+            config.get("policy_effect::e"),
+            Some("some(where (p.eft == allow))")
+        );
+        assert_eq!(config.to_string(), text);
+
+        config.set("matchers::m", "r.sub == p.sub && r.act == p.act");
+        config.set("policy_effect::e", "some(where (p.eft == allow))");
+        assert_eq!(
+            config.to_string(),
+            text.to_owned() + "m = r.sub == p.sub && r.act == p.act\n"
+        );
+
+        let model = DefaultModel::from_config(config).unwrap();
+        assert!(model.get_model()["m"].contains_key("m2"));
+
+        let err = Config::from_file_with_options(dir.join("a.conf"), options)
+            .await
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("includes itself"), "{}", err);
+
+        let err =
+            Config::from_str_with_options("include = missing.conf", options)
+                .await
+                .err()
+                .unwrap()
+                .to_string();
+        assert!(err.contains("cannot read `missing.conf`"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,6 +1,6 @@
 use crate::{
     enforcer::EnforceContext, model::OperatorFunction, Adapter, Effector,
-    EnforceArgs, Event, EventEmitter, Filter, Model, Result, RoleManager,
+    EnforceArgs, Event, EventEmitter, Model, PolicyFilter, Result, RoleManager,
     TryIntoAdapter, TryIntoModel,
 };
 
@@ -13,6 +13,9 @@ use crate::Logger;
 #[cfg(feature = "incremental")]
 use crate::emitter::EventData;
 
+#[cfg(feature = "explain")]
+use crate::Explanation;
+
 use async_trait::async_trait;
 use parking_lot::RwLock;
 
@@ -32,6 +35,8 @@ pub trait CoreApi: Send + Sync {
     ) -> Result<Self>
     where
         Self: Sized;
+    /// Registers a custom function for the matchers. The functions they call
+    /// are checked on the first enforce, so register them before it.
     fn add_function(&mut self, fname: &str, f: OperatorFunction);
     fn get_model(&self) -> &dyn Model;
     fn get_mut_model(&mut self) -> &mut dyn Model;
@@ -70,20 +75,43 @@ pub trait CoreApi: Send + Sync {
     where
         Self: Sized;
     fn enforce_mut<ARGS: EnforceArgs>(&mut self, rvals: ARGS) -> Result<bool>
+    where
+        Self: Sized;
+    fn batch_enforce<ARGS: EnforceArgs>(
+        &self,
+        rvals: Vec<ARGS>,
+    ) -> Result<Vec<bool>>
+    where
+        Self: Sized;
+    #[cfg(feature = "parallel")]
+    fn par_batch_enforce<ARGS: EnforceArgs>(
+        &self,
+        rvals: Vec<ARGS>,
+    ) -> Result<Vec<bool>>
     where
         Self: Sized;
     #[cfg(feature = "explain")]
-    fn enforce_ex<ARGS: EnforceArgs>(
+    fn enforce_ex<ARGS: EnforceArgs>(&self, rvals: ARGS) -> Result<Explanation>
+    where
+        Self: Sized;
+    #[cfg(feature = "explain")]
+    fn enforce_ex_with_context<ARGS: EnforceArgs>(
         &self,
+        ctx: EnforceContext,
         rvals: ARGS,
-    ) -> Result<(bool, Vec<Vec<String>>)>
+    ) -> Result<Explanation>
     where
         Self: Sized;
     fn build_role_links(&mut self) -> Result<()>;
     #[cfg(feature = "incremental")]
     fn build_incremental_role_links(&mut self, d: EventData) -> Result<()>;
     async fn load_policy(&mut self) -> Result<()>;
-    async fn load_filtered_policy<'a>(&mut self, f: Filter<'a>) -> Result<()>;
+    /// Loads the rules selected by `f`, either a [`PolicyFilter`] or a
+    /// positional [`Filter`](crate::Filter).
+    async fn load_filtered_policy<F>(&mut self, f: F) -> Result<()>
+    where
+        F: Into<PolicyFilter> + Send,
+        Self: Sized;
     fn is_filtered(&self) -> bool;
     fn is_enabled(&self) -> bool;
     async fn save_policy(&mut self) -> Result<()>;
@@ -0,0 +1,315 @@
+use crate::{RbacApi, Result};
+
+use async_trait::async_trait;
+
+use std::{collections::HashSet, ops::Range};
+
+#[async_trait]
+pub trait DomainRbacApi: RbacApi {
+    fn get_users_for_role_in_domain(
+        &self,
+        name: &str,
+        domain: &str,
+    ) -> Vec<String>;
+    fn get_roles_for_user_in_domain(
+        &self,
+        name: &str,
+        domain: &str,
+    ) -> Vec<String>;
+    fn get_permissions_for_user_in_domain(
+        &self,
+        user: &str,
+        domain: &str,
+    ) -> Vec<Vec<String>>;
+    async fn delete_roles_for_user_in_domain(
+        &mut self,
+        user: &str,
+        domain: &str,
+    ) -> Result<bool>;
+    /// Returns the domains the role manager holds links for.
+    fn get_all_domains(&self) -> Vec<String>;
+    /// Removes every policy and role link of the given domains. Fails if
+    /// `p` has no `dom` field.
+    async fn delete_domains(&mut self, domains: Vec<&str>) -> Result<bool>;
+    fn get_all_users_by_domain(&self, domain: &str) -> Vec<String>;
+}
+
+#[async_trait]
+impl<T> DomainRbacApi for T
+where
+    T: RbacApi,
+{
+    fn get_users_for_role_in_domain(
+        &self,
+        name: &str,
+        domain: &str,
+    ) -> Vec<String> {
+        self.get_role_manager().read().get_users(name, Some(domain))
+    }
+
+    fn get_roles_for_user_in_domain(
+        &self,
+        name: &str,
+        domain: &str,
+    ) -> Vec<String> {
+        self.get_role_manager().read().get_roles(name, Some(domain))
+    }
+
+    fn get_permissions_for_user_in_domain(
+        &self,
+        user: &str,
+        domain: &str,
+    ) -> Vec<Vec<String>> {
+        self.get_permissions_for_user(user, Some(domain))
+    }
+
+    async fn delete_roles_for_user_in_domain(
+        &mut self,
+        user: &str,
+        domain: &str,
+    ) -> Result<bool> {
+        self.delete_roles_for_user(user, Some(domain)).await
+    }
+
+    fn get_all_domains(&self) -> Vec<String> {
+        self.get_role_manager().read().get_all_domains()
+    }
+
+    async fn delete_domains(&mut self, domains: Vec<&str>) -> Result<bool> {
+        if domains.is_empty() {
+            return Ok(false);
+        }
+
+        let field_index = self.get_field_index("p", "dom")?;
+        let fields = grouping_domain_indices(self);
+        let mut removed = false;
+        for domain in domains {
+            removed |= self
+                .remove_filtered_policy(field_index, vec![domain.to_owned()])
+                .await?;
+            for i in fields.clone() {
+                removed |= self
+                    .remove_filtered_grouping_policy(i, vec![domain.to_owned()])
+                    .await?;
+            }
+            self.get_role_manager().write().delete_domain(domain)?;
+        }
+
+        Ok(removed)
+    }
+
+    fn get_all_users_by_domain(&self, domain: &str) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let grouping_users = grouping_domain_indices(self).flat_map(|i| {
+            self.get_filtered_grouping_policy(i, vec![domain.to_owned()])
+        });
+        let policy_users = self
+            .get_filtered_policy(
+                self.policy_domain_index(),
+                vec![domain.to_owned()],
+            )
+            .into_iter();
+
+        grouping_users
+            .chain(policy_users)
+            .filter_map(|mut rule| {
+                let user = rule.swap_remove(0);
+                if seen.insert(user.clone()) {
+                    Some(user)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+// positions of the domain fields of `g`, following the two names
+fn grouping_domain_indices<T: RbacApi + ?Sized>(e: &T) -> Range<usize> {
+    let count = e
+        .get_model()
+        .get_model()
+        .get("g")
+        .and_then(|ast_map| ast_map.get("g"))
+        .and_then(|ast| ast.role_arity().ok())
+        .unwrap_or(2);
+    2..count
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    fn sort_unstable<T: Ord>(mut v: Vec<T>) -> Vec<T> {
+        v.sort_unstable();
+        v
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_domain_rbac_api() {
+        let m =
+            DefaultModel::from_file("examples/rbac_with_domains_model.conf")
+                .await
+                .unwrap();
+
+        let adapter = FileAdapter::new("examples/rbac_with_domains_policy.csv");
+        let e = Enforcer::new(m, adapter).await.unwrap();
+
+        assert_eq!(
+            vec!["alice"],
+            e.get_users_for_role_in_domain("admin", "domain1")
+        );
+        assert_eq!(
+            vec!["bob"],
+            e.get_users_for_role_in_domain("admin", "domain2")
+        );
+        assert_eq!(
+            vec!["admin"],
+            e.get_roles_for_user_in_domain("alice", "domain1")
+        );
+        assert!(e
+            .get_roles_for_user_in_domain("alice", "domain2")
+            .is_empty());
+        assert_eq!(
+            vec![
+                vec!["admin", "domain1", "data1", "read"],
+                vec!["admin", "domain1", "data1", "write"],
+            ],
+            sort_unstable(
+                e.get_permissions_for_user_in_domain("admin", "domain1")
+            )
+        );
+        assert_eq!(
+            vec!["domain1", "domain2"],
+            sort_unstable(e.get_all_domains())
+        );
+        assert_eq!(
+            vec!["alice", "admin"],
+            e.get_all_users_by_domain("domain1")
+        );
+        assert!(e.get_all_users_by_domain("domain3").is_empty());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_delete_domains() {
+        let m =
+            DefaultModel::from_file("examples/rbac_with_domains_model.conf")
+                .await
+                .unwrap();
+
+        let mut e = Enforcer::new(m, MemoryAdapter::default()).await.unwrap();
+        e.add_policies(
+            vec![
+                vec!["admin", "domain1", "data1", "read"],
+                vec!["admin", "domain2", "data2", "read"],
+            ]
+            .into_iter()
+            .map(|p| p.into_iter().map(ToOwned::to_owned).collect())
+            .collect(),
+        )
+        .await
+        .unwrap();
+        e.add_role_for_user("alice", "admin", Some("domain1"))
+            .await
+            .unwrap();
+        e.add_role_for_user("bob", "admin", Some("domain2"))
+            .await
+            .unwrap();
+        e.add_role_for_user("carol", "admin", Some("domain1"))
+            .await
+            .unwrap();
+
+        assert!(e
+            .delete_roles_for_user_in_domain("alice", "domain1")
+            .await
+            .unwrap());
+        assert!(!e.enforce(("alice", "domain1", "data1", "read")).unwrap());
+        assert!(e.enforce(("bob", "domain2", "data2", "read")).unwrap());
+
+        assert!(e.delete_domains(vec!["domain2"]).await.unwrap());
+        assert!(!e.enforce(("bob", "domain2", "data2", "read")).unwrap());
+        assert_eq!(vec!["domain1"], e.get_all_domains());
+        assert_eq!(
+            vec![vec!["admin", "domain1", "data1", "read"]],
+            e.get_policy()
+        );
+        assert_eq!(
+            vec![vec!["carol", "admin", "domain1"]],
+            e.get_grouping_policy()
+        );
+
+        // nothing to delete
+        assert!(!e.delete_domains(vec![]).await.unwrap());
+        assert!(!e.delete_domains(vec!["domain3"]).await.unwrap());
+        assert_eq!(1, e.get_policy().len());
+
+        assert!(e.delete_domains(vec!["domain1"]).await.unwrap());
+        assert!(e.get_policy().is_empty());
+        assert!(e.get_all_domains().is_empty());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_delete_domains_without_dom() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let mut e = Enforcer::new(m, adapter).await.unwrap();
+        let policy = e.get_policy();
+
+        assert!(e.get_all_domains().is_empty());
+        assert!(e.delete_domains(vec!["data2"]).await.is_err());
+        assert_eq!(policy, e.get_policy());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_multiple_domains() {
+        let e = Enforcer::new(
+            "examples/rbac_with_multiple_domains_model.conf",
+            "examples/rbac_with_multiple_domains_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            vec!["eu", "tenant1", "tenant2", "us"],
+            sort_unstable(e.get_all_domains())
+        );
+        assert_eq!(vec!["bob"], e.get_all_users_by_domain("us"));
+        assert_eq!(vec!["alice", "bob"], e.get_all_users_by_domain("eu"));
+        assert_eq!(vec!["bob", "admin"], e.get_all_users_by_domain("tenant2"));
+    }
+}
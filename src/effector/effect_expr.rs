@@ -0,0 +1,444 @@
+use crate::{error::ModelError, Result};
+
+/// Parsed form of a `policy_effect` expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EffectExpr {
+    /// `some(where (p_eft == <effect>))`
+    Any(String),
+    /// `priority(p_eft)`, the first allow or deny decides
+    Priority,
+    /// `subjectPriority(p_eft)`, the allow or deny of the deepest subject
+    /// in the role hierarchy decides
+    SubjectPriority,
+    /// a bare `allow` or `deny`
+    Const(bool),
+    Not(Box<EffectExpr>),
+    And(Box<EffectExpr>, Box<EffectExpr>),
+    Or(Box<EffectExpr>, Box<EffectExpr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    Eq,
+}
+
+impl EffectExpr {
+    pub fn parse(expr: &str) -> Result<EffectExpr> {
+        let unsupported = || ModelError::E(expr.to_owned());
+
+        let tokens = tokenize(expr).ok_or_else(unsupported)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let res = parser.parse_or().ok_or_else(unsupported)?;
+
+        if parser.pos != parser.tokens.len() {
+            return Err(unsupported().into());
+        }
+
+        Ok(res)
+    }
+
+    /// Whether `some(where (p_eft == <effect>))` appears in the expression.
+    pub fn references(&self, effect: &str) -> bool {
+        match self {
+            EffectExpr::Any(name) => name == effect,
+            EffectExpr::Priority
+            | EffectExpr::SubjectPriority
+            | EffectExpr::Const(_) => false,
+            EffectExpr::Not(e) => e.references(effect),
+            EffectExpr::And(l, r) | EffectExpr::Or(l, r) => {
+                l.references(effect) || r.references(effect)
+            }
+        }
+    }
+
+    /// Whether `priority(p_eft)` appears in the expression.
+    pub fn references_priority(&self) -> bool {
+        match self {
+            EffectExpr::Priority => true,
+            EffectExpr::Any(_)
+            | EffectExpr::SubjectPriority
+            | EffectExpr::Const(_) => false,
+            EffectExpr::Not(e) => e.references_priority(),
+            EffectExpr::And(l, r) | EffectExpr::Or(l, r) => {
+                l.references_priority() || r.references_priority()
+            }
+        }
+    }
+
+    /// Whether `subjectPriority(p_eft)` appears in the expression.
+    pub fn references_subject_priority(&self) -> bool {
+        match self {
+            EffectExpr::SubjectPriority => true,
+            EffectExpr::Any(_)
+            | EffectExpr::Priority
+            | EffectExpr::Const(_) => false,
+            EffectExpr::Not(e) => e.references_subject_priority(),
+            EffectExpr::And(l, r) | EffectExpr::Or(l, r) => {
+                l.references_subject_priority()
+                    || r.references_subject_priority()
+            }
+        }
+    }
+
+    /// Evaluates the expression against the effects seen so far.
+    ///
+    /// Returns `None` while the result may still change, `finished` tells
+    /// that no more effects will be pushed. `priority` and
+    /// `subject_priority` hold the decisions picked so far.
+    pub fn settle<F>(
+        &self,
+        seen: &F,
+        priority: Option<bool>,
+        subject_priority: Option<bool>,
+        finished: bool,
+    ) -> Option<bool>
+    where
+        F: Fn(&str) -> bool,
+    {
+        match self {
+            EffectExpr::Any(name) => {
+                if seen(name) {
+                    Some(true)
+                } else if finished {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            EffectExpr::Priority => {
+                priority.or(if finished { Some(false) } else { None })
+            }
+            // a deeper subject may still come up, so wait for all effects
+            EffectExpr::SubjectPriority if finished => {
+                Some(subject_priority.unwrap_or(false))
+            }
+            EffectExpr::SubjectPriority => None,
+            EffectExpr::Const(b) => Some(*b),
+            EffectExpr::Not(e) => e
+                .settle(seen, priority, subject_priority, finished)
+                .map(|b| !b),
+            EffectExpr::And(l, r) => {
+                match (
+                    l.settle(seen, priority, subject_priority, finished),
+                    r.settle(seen, priority, subject_priority, finished),
+                ) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }
+            }
+            EffectExpr::Or(l, r) => {
+                match (
+                    l.settle(seen, priority, subject_priority, finished),
+                    r.settle(seen, priority, subject_priority, finished),
+                ) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// A `policy_effect` parsed once at model load, along with what effect
+/// streams look up on every pushed effect.
+#[derive(Debug)]
+pub struct ParsedEffect {
+    text: String,
+    expr: EffectExpr,
+    // effects named by `some(where (p_eft == <effect>))`, without repeats
+    named: Vec<String>,
+    priority: bool,
+    subject_priority: bool,
+}
+
+impl ParsedEffect {
+    pub fn parse(text: &str) -> Result<ParsedEffect> {
+        let expr = EffectExpr::parse(text)?;
+        let mut named = vec![];
+        collect_named(&expr, &mut named);
+
+        Ok(ParsedEffect {
+            text: text.to_owned(),
+            priority: expr.references_priority(),
+            subject_priority: expr.references_subject_priority(),
+            named,
+            expr,
+        })
+    }
+
+    /// The expression as written in the model.
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[inline]
+    pub fn expr(&self) -> &EffectExpr {
+        &self.expr
+    }
+
+    /// The effects named by the expression.
+    #[inline]
+    pub fn named(&self) -> &[String] {
+        &self.named
+    }
+
+    /// Index of `effect` in `named`, if the expression names it.
+    #[inline]
+    pub fn position(&self, effect: &str) -> Option<usize> {
+        self.named.iter().position(|x| x == effect)
+    }
+
+    #[inline]
+    pub fn references_priority(&self) -> bool {
+        self.priority
+    }
+
+    #[inline]
+    pub fn references_subject_priority(&self) -> bool {
+        self.subject_priority
+    }
+}
+
+fn collect_named(expr: &EffectExpr, named: &mut Vec<String>) {
+    match expr {
+        EffectExpr::Any(name) => {
+            if !named.contains(name) {
+                named.push(name.to_owned());
+            }
+        }
+        EffectExpr::Priority
+        | EffectExpr::SubjectPriority
+        | EffectExpr::Const(_) => {}
+        EffectExpr::Not(e) => collect_named(e, named),
+        EffectExpr::And(l, r) | EffectExpr::Or(l, r) => {
+            collect_named(l, named);
+            collect_named(r, named);
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Option<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '!' => Token::Not,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Eq,
+            c if is_ident_char(c) => {
+                let mut ident = c.to_string();
+                while let Some(c) = chars.next_if(|c| is_ident_char(*c)) {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            _ => return None,
+        };
+        tokens.push(token);
+    }
+
+    Some(tokens)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+// accepts both the escaped `p_eft` and the raw `p.eft`
+fn is_eft(ident: &str) -> bool {
+    ident.ends_with("_eft") || ident.ends_with(".eft")
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Option<()> {
+        self.eat(token).then_some(())
+    }
+
+    fn expect_eft(&mut self) -> Option<()> {
+        match self.next()? {
+            Token::Ident(ident) if is_eft(&ident) => Some(()),
+            _ => None,
+        }
+    }
+
+    // (p_eft)
+    fn expect_eft_call(&mut self) -> Option<()> {
+        self.expect(&Token::LParen)?;
+        self.expect_eft()?;
+        self.expect(&Token::RParen)
+    }
+
+    fn parse_or(&mut self) -> Option<EffectExpr> {
+        let mut lhs = self.parse_and()?;
+        while self.eat(&Token::Or) {
+            lhs = EffectExpr::Or(Box::new(lhs), Box::new(self.parse_and()?));
+        }
+        Some(lhs)
+    }
+
+    fn parse_and(&mut self) -> Option<EffectExpr> {
+        let mut lhs = self.parse_unary()?;
+        while self.eat(&Token::And) {
+            lhs = EffectExpr::And(Box::new(lhs), Box::new(self.parse_unary()?));
+        }
+        Some(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Option<EffectExpr> {
+        if self.eat(&Token::Not) {
+            return Some(EffectExpr::Not(Box::new(self.parse_unary()?)));
+        }
+
+        match self.next()? {
+            Token::LParen => {
+                let expr = self.parse_or()?;
+                self.expect(&Token::RParen)?;
+                Some(expr)
+            }
+            Token::Ident(ident) => match ident.as_str() {
+                "some" => self.parse_some(),
+                "priority" => {
+                    self.expect_eft_call()?;
+                    Some(EffectExpr::Priority)
+                }
+                "subjectPriority" => {
+                    self.expect_eft_call()?;
+                    Some(EffectExpr::SubjectPriority)
+                }
+                "allow" => Some(EffectExpr::Const(true)),
+                "deny" => Some(EffectExpr::Const(false)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // some(where (p_eft == <effect>))
+    fn parse_some(&mut self) -> Option<EffectExpr> {
+        self.expect(&Token::LParen)?;
+        self.expect(&Token::Ident("where".to_owned()))?;
+        self.expect(&Token::LParen)?;
+        self.expect_eft()?;
+        self.expect(&Token::Eq)?;
+        let effect = match self.next()? {
+            Token::Ident(effect) => effect,
+            _ => return None,
+        };
+        self.expect(&Token::RParen)?;
+        self.expect(&Token::RParen)?;
+
+        Some(EffectExpr::Any(effect))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn any(effect: &str) -> EffectExpr {
+        EffectExpr::Any(effect.to_owned())
+    }
+
+    #[test]
+    fn test_parse_effect_expr() {
+        assert_eq!(
+            any("allow"),
+            EffectExpr::parse("some(where (p_eft == allow))").unwrap()
+        );
+        assert_eq!(
+            any("allow"),
+            EffectExpr::parse("some( where(p.eft==allow) )").unwrap()
+        );
+        assert_eq!(
+            EffectExpr::And(
+                Box::new(any("allow")),
+                Box::new(EffectExpr::Not(Box::new(any("deny"))))
+            ),
+            EffectExpr::parse(
+                "some(where (p_eft == allow)) && !some(where (p_eft == deny))"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            EffectExpr::Or(
+                Box::new(EffectExpr::Priority),
+                Box::new(EffectExpr::Const(false))
+            ),
+            EffectExpr::parse("priority(p_eft) || deny").unwrap()
+        );
+        assert_eq!(
+            EffectExpr::Or(
+                Box::new(EffectExpr::SubjectPriority),
+                Box::new(EffectExpr::Const(false))
+            ),
+            EffectExpr::parse("subjectPriority(p.eft) || deny").unwrap()
+        );
+        assert_eq!(
+            EffectExpr::Not(Box::new(any("block"))),
+            EffectExpr::parse("!some(where (p2_eft == block))").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_effect_expr() {
+        for expr in [
+            "",
+            "some(where (p_eft == allow)",
+            "some(where (p_eft = allow))",
+            "some(where (p_sub == allow))",
+            "all(where (p_eft == allow))",
+            "some(where (p_eft == allow)) &&",
+            "priority(p_eft) deny",
+        ] {
+            assert!(EffectExpr::parse(expr).is_err(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_parsed_effect() {
+        let effect = ParsedEffect::parse(
+            "some(where (p_eft == allow)) && !some(where (p_eft == deny)) \
+             || some(where (p_eft == allow)) && priority(p_eft)",
+        )
+        .unwrap();
+        assert_eq!(["allow", "deny"], effect.named());
+        assert_eq!(Some(1), effect.position("deny"));
+        assert_eq!(None, effect.position("block"));
+        assert!(effect.references_priority());
+        assert!(!effect.references_subject_priority());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::push_index_if_explain;
+
+use std::sync::Arc;
+
+mod effect_expr;
+
+pub use effect_expr::{EffectExpr, ParsedEffect};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EffectKind {
+    Allow = 0,
+    Indeterminate = 1,
+    Deny = 2,
+}
+
+pub trait Effector: Send + Sync {
+    fn new_stream(&self, expr: &str, cap: usize) -> Box<dyn EffectorStream>;
+    /// Like `new_stream`, for an effect the model has already parsed.
+    fn new_parsed_stream(
+        &self,
+        effect: &Arc<ParsedEffect>,
+        cap: usize,
+    ) -> Box<dyn EffectorStream> {
+        self.new_stream(effect.text(), cap)
+    }
+}
+
+pub trait EffectorStream: Send + Sync {
+    fn next(&self) -> bool;
+    #[cfg(feature = "explain")]
+    fn explain(&self) -> Option<Vec<usize>>;
+    fn push_effect(&mut self, eft: EffectKind) -> bool;
+    /// Pushes the `eft` of a matched policy, which may be a custom effect.
+    fn push_named_effect(&mut self, eft: &str) -> bool {
+        self.push_effect(match eft {
+            "allow" => EffectKind::Allow,
+            "deny" => EffectKind::Deny,
+            _ => EffectKind::Indeterminate,
+        })
+    }
+    /// Pushes the `eft` of a matched policy together with the depth of its
+    /// subject in the role hierarchy, as needed by `subjectPriority(p_eft)`.
+    fn push_ranked_effect(&mut self, eft: &str, _depth: usize) -> bool {
+        self.push_named_effect(eft)
+    }
+}
+
+#[derive(Clone)]
+pub struct DefaultEffectStream {
+    done: bool,
+    res: bool,
+    effect: Arc<ParsedEffect>,
+    // whether each effect named by the expression was pushed
+    seen: Vec<bool>,
+    priority: Option<bool>,
+    // (depth, allowed, index) of the deepest decisive effect
+    subject_priority: Option<(usize, bool, usize)>,
+    idx: usize,
+    cap: usize,
+    #[cfg(feature = "explain")]
+    expl: Vec<usize>,
+}
+
+#[derive(Default)]
+pub struct DefaultEffector;
+
+impl Effector for DefaultEffector {
+    fn new_stream(&self, expr: &str, cap: usize) -> Box<dyn EffectorStream> {
+        let effect = ParsedEffect::parse(expr)
+            .unwrap_or_else(|_| panic!("unsupported effect: `{}`", expr));
+        self.new_parsed_stream(&Arc::new(effect), cap)
+    }
+
+    fn new_parsed_stream(
+        &self,
+        effect: &Arc<ParsedEffect>,
+        cap: usize,
+    ) -> Box<dyn EffectorStream> {
+        assert!(cap > 0);
+
+        Box::new(DefaultEffectStream {
+            done: false,
+            res: false,
+            seen: vec![false; effect.named().len()],
+            effect: Arc::clone(effect),
+            priority: None,
+            subject_priority: None,
+            cap,
+            idx: 0,
+            #[cfg(feature = "explain")]
+            expl: Vec::with_capacity(10),
+        })
+    }
+}
+
+impl DefaultEffectStream {
+    fn advance(&mut self) -> bool {
+        let finished = self.idx + 1 == self.cap;
+        let (effect, seen) = (&self.effect, &self.seen);
+        let settled = effect.expr().settle(
+            &|eft| effect.position(eft).is_some_and(|i| seen[i]),
+            self.priority,
+            self.subject_priority.map(|(_, allowed, _)| allowed),
+            finished,
+        );
+
+        if let Some(res) = settled {
+            self.done = true;
+            self.res = res;
+
+            #[cfg(feature = "explain")]
+            if let Some((_, _, idx)) = self.subject_priority {
+                if finished && self.effect.references_subject_priority() {
+                    self.expl.push(idx);
+                    self.expl.sort_unstable();
+                    self.expl.dedup();
+                }
+            }
+        }
+
+        if finished {
+            self.idx = self.cap;
+        } else {
+            self.idx += 1;
+        }
+
+        self.done
+    }
+}
+
+impl EffectorStream for DefaultEffectStream {
+    #[inline]
+    fn next(&self) -> bool {
+        assert!(self.done);
+        self.res
+    }
+
+    #[cfg(feature = "explain")]
+    #[inline]
+    fn explain(&self) -> Option<Vec<usize>> {
+        assert!(self.done);
+        if self.expl.is_empty() {
+            None
+        } else {
+            Some(self.expl.clone())
+        }
+    }
+
+    fn push_effect(&mut self, eft: EffectKind) -> bool {
+        match eft {
+            EffectKind::Allow => self.push_named_effect("allow"),
+            EffectKind::Deny => self.push_named_effect("deny"),
+            EffectKind::Indeterminate => self.advance(),
+        }
+    }
+
+    fn push_named_effect(&mut self, eft: &str) -> bool {
+        self.push_ranked_effect(eft, 0)
+    }
+
+    fn push_ranked_effect(&mut self, eft: &str, depth: usize) -> bool {
+        let mut relevant = false;
+
+        if eft == "allow" || eft == "deny" {
+            if self.priority.is_none() {
+                self.priority = Some(eft == "allow");
+                relevant |= self.effect.references_priority();
+            }
+            if self
+                .subject_priority
+                .is_none_or(|(deepest, _, _)| depth > deepest)
+            {
+                self.subject_priority = Some((depth, eft == "allow", self.idx));
+            }
+        }
+        if let Some(i) = self.effect.position(eft) {
+            self.seen[i] = true;
+            relevant = true;
+        }
+
+        if relevant {
+            push_index_if_explain!(self);
+        }
+
+        self.advance()
+    }
+}
@@ -4,7 +4,13 @@ use crate::core_api::CoreApi;
 #[cfg(feature = "cached")]
 use crate::cached_api::CachedApi;
 
-use std::{fmt, hash::Hash};
+use serde::{Deserialize, Serialize};
+
+use std::{convert::TryFrom, fmt, hash::Hash};
+
+/// Version of the serialized form of [`EventData`], bumped on any change
+/// peers running an older release could not read.
+pub const EVENT_DATA_VERSION: u32 = 1;
 
 #[derive(Hash, PartialEq, Eq)]
 pub enum Event {
@@ -15,18 +21,181 @@ pub enum Event {
 pub trait EventKey: Hash + PartialEq + Eq + Send + Sync {}
 impl<T> EventKey for T where T: Hash + PartialEq + Eq + Send + Sync {}
 
-#[derive(Clone)]
+/// EventData describes a policy change.
+///
+/// It serializes as an object holding a `version`, the `type` of the
+/// change and its fields, e.g. `{"version":1,"type":"AddPolicy","sec":"p",
+/// "ptype":"p","rule":["alice","data1","read"]}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "EventMessage", try_from = "EventMessage")]
 pub enum EventData {
     AddPolicy(String, String, Vec<String>),
     AddPolicies(String, String, Vec<Vec<String>>),
     RemovePolicy(String, String, Vec<String>),
     RemovePolicies(String, String, Vec<Vec<String>>),
     RemoveFilteredPolicy(String, String, Vec<Vec<String>>),
+    UpdatePolicy(String, String, Vec<String>, Vec<String>),
+    UpdatePolicies(String, String, Vec<Vec<String>>, Vec<Vec<String>>),
     SavePolicy(Vec<Vec<String>>),
+    /// The changes committed by a [`Transaction`](crate::Transaction).
+    Transaction(Vec<EventData>),
+    ClearPolicy,
+    ClearCache,
+}
+
+// the versioned wire format of EventData
+#[derive(Serialize, Deserialize)]
+struct EventMessage {
+    version: u32,
+    #[serde(flatten)]
+    event: EventKind,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum EventKind {
+    AddPolicy {
+        sec: String,
+        ptype: String,
+        rule: Vec<String>,
+    },
+    AddPolicies {
+        sec: String,
+        ptype: String,
+        rules: Vec<Vec<String>>,
+    },
+    RemovePolicy {
+        sec: String,
+        ptype: String,
+        rule: Vec<String>,
+    },
+    RemovePolicies {
+        sec: String,
+        ptype: String,
+        rules: Vec<Vec<String>>,
+    },
+    RemoveFilteredPolicy {
+        sec: String,
+        ptype: String,
+        rules: Vec<Vec<String>>,
+    },
+    UpdatePolicy {
+        sec: String,
+        ptype: String,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    },
+    UpdatePolicies {
+        sec: String,
+        ptype: String,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    },
+    SavePolicy {
+        rules: Vec<Vec<String>>,
+    },
+    Transaction {
+        changes: Vec<EventData>,
+    },
     ClearPolicy,
     ClearCache,
 }
 
+impl From<EventData> for EventMessage {
+    fn from(d: EventData) -> Self {
+        let event = match d {
+            EventData::AddPolicy(sec, ptype, rule) => {
+                EventKind::AddPolicy { sec, ptype, rule }
+            }
+            EventData::AddPolicies(sec, ptype, rules) => {
+                EventKind::AddPolicies { sec, ptype, rules }
+            }
+            EventData::RemovePolicy(sec, ptype, rule) => {
+                EventKind::RemovePolicy { sec, ptype, rule }
+            }
+            EventData::RemovePolicies(sec, ptype, rules) => {
+                EventKind::RemovePolicies { sec, ptype, rules }
+            }
+            EventData::RemoveFilteredPolicy(sec, ptype, rules) => {
+                EventKind::RemoveFilteredPolicy { sec, ptype, rules }
+            }
+            EventData::UpdatePolicy(sec, ptype, old_rule, new_rule) => {
+                EventKind::UpdatePolicy {
+                    sec,
+                    ptype,
+                    old_rule,
+                    new_rule,
+                }
+            }
+            EventData::UpdatePolicies(sec, ptype, old_rules, new_rules) => {
+                EventKind::UpdatePolicies {
+                    sec,
+                    ptype,
+                    old_rules,
+                    new_rules,
+                }
+            }
+            EventData::SavePolicy(rules) => EventKind::SavePolicy { rules },
+            EventData::Transaction(changes) => {
+                EventKind::Transaction { changes }
+            }
+            EventData::ClearPolicy => EventKind::ClearPolicy,
+            EventData::ClearCache => EventKind::ClearCache,
+        };
+
+        EventMessage {
+            version: EVENT_DATA_VERSION,
+            event,
+        }
+    }
+}
+
+impl TryFrom<EventMessage> for EventData {
+    type Error = String;
+
+    fn try_from(m: EventMessage) -> Result<Self, Self::Error> {
+        if m.version != EVENT_DATA_VERSION {
+            return Err(format!("unsupported event version: {}", m.version));
+        }
+
+        Ok(match m.event {
+            EventKind::AddPolicy { sec, ptype, rule } => {
+                EventData::AddPolicy(sec, ptype, rule)
+            }
+            EventKind::AddPolicies { sec, ptype, rules } => {
+                EventData::AddPolicies(sec, ptype, rules)
+            }
+            EventKind::RemovePolicy { sec, ptype, rule } => {
+                EventData::RemovePolicy(sec, ptype, rule)
+            }
+            EventKind::RemovePolicies { sec, ptype, rules } => {
+                EventData::RemovePolicies(sec, ptype, rules)
+            }
+            EventKind::RemoveFilteredPolicy { sec, ptype, rules } => {
+                EventData::RemoveFilteredPolicy(sec, ptype, rules)
+            }
+            EventKind::UpdatePolicy {
+                sec,
+                ptype,
+                old_rule,
+                new_rule,
+            } => EventData::UpdatePolicy(sec, ptype, old_rule, new_rule),
+            EventKind::UpdatePolicies {
+                sec,
+                ptype,
+                old_rules,
+                new_rules,
+            } => EventData::UpdatePolicies(sec, ptype, old_rules, new_rules),
+            EventKind::SavePolicy { rules } => EventData::SavePolicy(rules),
+            EventKind::Transaction { changes } => {
+                EventData::Transaction(changes)
+            }
+            EventKind::ClearPolicy => EventData::ClearPolicy,
+            EventKind::ClearCache => EventData::ClearCache,
+        })
+    }
+}
+
 impl fmt::Display for EventData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use EventData::*;
@@ -66,9 +235,28 @@ impl fmt::Display for EventData {
                 ptype,
                 p.len()
             ),
+            UpdatePolicy(ref sec, ref ptype, ref old, ref new) => write!(
+                f,
+                "Type: UpdatePolicy, Assertion: {}::{}, Old: {:?}, New: {:?}",
+                sec,
+                ptype,
+                old.join(", "),
+                new.join(", ")
+            ),
+            UpdatePolicies(ref sec, ref ptype, ref old, ref new) => write!(
+                f,
+                "Type: UpdatePolicies, Assertion: {}::{}, Removed: {}, Added: {}",
+                sec,
+                ptype,
+                old.len(),
+                new.len()
+            ),
             SavePolicy(ref p) => {
                 write!(f, "Type: SavePolicy, Saved: {}", p.len())
             }
+            Transaction(ref changes) => {
+                write!(f, "Type: Transaction, Changes: {}", changes.len())
+            }
             ClearPolicy => write!(f, "Type: ClearPolicy"),
             ClearCache => write!(f, "Type: ClearCache, Data: ClearCache"),
         }
@@ -96,7 +284,7 @@ pub(crate) fn notify_logger_and_watcher<T: CoreApi>(e: &mut T, d: EventData) {
     #[cfg(feature = "watcher")]
     {
         if let Some(w) = e.get_mut_watcher() {
-            w.update(d);
+            crate::watcher::notify_watcher(w, d);
         }
     }
 }
@@ -113,3 +301,46 @@ pub(crate) fn clear_cache<T: CoreApi + CachedApi<u64, bool>>(
     }
     ce.get_mut_cache().clear();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_data_wire_format() {
+        let d = EventData::UpdatePolicy(
+            "p".to_owned(),
+            "p".to_owned(),
+            vec!["alice".to_owned(), "data1".to_owned()],
+            vec!["bob".to_owned(), "data1".to_owned()],
+        );
+        let json = serde_json::to_value(&d).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "version": 1,
+                "type": "UpdatePolicy",
+                "sec": "p",
+                "ptype": "p",
+                "old_rule": ["alice", "data1"],
+                "new_rule": ["bob", "data1"],
+            }),
+            json
+        );
+        assert_eq!(d, serde_json::from_value(json).unwrap());
+
+        let d = EventData::Transaction(vec![d, EventData::ClearCache]);
+        let json = serde_json::to_string(&d).unwrap();
+        assert_eq!(d, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn test_event_data_unsupported_version() {
+        let json = r#"{"version":2,"type":"ClearPolicy"}"#;
+        let err = serde_json::from_str::<EventData>(json).unwrap_err();
+        assert!(err.to_string().contains("unsupported event version: 2"));
+        assert!(
+            serde_json::from_str::<EventData>(r#"{"type":"ClearPolicy"}"#)
+                .is_err()
+        );
+    }
+}
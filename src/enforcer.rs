@@ -1,20 +1,24 @@
 use crate::{
-    adapter::{Adapter, Filter},
+    adapter::{Adapter, PolicyFilter},
     convert::{EnforceArgs, TryIntoAdapter, TryIntoModel},
     core_api::CoreApi,
-    effector::{DefaultEffector, EffectKind, Effector},
+    effector::{DefaultEffector, EffectKind, Effector, ParsedEffect},
     emitter::{Event, EventData, EventEmitter},
     error::{ModelError, PolicyError, RequestError},
-    get_or_err, get_or_err_with_context,
+    get_or_err_with_context,
     management_api::MgmtApi,
-    model::{FunctionMap, Model, OperatorFunction},
-    rbac::{DefaultRoleManager, RoleManager},
+    model::{Assertion, FunctionMap, Model, OperatorFunction},
+    rbac::{ConditionalRoleManager, DefaultRoleManager, RoleManager},
     register_g_function,
-    util::{escape_assertion, escape_eval},
+    trace::{EnforceTrace, PolicyTrace},
+    util::escape_assertion,
     Result,
 };
 
-use crate::model::DefaultModel;
+use crate::model::{sections_to_json, DefaultModel};
+
+#[cfg(feature = "explain")]
+use crate::explanation::{Explanation, MatchedRule};
 
 #[cfg(any(feature = "logging", feature = "watcher"))]
 use crate::emitter::notify_logger_and_watcher;
@@ -26,16 +30,18 @@ use crate::watcher::Watcher;
 use crate::{DefaultLogger, Logger};
 
 use async_trait::async_trait;
+use hashlink::LruCache;
 use once_cell::sync::Lazy;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use rhai::{
     def_package,
     packages::{
         ArithmeticPackage, BasicArrayPackage, BasicMapPackage, LogicPackage,
         Package,
     },
-    Dynamic, Engine, EvalAltResult, ImmutableString, Scope,
+    Dynamic, Engine, EvalAltResult, ImmutableString, Scope, AST,
 };
+use serde::{Deserialize, Serialize};
 
 def_package! {
     pub CasbinPackage(lib) {
@@ -52,9 +58,30 @@ def_package! {
 
 static CASBIN_PACKAGE: Lazy<CasbinPackage> = Lazy::new(CasbinPackage::new);
 
-use std::{cmp::max, collections::HashMap, sync::Arc};
+use std::{
+    borrow::Cow,
+    cmp::max,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 type EventCallback = fn(&mut Enforcer, EventData);
+// rules given to `eval`, by their text
+type EvalCache = Mutex<LruCache<String, Arc<AST>>>;
+
+const EVAL_CACHE_CAPACITY: usize = 1000;
+type EnforceAsts<'a> = (
+    &'a Assertion,
+    &'a Assertion,
+    Arc<ParsedEffect>,
+    Cow<'a, AST>,
+);
+
+pub(crate) static DEFAULT_ENFORCE_CONTEXT: Lazy<EnforceContext> =
+    Lazy::new(|| EnforceContext::new(""));
 
 /// Enforcer is the main interface for authorization enforcement and policy management.
 pub struct Enforcer {
@@ -72,10 +99,14 @@ pub struct Enforcer {
     watcher: Option<Box<dyn Watcher>>,
     events: HashMap<Event, Vec<EventCallback>>,
     engine: Engine,
+    eval_cache: Arc<EvalCache>,
+    // whether the functions called by the matchers were found
+    functions_checked: AtomicBool,
     #[cfg(feature = "logging")]
     logger: Box<dyn Logger>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EnforceContext {
     pub r_type: String,
     pub p_type: String,
@@ -119,132 +150,26 @@ impl EventEmitter<Event> for Enforcer {
 }
 
 impl Enforcer {
-    pub(crate) fn private_enforce(
-        &self,
-        rvals: &[Dynamic],
-    ) -> Result<(bool, Option<Vec<usize>>)> {
-        if !self.enabled {
-            return Ok((true, None));
-        }
-
-        let mut scope: Scope = Scope::new();
-
-        let r_ast = get_or_err!(self, "r", ModelError::R, "request");
-        let p_ast = get_or_err!(self, "p", ModelError::P, "policy");
-        let m_ast = get_or_err!(self, "m", ModelError::M, "matcher");
-        let e_ast = get_or_err!(self, "e", ModelError::E, "effector");
-
-        if r_ast.tokens.len() != rvals.len() {
-            return Err(RequestError::UnmatchRequestDefinition(
-                r_ast.tokens.len(),
-                rvals.len(),
-            )
-            .into());
-        }
-
-        for (rtoken, rval) in r_ast.tokens.iter().zip(rvals.iter()) {
-            scope.push_constant_dynamic(rtoken, rval.to_owned());
+    // Custom functions can be added after the enforcer is built, so the
+    // calls made by the matchers are only checked on the first enforce.
+    fn check_functions(&self) -> Result<()> {
+        if self.functions_checked.load(Ordering::Relaxed) {
+            return Ok(());
         }
-
-        let policies = p_ast.get_policy();
-        let (policy_len, scope_len) = (policies.len(), scope.len());
-
-        let mut eft_stream =
-            self.eft.new_stream(&e_ast.value, max(policy_len, 1));
-        let m_ast_compiled = if let Some(default_model) =
+        if let Some(default_model) =
             self.model.as_any().downcast_ref::<DefaultModel>()
         {
-            default_model.get_compiled_matcher("m").ok_or_else(|| {
-                crate::error::Error::ModelError(crate::error::ModelError::M(
-                    "Matcher 'm' not compiled".to_string(),
-                ))
-            })?
-        } else {
-            // Fallback to original compilation (for other Model implementations)
-            &self
-                .engine
-                .compile_expression(escape_eval(&m_ast.value))
-                .map_err(Into::<Box<EvalAltResult>>::into)?
-        };
-
-        if policy_len == 0 {
-            for token in p_ast.tokens.iter() {
-                scope.push_constant(token, String::new());
-            }
-
-            let eval_result = self
-                .engine
-                .eval_ast_with_scope::<bool>(&mut scope, m_ast_compiled)?;
-            let eft = if eval_result {
-                EffectKind::Allow
-            } else {
-                EffectKind::Indeterminate
-            };
-
-            eft_stream.push_effect(eft);
-
-            return Ok((eft_stream.next(), None));
-        }
-
-        for pvals in policies {
-            scope.rewind(scope_len);
-
-            if p_ast.tokens.len() != pvals.len() {
-                return Err(PolicyError::UnmatchPolicyDefinition(
-                    p_ast.tokens.len(),
-                    pvals.len(),
-                )
-                .into());
-            }
-            for (ptoken, pval) in p_ast.tokens.iter().zip(pvals.iter()) {
-                scope.push_constant(ptoken, pval.to_owned());
-            }
-
-            let eval_result = self
-                .engine
-                .eval_ast_with_scope::<bool>(&mut scope, m_ast_compiled)?;
-            let eft = match p_ast.tokens.iter().position(|x| x == "p_eft") {
-                Some(j) if eval_result => {
-                    let p_eft = &pvals[j];
-                    if p_eft == "deny" {
-                        EffectKind::Deny
-                    } else if p_eft == "allow" {
-                        EffectKind::Allow
-                    } else {
-                        EffectKind::Indeterminate
-                    }
-                }
-                None if eval_result => EffectKind::Allow,
-                _ => EffectKind::Indeterminate,
-            };
-
-            if eft_stream.push_effect(eft) {
-                break;
-            }
+            default_model.check_matchers(Some(&self.fm))?;
         }
+        self.functions_checked.store(true, Ordering::Relaxed);
 
-        Ok((eft_stream.next(), {
-            #[cfg(feature = "explain")]
-            {
-                eft_stream.explain()
-            }
-            #[cfg(not(feature = "explain"))]
-            {
-                None
-            }
-        }))
+        Ok(())
     }
 
-    pub(crate) fn private_enforce_with_context(
+    fn get_enforce_asts(
         &self,
-        ctx: EnforceContext,
-        rvals: &[Dynamic],
-    ) -> Result<(bool, Option<Vec<usize>>)> {
-        if !self.enabled {
-            return Ok((true, None));
-        }
-
-        let mut scope: Scope = Scope::new();
+        ctx: &EnforceContext,
+    ) -> Result<EnforceAsts<'_>> {
         let r_ast = get_or_err_with_context!(
             self,
             "r",
@@ -274,6 +199,57 @@ impl Enforcer {
             "effector"
         );
 
+        let default_model = self.model.as_any().downcast_ref::<DefaultModel>();
+
+        let e_expr = if let Some(default_model) = default_model {
+            Arc::clone(default_model.get_effect(&ctx.e_type).ok_or_else(
+                || {
+                    crate::error::Error::ModelError(
+                        crate::error::ModelError::E(e_ast.value.clone()),
+                    )
+                },
+            )?)
+        } else {
+            Arc::new(ParsedEffect::parse(&e_ast.value)?)
+        };
+
+        let m_ast_compiled = if let Some(default_model) = default_model {
+            Cow::Borrowed(
+                default_model.get_compiled_matcher(&ctx.m_type).ok_or_else(
+                    || {
+                        crate::error::Error::ModelError(
+                            crate::error::ModelError::M(format!(
+                                "Matcher '{}' not compiled",
+                                ctx.m_type
+                            )),
+                        )
+                    },
+                )?,
+            )
+        } else {
+            // Fallback to original compilation (for other Model implementations)
+            Cow::Owned(
+                self.engine
+                    .compile_expression(&m_ast.value)
+                    .map_err(Into::<Box<EvalAltResult>>::into)?,
+            )
+        };
+
+        Ok((r_ast, p_ast, e_expr, m_ast_compiled))
+    }
+
+    // Evaluates a single request against already resolved assertions,
+    // `scope` is expected to be empty and may be reused between requests.
+    // Every policy scanned is recorded in `trace` when given.
+    fn eval_request(
+        &self,
+        scope: &mut Scope,
+        asts: &EnforceAsts<'_>,
+        rvals: &[Dynamic],
+        mut trace: Option<&mut Vec<PolicyTrace>>,
+    ) -> Result<(bool, Option<Vec<usize>>)> {
+        let (r_ast, p_ast, e_expr, m_ast_compiled) = asts;
+
         if r_ast.tokens.len() != rvals.len() {
             return Err(RequestError::UnmatchRequestDefinition(
                 r_ast.tokens.len(),
@@ -290,48 +266,48 @@ impl Enforcer {
         let (policy_len, scope_len) = (policies.len(), scope.len());
 
         let mut eft_stream =
-            self.eft.new_stream(&e_ast.value, max(policy_len, 1));
-        let m_ast_compiled = if let Some(default_model) =
-            self.model.as_any().downcast_ref::<DefaultModel>()
-        {
-            default_model.get_compiled_matcher(&ctx.m_type).ok_or_else(
-                || {
-                    crate::error::Error::ModelError(
-                        crate::error::ModelError::M(format!(
-                            "Matcher '{}' not compiled",
-                            ctx.m_type
-                        )),
-                    )
-                },
-            )?
-        } else {
-            // Fallback to original compilation (for other Model implementations)
-            &self
-                .engine
-                .compile_expression(escape_eval(&m_ast.value))
-                .map_err(Into::<Box<EvalAltResult>>::into)?
-        };
+            self.eft.new_parsed_stream(e_expr, max(policy_len, 1));
 
         if policy_len == 0 {
             for token in p_ast.tokens.iter() {
                 scope.push_constant(token, String::new());
             }
 
-            let eval_result = self
-                .engine
-                .eval_ast_with_scope::<bool>(&mut scope, m_ast_compiled)?;
+            let step = || PolicyTrace::new(None, &p_ast.tokens, &[]);
+            let eval_result = self.eval_matcher(
+                scope,
+                m_ast_compiled,
+                trace.as_deref_mut(),
+                step,
+            )?;
             let eft = if eval_result {
                 EffectKind::Allow
             } else {
                 EffectKind::Indeterminate
             };
 
-            eft_stream.push_effect(eft);
+            let done = eft_stream.push_effect(eft);
+            if let Some(trace) = trace {
+                let eft = if eval_result {
+                    "allow"
+                } else {
+                    "indeterminate"
+                };
+                trace.push(PolicyTrace {
+                    matched: Some(eval_result),
+                    effect: Some(eft.to_owned()),
+                    done,
+                    ..step()
+                });
+            }
 
             return Ok((eft_stream.next(), None));
         }
 
-        for pvals in policies {
+        let eft_index = p_ast.tokens.iter().position(|x| x == "p_eft");
+        let subject_priority = e_expr.references_subject_priority();
+
+        for (idx, pvals) in policies.iter().enumerate() {
             scope.rewind(scope_len);
 
             if p_ast.tokens.len() != pvals.len() {
@@ -345,25 +321,37 @@ impl Enforcer {
                 scope.push_constant(ptoken, pval.to_owned());
             }
 
-            let eval_result = self
-                .engine
-                .eval_ast_with_scope::<bool>(&mut scope, m_ast_compiled)?;
-            let eft = match p_ast.tokens.iter().position(|x| x == "p_eft") {
-                Some(j) if eval_result => {
-                    let p_eft = &pvals[j];
-                    if p_eft == "deny" {
-                        EffectKind::Deny
-                    } else if p_eft == "allow" {
-                        EffectKind::Allow
-                    } else {
-                        EffectKind::Indeterminate
-                    }
-                }
-                None if eval_result => EffectKind::Allow,
-                _ => EffectKind::Indeterminate,
+            let step = || PolicyTrace::new(Some(idx), &p_ast.tokens, pvals);
+            let eval_result = self.eval_matcher(
+                scope,
+                m_ast_compiled,
+                trace.as_deref_mut(),
+                step,
+            )?;
+            let eft = if !eval_result {
+                "indeterminate"
+            } else {
+                eft_index.map_or("allow", |j| pvals[j].as_str())
+            };
+            let done = if !eval_result {
+                eft_stream.push_effect(EffectKind::Indeterminate)
+            } else if subject_priority {
+                eft_stream
+                    .push_ranked_effect(eft, self.subject_depth(p_ast, pvals))
+            } else {
+                eft_stream.push_named_effect(eft)
             };
 
-            if eft_stream.push_effect(eft) {
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.push(PolicyTrace {
+                    matched: Some(eval_result),
+                    effect: Some(eft.to_owned()),
+                    done,
+                    ..step()
+                });
+            }
+
+            if done {
                 break;
             }
         }
@@ -380,6 +368,217 @@ impl Enforcer {
         }))
     }
 
+    // Runs the matcher, a failure is recorded in `trace` as the `step` it
+    // happened on
+    fn eval_matcher(
+        &self,
+        scope: &mut Scope,
+        m_ast: &AST,
+        trace: Option<&mut Vec<PolicyTrace>>,
+        step: impl FnOnce() -> PolicyTrace,
+    ) -> Result<bool> {
+        self.engine
+            .eval_ast_with_scope::<bool>(scope, m_ast)
+            .map_err(|err| {
+                if let Some(trace) = trace {
+                    trace.push(PolicyTrace {
+                        error: Some(err.to_string()),
+                        ..step()
+                    });
+                }
+                err.into()
+            })
+    }
+
+    // depth of the policy subject in the role hierarchy,
+    // used to rank effects for `subjectPriority(p_eft)`
+    fn subject_depth(&self, p_ast: &Assertion, pvals: &[String]) -> usize {
+        let field = |name: &str| {
+            let token = format!("{}_{}", p_ast.key, name);
+            p_ast.tokens.iter().position(|x| *x == token)
+        };
+        let subject = &pvals[field("sub").unwrap_or(0)];
+        let domain = field("dom").map(|j| pvals[j].as_str());
+
+        self.rm.read().get_depth(subject, domain)
+    }
+
+    // Resolves the policy `indices` reported by the effector into the rules
+    // they point at
+    #[cfg(feature = "explain")]
+    pub(crate) fn explain(
+        &self,
+        ctx: &EnforceContext,
+        authorized: bool,
+        indices: Option<Vec<usize>>,
+    ) -> Result<Explanation> {
+        let p_ast = get_or_err_with_context!(
+            self,
+            "p",
+            &ctx.p_type,
+            ModelError::P,
+            "policy"
+        );
+        let policies = p_ast.get_policy();
+        let rules = indices
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|index| {
+                policies.iter().nth(index).map(|rule| MatchedRule {
+                    ptype: ctx.p_type.clone(),
+                    index,
+                    rule: rule.clone(),
+                })
+            })
+            .collect();
+        let eft_token = format!("{}_eft", ctx.p_type);
+        let eft_index = p_ast.tokens.iter().position(|x| *x == eft_token);
+
+        Ok(Explanation::new(authorized, rules, eft_index, ctx.clone()))
+    }
+
+    pub(crate) fn private_enforce(
+        &self,
+        rvals: &[Dynamic],
+    ) -> Result<(bool, Option<Vec<usize>>)> {
+        self.private_enforce_with_context(&DEFAULT_ENFORCE_CONTEXT, rvals)
+    }
+
+    pub(crate) fn private_enforce_with_context(
+        &self,
+        ctx: &EnforceContext,
+        rvals: &[Dynamic],
+    ) -> Result<(bool, Option<Vec<usize>>)> {
+        if !self.enabled {
+            return Ok((true, None));
+        }
+
+        self.check_functions()?;
+        let asts = self.get_enforce_asts(ctx)?;
+
+        self.eval_request(&mut Scope::new(), &asts, rvals, None)
+    }
+
+    /// Enforces a request like `enforce`, recording how every policy rule
+    /// scanned evaluated. A failing evaluation is reported in the returned
+    /// trace rather than as an error, including calls to unknown functions.
+    pub fn enforce_trace<ARGS: EnforceArgs>(
+        &self,
+        rvals: ARGS,
+    ) -> Result<EnforceTrace> {
+        self.enforce_trace_with_context(DEFAULT_ENFORCE_CONTEXT.clone(), rvals)
+    }
+
+    pub fn enforce_trace_with_context<ARGS: EnforceArgs>(
+        &self,
+        ctx: EnforceContext,
+        rvals: ARGS,
+    ) -> Result<EnforceTrace> {
+        let rvals = rvals.try_into_vec()?;
+        let mut trace = EnforceTrace {
+            request: rvals.iter().map(|x| x.to_string()).collect(),
+            context: ctx,
+            steps: vec![],
+            allowed: None,
+            error: None,
+        };
+
+        if !self.enabled {
+            trace.allowed = Some(true);
+            return Ok(trace);
+        }
+
+        let asts = self.get_enforce_asts(&trace.context)?;
+        match self.eval_request(
+            &mut Scope::new(),
+            &asts,
+            &rvals,
+            Some(&mut trace.steps),
+        ) {
+            Ok((authorized, _)) => trace.allowed = Some(authorized),
+            Err(err) => trace.error = Some(err.to_string()),
+        }
+
+        Ok(trace)
+    }
+
+    pub(crate) fn private_batch_enforce(
+        &self,
+        rvalss: &[Vec<Dynamic>],
+    ) -> Result<Vec<bool>> {
+        if !self.enabled {
+            return Ok(vec![true; rvalss.len()]);
+        }
+
+        self.check_functions()?;
+        let asts = self.get_enforce_asts(&DEFAULT_ENFORCE_CONTEXT)?;
+        let mut scope: Scope = Scope::new();
+
+        rvalss
+            .iter()
+            .map(|rvals| {
+                scope.clear();
+                self.eval_request(&mut scope, &asts, rvals, None)
+                    .map(|(authorized, _)| authorized)
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "parallel")]
+    pub(crate) fn private_par_batch_enforce(
+        &self,
+        rvalss: &[Vec<Dynamic>],
+    ) -> Result<Vec<bool>> {
+        use rayon::prelude::*;
+
+        if !self.enabled {
+            return Ok(vec![true; rvalss.len()]);
+        }
+
+        self.check_functions()?;
+        let asts = self.get_enforce_asts(&DEFAULT_ENFORCE_CONTEXT)?;
+
+        rvalss
+            .par_iter()
+            .map_init(Scope::new, |scope, rvals| {
+                scope.clear();
+                self.eval_request(scope, &asts, rvals, None)
+                    .map(|(authorized, _)| authorized)
+            })
+            .collect()
+    }
+
+    // Replaces the `eval` of rhai by one evaluating rules written like the
+    // matchers, e.g. `r.sub.age > 18`, in the scope of the request. Rules
+    // are compiled once, the most recently used are kept in `cache`.
+    fn register_eval(engine: &mut Engine, cache: Arc<EvalCache>) {
+        engine.disable_symbol("eval");
+        engine
+            .register_custom_syntax(
+                ["eval", "(", "$expr$", ")"],
+                false,
+                move |ctx, inputs| {
+                    let rule =
+                        ctx.eval_expression_tree(&inputs[0])?.to_string();
+                    let compiled = cache.lock().get(&rule).cloned();
+                    let ast = match compiled {
+                        Some(ast) => ast,
+                        None => {
+                            let ast =
+                                Arc::new(ctx.engine().compile_expression(
+                                    escape_assertion(&rule),
+                                )?);
+                            cache.lock().insert(rule, Arc::clone(&ast));
+                            ast
+                        }
+                    };
+
+                    ctx.engine().eval_ast_with_scope(ctx.scope_mut(), &ast)
+                },
+            )
+            .expect("`eval` is a valid custom syntax");
+    }
+
     fn register_function(engine: &mut Engine, key: &str, f: OperatorFunction) {
         match f {
             OperatorFunction::Arg0(func) => {
@@ -415,6 +614,38 @@ impl Enforcer {
 
         Ok(())
     }
+
+    /// Serializes the model definitions together with every loaded policy
+    /// rule. Equal states always produce identical JSON.
+    pub fn export_state(&self) -> Result<String> {
+        sections_to_json(self.model.get_model())
+    }
+
+    /// Replaces the model and its policies with a state produced by
+    /// `export_state`. The adapter is left untouched. The role manager is
+    /// swapped for a new one when only one of the models has conditional
+    /// role definitions, and its old links are dropped either way.
+    pub fn import_state(&mut self, state: &str) -> Result<()> {
+        let mut model = DefaultModel::from_json(state)?;
+        model.check_matchers(None)?;
+        model.compile_matchers(&self.engine)?;
+        if has_conditional_roles(&model) != has_conditional_roles(&*self.model)
+        {
+            self.rm = role_manager_for(&model);
+        }
+        self.model = Box::new(model);
+        self.functions_checked.store(false, Ordering::Relaxed);
+        self.register_g_functions()?;
+
+        if self.auto_build_role_links {
+            self.build_role_links()?;
+        } else {
+            // the links of the replaced policy must not outlive it
+            self.rm.write().clear();
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -428,11 +659,14 @@ impl CoreApi for Enforcer {
         let adapter = a.try_into_adapter().await?;
         let fm = FunctionMap::default();
         let eft = Box::new(DefaultEffector);
-        let rm = Arc::new(RwLock::new(DefaultRoleManager::new(10)));
+        let rm = role_manager_for(&*model);
 
         let mut engine = Engine::new_raw();
 
         engine.register_global_module(CASBIN_PACKAGE.as_shared_module());
+        let eval_cache =
+            Arc::new(Mutex::new(LruCache::new(EVAL_CACHE_CAPACITY)));
+        Self::register_eval(&mut engine, Arc::clone(&eval_cache));
 
         for (key, &func) in fm.get_functions() {
             Self::register_function(&mut engine, key, func);
@@ -453,6 +687,8 @@ impl CoreApi for Enforcer {
             watcher: None,
             events: HashMap::new(),
             engine,
+            eval_cache,
+            functions_checked: AtomicBool::new(false),
             #[cfg(feature = "logging")]
             logger: Box::new(DefaultLogger::default()),
         };
@@ -462,10 +698,11 @@ impl CoreApi for Enforcer {
 
         e.register_g_functions()?;
 
-        // If using DefaultModel, compile matcher expressions
+        // If using DefaultModel, validate and compile matcher expressions
         if let Some(default_model) =
             e.model.as_any_mut().downcast_mut::<DefaultModel>()
         {
+            default_model.check_matchers(None)?;
             default_model.compile_matchers(&e.engine)?;
         }
 
@@ -570,6 +807,7 @@ impl CoreApi for Enforcer {
 
     async fn set_model<M: TryIntoModel>(&mut self, m: M) -> Result<()> {
         self.model = m.try_into_model().await?;
+        self.functions_checked.store(false, Ordering::Relaxed);
 
         // If using DefaultModel, recompile matcher expressions
         if let Some(default_model) =
@@ -632,15 +870,12 @@ impl CoreApi for Enforcer {
             );
 
             #[cfg(feature = "explain")]
-            if let Some(indices) = indices {
-                let all_rules = get_or_err!(self, "p", ModelError::P, "policy")
-                    .get_policy();
-
-                let rules: Vec<String> = indices
+            if indices.is_some() {
+                let rules = self
+                    .explain(&DEFAULT_ENFORCE_CONTEXT, authorized, indices)?
+                    .rules
                     .into_iter()
-                    .filter_map(|y| {
-                        all_rules.iter().nth(y).map(|x| x.join(", "))
-                    })
+                    .map(|x| x.rule.join(", "))
                     .collect();
 
                 self.logger.print_explain_log(rules);
@@ -689,7 +924,7 @@ impl CoreApi for Enforcer {
         let rvals = rvals.try_into_vec()?;
         #[allow(unused_variables)]
         let (authorized, indices) =
-            self.private_enforce_with_context(ctx, &rvals)?;
+            self.private_enforce_with_context(&ctx, &rvals)?;
 
         #[cfg(feature = "logging")]
         {
@@ -700,15 +935,12 @@ impl CoreApi for Enforcer {
             );
 
             #[cfg(feature = "explain")]
-            if let Some(indices) = indices {
-                let all_rules = get_or_err!(self, "p", ModelError::P, "policy")
-                    .get_policy();
-
-                let rules: Vec<String> = indices
+            if indices.is_some() {
+                let rules = self
+                    .explain(&ctx, authorized, indices)?
+                    .rules
                     .into_iter()
-                    .filter_map(|y| {
-                        all_rules.iter().nth(y).map(|x| x.join(", "))
-                    })
+                    .map(|x| x.rule.join(", "))
                     .collect();
 
                 self.logger.print_explain_log(rules);
@@ -722,29 +954,104 @@ impl CoreApi for Enforcer {
         self.enforce(rvals)
     }
 
+    /// Enforces a batch of requests against the default `r`/`p`/`e`/`m`
+    /// assertions, resolving the model and the compiled matcher only once.
+    ///
+    /// # Examples
+    /// ```
+    /// use casbin::prelude::*;
+    /// #[cfg(feature = "runtime-async-std")]
+    /// #[async_std::main]
+    /// async fn main() -> Result<()> {
+    ///     let e = Enforcer::new("examples/basic_model.conf", "examples/basic_policy.csv").await?;
+    ///     assert_eq!(
+    ///         vec![true, false],
+    ///         e.batch_enforce(vec![("alice", "data1", "read"), ("alice", "data1", "write")])?
+    ///     );
+    ///     Ok(())
+    /// }
+    ///
+    /// #[cfg(feature = "runtime-tokio")]
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let e = Enforcer::new("examples/basic_model.conf", "examples/basic_policy.csv").await?;
+    ///     assert_eq!(
+    ///         vec![true, false],
+    ///         e.batch_enforce(vec![("alice", "data1", "read"), ("alice", "data1", "write")])?
+    ///     );
+    ///
+    ///     Ok(())
+    /// }
+    /// #[cfg(all(not(feature = "runtime-async-std"), not(feature = "runtime-tokio")))]
+    /// fn main() {}
+    /// ```
+    fn batch_enforce<ARGS: EnforceArgs>(
+        &self,
+        rvals: Vec<ARGS>,
+    ) -> Result<Vec<bool>> {
+        let rvalss = rvals
+            .into_iter()
+            .map(|x| x.try_into_vec())
+            .collect::<Result<Vec<_>>>()?;
+        let results = self.private_batch_enforce(&rvalss)?;
+
+        #[cfg(feature = "logging")]
+        for (rvals, authorized) in rvalss.iter().zip(results.iter()) {
+            self.logger.print_enforce_log(
+                rvals.iter().map(|x| x.to_string()).collect(),
+                *authorized,
+                false,
+            );
+        }
+
+        Ok(results)
+    }
+
+    #[cfg(feature = "parallel")]
+    fn par_batch_enforce<ARGS: EnforceArgs>(
+        &self,
+        rvals: Vec<ARGS>,
+    ) -> Result<Vec<bool>> {
+        let rvalss = rvals
+            .into_iter()
+            .map(|x| x.try_into_vec())
+            .collect::<Result<Vec<_>>>()?;
+        let results = self.private_par_batch_enforce(&rvalss)?;
+
+        #[cfg(feature = "logging")]
+        for (rvals, authorized) in rvalss.iter().zip(results.iter()) {
+            self.logger.print_enforce_log(
+                rvals.iter().map(|x| x.to_string()).collect(),
+                *authorized,
+                false,
+            );
+        }
+
+        Ok(results)
+    }
+
     #[cfg(feature = "explain")]
     fn enforce_ex<ARGS: EnforceArgs>(
         &self,
         rvals: ARGS,
-    ) -> Result<(bool, Vec<Vec<String>>)> {
+    ) -> Result<Explanation> {
         let rvals = rvals.try_into_vec()?;
-        #[allow(unused_variables)]
         let (authorized, indices) = self.private_enforce(&rvals)?;
 
-        let rules = match indices {
-            Some(indices) => {
-                let all_rules = get_or_err!(self, "p", ModelError::P, "policy")
-                    .get_policy();
+        self.explain(&DEFAULT_ENFORCE_CONTEXT, authorized, indices)
+    }
 
-                indices
-                    .into_iter()
-                    .filter_map(|y| all_rules.iter().nth(y).cloned())
-                    .collect::<Vec<_>>()
-            }
-            None => vec![],
-        };
+    #[cfg(feature = "explain")]
+    fn enforce_ex_with_context<ARGS: EnforceArgs>(
+        &self,
+        ctx: EnforceContext,
+        rvals: ARGS,
+    ) -> Result<Explanation> {
+        let rvals = rvals.try_into_vec()?;
+        let (authorized, indices) =
+            self.private_enforce_with_context(&ctx, &rvals)?;
 
-        Ok((authorized, rules))
+        self.explain(&ctx, authorized, indices)
     }
 
     fn build_role_links(&mut self) -> Result<()> {
@@ -764,7 +1071,9 @@ impl CoreApi for Enforcer {
 
     async fn load_policy(&mut self) -> Result<()> {
         self.model.clear_policy();
+        self.eval_cache.lock().clear();
         self.adapter.load_policy(&mut *self.model).await?;
+        self.model.sort_policies_by_priority();
 
         if self.auto_build_role_links {
             self.build_role_links()?;
@@ -773,11 +1082,16 @@ impl CoreApi for Enforcer {
         Ok(())
     }
 
-    async fn load_filtered_policy<'a>(&mut self, f: Filter<'a>) -> Result<()> {
+    async fn load_filtered_policy<F>(&mut self, f: F) -> Result<()>
+    where
+        F: Into<PolicyFilter> + Send,
+    {
         self.model.clear_policy();
+        self.eval_cache.lock().clear();
         self.adapter
-            .load_filtered_policy(&mut *self.model, f)
+            .load_policy_with_filter(&mut *self.model, f.into())
             .await?;
+        self.model.sort_policies_by_priority();
 
         if self.auto_build_role_links {
             self.build_role_links()?;
@@ -806,7 +1120,11 @@ impl CoreApi for Enforcer {
 
         policies.extend(gpolicies);
 
-        #[cfg(any(feature = "logging", feature = "watcher"))]
+        #[cfg(feature = "watcher")]
+        if self.auto_notify_watcher {
+            self.emit(Event::PolicyChange, EventData::SavePolicy(policies));
+        }
+        #[cfg(all(feature = "logging", not(feature = "watcher")))]
         self.emit(Event::PolicyChange, EventData::SavePolicy(policies));
 
         Ok(())
@@ -818,8 +1136,13 @@ impl CoreApi for Enforcer {
             self.adapter.clear_policy().await?;
         }
         self.model.clear_policy();
+        self.eval_cache.lock().clear();
 
-        #[cfg(any(feature = "logging", feature = "watcher"))]
+        #[cfg(feature = "watcher")]
+        if self.auto_notify_watcher {
+            self.emit(Event::PolicyChange, EventData::ClearPolicy);
+        }
+        #[cfg(all(feature = "logging", not(feature = "watcher")))]
         self.emit(Event::PolicyChange, EventData::ClearPolicy);
 
         Ok(())
@@ -852,12 +1175,6 @@ impl CoreApi for Enforcer {
     #[cfg(feature = "watcher")]
     #[inline]
     fn enable_auto_notify_watcher(&mut self, auto_notify_watcher: bool) {
-        if !auto_notify_watcher {
-            self.off(Event::PolicyChange);
-        } else {
-            self.on(Event::PolicyChange, notify_logger_and_watcher);
-        }
-
         self.auto_notify_watcher = auto_notify_watcher;
     }
 
@@ -878,11 +1195,37 @@ impl CoreApi for Enforcer {
     }
 }
 
+// role definitions with condition fields, like `g = _, _, (_, _)`, need a
+// role manager able to evaluate them
+fn role_manager_for(model: &dyn Model) -> Arc<RwLock<dyn RoleManager>> {
+    if has_conditional_roles(model) {
+        Arc::new(RwLock::new(ConditionalRoleManager::new(10)))
+    } else {
+        Arc::new(RwLock::new(DefaultRoleManager::new(10)))
+    }
+}
+
+/// Whether a role definition has condition fields, like `g = _, _, (_, _)`,
+/// making decisions depend on more than the policy.
+pub(crate) fn has_conditional_roles(model: &dyn Model) -> bool {
+    model.get_model().get("g").is_some_and(|ast_map| {
+        ast_map.values().any(|ast| ast.condition_arity() > 0)
+    })
+}
+
 #[cfg(test)]
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::extra_unused_type_parameters,
+    clippy::useless_vec
+)]
 mod tests {
     use super::*;
     use crate::prelude::*;
 
+    #[cfg(not(target_arch = "wasm32"))]
+    use crate::adapter::file_adapter::TempPolicy;
+
     fn is_send<T: Send>() -> bool {
         true
     }
@@ -908,19 +1251,20 @@ mod tests {
     )]
     async fn test_enforcer_swap_adapter_type() {
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub, obj, act");
-        m.add_def("e", "e", "some(where (p.eft == allow))");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
         m.add_def(
             "m",
             "m",
             "r.sub == p.sub && keyMatch(r.obj, p.obj) && regexMatch(r.act, p.act)",
-        );
+        ).unwrap();
 
-        let file = FileAdapter::new("examples/basic_policy.csv");
+        let policy = TempPolicy::copy("examples/basic_policy.csv");
+        let file = policy.adapter();
         let mem = MemoryAdapter::default();
         let mut e = Enforcer::new(m, file).await.unwrap();
-        // this should fail since FileAdapter has basically no add_policy
+        // the rule is appended to the policy file
         assert!(e
             .adapter
             .add_policy(
@@ -954,14 +1298,14 @@ mod tests {
     )]
     async fn test_key_match_model_in_memory() {
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub, obj, act");
-        m.add_def("e", "e", "some(where (p.eft == allow))");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
         m.add_def(
             "m",
             "m",
             "r.sub == p.sub && keyMatch(r.obj, p.obj) && regexMatch(r.act, p.act)",
-        );
+        ).unwrap();
 
         let adapter = FileAdapter::new("examples/keymatch_policy.csv");
         let e = Enforcer::new(m, adapter).await.unwrap();
@@ -1054,14 +1398,14 @@ mod tests {
     )]
     async fn test_key_match_model_in_memory_deny() {
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub, obj, act");
-        m.add_def("e", "e", "!some(where (p.eft == deny))");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("e", "e", "!some(where (p.eft == deny))").unwrap();
         m.add_def(
             "m",
             "m",
             "r.sub == p.sub && keyMatch(r.obj, p.obj) && regexMatch(r.act, p.act)",
-        );
+        ).unwrap();
 
         let adapter = FileAdapter::new("examples/keymatch_policy.csv");
         let e = Enforcer::new(m, adapter).await.unwrap();
@@ -1072,6 +1416,146 @@ mod tests {
         );
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_custom_effect() {
+        let mut m = DefaultModel::default();
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act, eft").unwrap();
+        m.add_def(
+            "e",
+            "e",
+            "some( where(p.eft==permit) )&&!some(where (p.eft == block))",
+        )
+        .unwrap();
+        m.add_def(
+            "m",
+            "m",
+            "r.sub == p.sub && r.obj == p.obj && r.act == p.act",
+        )
+        .unwrap();
+
+        let mut e = Enforcer::new(m, MemoryAdapter::default()).await.unwrap();
+        for rule in [
+            ["alice", "data1", "read", "permit"],
+            ["alice", "data1", "write", "permit"],
+            ["alice", "data1", "write", "block"],
+            ["bob", "data1", "read", "allow"],
+        ] {
+            e.add_policy(rule.iter().map(|s| s.to_string()).collect())
+                .await
+                .unwrap();
+        }
+
+        assert!(e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(!e.enforce(("alice", "data1", "write")).unwrap());
+        assert!(!e.enforce(("bob", "data1", "read")).unwrap());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_subject_priority() {
+        let e = Enforcer::new(
+            "examples/subject_priority_model.conf",
+            "examples/subject_priority_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        assert!(e.enforce(("jane", "data1", "read")).unwrap());
+        assert!(e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(!e.enforce(("admin", "data1", "read")).unwrap());
+        assert!(!e.enforce(("bob", "data1", "read")).unwrap());
+        assert!(e.enforce(("editor", "data2", "write")).unwrap());
+        assert!(!e.enforce(("jane", "data2", "write")).unwrap());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_explicit_priority() {
+        let policy = TempPolicy::copy("examples/priority_policy_explicit.csv");
+        let mut e = Enforcer::new(
+            "examples/priority_model_explicit.conf",
+            policy.adapter(),
+        )
+        .await
+        .unwrap();
+
+        assert!(e.enforce(("alice", "data1", "write")).unwrap());
+        assert!(e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(!e.enforce(("bob", "data2", "read")).unwrap());
+        assert!(e.enforce(("bob", "data2", "write")).unwrap());
+        assert!(!e.enforce(("data1_deny_group", "data1", "read")).unwrap());
+        assert!(e.enforce(("data2_allow_group", "data2", "read")).unwrap());
+
+        let rule = |r: [&str; 5]| r.iter().map(|s| s.to_string()).collect();
+
+        e.add_policy(rule(["1", "bob", "data2", "write", "deny"]))
+            .await
+            .unwrap();
+        assert!(!e.enforce(("bob", "data2", "write")).unwrap());
+
+        e.update_policy(
+            rule(["1", "bob", "data2", "write", "deny"]),
+            rule(["20", "bob", "data2", "write", "deny"]),
+        )
+        .await
+        .unwrap();
+        assert!(e.enforce(("bob", "data2", "write")).unwrap());
+        assert_eq!(
+            vec!["1", "1", "1", "10", "10", "10", "10", "20"],
+            e.get_policy()
+                .into_iter()
+                .map(|p| p[0].clone())
+                .collect::<Vec<_>>()
+        );
+
+        // new rules go after the existing ones of the same priority
+        e.add_policies(vec![
+            rule(["15", "carol", "data3", "read", "allow"]),
+            rule(["1", "carol", "data3", "write", "allow"]),
+        ])
+        .await
+        .unwrap();
+        let policy = e.get_policy();
+        assert_eq!(
+            vec!["1", "1", "1", "1", "10", "10", "10", "10", "15", "20"],
+            policy.iter().map(|p| p[0].as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(rule(["1", "carol", "data3", "write", "allow"]), policy[3]);
+    }
+
+    #[test]
+    fn test_unsupported_effect() {
+        let mut m = DefaultModel::default();
+        assert!(m
+            .add_def("e", "e", "some(where (p.eft == allow)) ||")
+            .is_err());
+        assert!(m.add_def("e", "e", "all(where (p.eft == allow))").is_err());
+        assert!(m.get_model().get("e").is_none());
+    }
+
     use crate::RbacApi;
     #[cfg(not(target_arch = "wasm32"))]
     #[cfg_attr(
@@ -1084,15 +1568,16 @@ mod tests {
     )]
     async fn test_rbac_model_in_memory_indeterminate() {
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub, obj, act");
-        m.add_def("g", "g", "_, _");
-        m.add_def("e", "e", "some(where (p.eft == allow))");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("g", "g", "_, _").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
         m.add_def(
             "m",
             "m",
             "g(r.sub, p.sub) && r.obj == p.obj && r.act == p.act",
-        );
+        )
+        .unwrap();
 
         let adapter = MemoryAdapter::default();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
@@ -1119,15 +1604,16 @@ mod tests {
     )]
     async fn test_rbac_model_in_memory() {
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub, obj, act");
-        m.add_def("g", "g", "_, _");
-        m.add_def("e", "e", "some(where (p.eft == allow))");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("g", "g", "_, _").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
         m.add_def(
             "m",
             "m",
             "g(r.sub, p.sub) && r.obj == p.obj && r.act == p.act",
-        );
+        )
+        .unwrap();
 
         let adapter = MemoryAdapter::default();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
@@ -1192,15 +1678,16 @@ mod tests {
     )]
     async fn test_not_used_rbac_model_in_memory() {
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub, obj, act");
-        m.add_def("g", "g", "_, _");
-        m.add_def("e", "e", "some(where (p.eft == allow))");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("g", "g", "_, _").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
         m.add_def(
             "m",
             "m",
             "g(r.sub, p.sub) && r.obj == p.obj && r.act == p.act",
-        );
+        )
+        .unwrap();
 
         let adapter = MemoryAdapter::default();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
@@ -1288,7 +1775,8 @@ mod tests {
             .await
             .unwrap();
 
-        let adapter = FileAdapter::new("examples/basic_policy.csv");
+        let policy = TempPolicy::copy("examples/basic_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
         e.enable_auto_save(false);
         e.remove_policy(
@@ -1320,7 +1808,7 @@ mod tests {
         .await
         .unwrap();
         e.load_policy().await.unwrap();
-        assert_eq!(true, e.enforce(("alice", "data1", "read")).unwrap());
+        assert_eq!(false, e.enforce(("alice", "data1", "read")).unwrap());
         assert_eq!(false, e.enforce(("alice", "data1", "write")).unwrap());
         assert_eq!(false, e.enforce(("alice", "data2", "read")).unwrap());
         assert_eq!(false, e.enforce(("alice", "data2", "write")).unwrap());
@@ -1496,6 +1984,187 @@ mod tests {
         assert!(!e.enforce(("bob", "domain2", "data2", "write")).unwrap());
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_export_import_state() {
+        let mut e = Enforcer::new(
+            "examples/rbac_with_domains_model.conf",
+            "examples/rbac_with_domains_policy.csv",
+        )
+        .await
+        .unwrap();
+        e.enable_auto_save(false);
+        e.add_grouping_policy(
+            vec!["carol", "admin", "domain2"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+        let state = e.export_state().unwrap();
+        let mut other = Enforcer::new(
+            "examples/basic_model.conf",
+            MemoryAdapter::default(),
+        )
+        .await
+        .unwrap();
+        other.import_state(&state).unwrap();
+
+        assert_eq!(state, other.export_state().unwrap());
+        for rvals in [
+            ("alice", "domain1", "data1", "read"),
+            ("bob", "domain2", "data2", "write"),
+            ("carol", "domain2", "data2", "read"),
+            ("carol", "domain1", "data1", "read"),
+        ] {
+            assert_eq!(
+                e.enforce(rvals).unwrap(),
+                other.enforce(rvals).unwrap()
+            );
+        }
+        assert!(other
+            .enforce(("carol", "domain2", "data2", "read"))
+            .unwrap());
+        assert!(other.import_state("not json").is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_import_state_role_manager() {
+        let conditional = Enforcer::new(
+            "examples/rbac_with_conditions_model.conf",
+            "examples/rbac_with_conditions_policy.csv",
+        )
+        .await
+        .unwrap();
+        let plain = Enforcer::new(
+            "examples/rbac_model.conf",
+            "examples/rbac_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let mut e = Enforcer::new(
+            "examples/rbac_model.conf",
+            "examples/rbac_policy.csv",
+        )
+        .await
+        .unwrap();
+        e.import_state(&conditional.export_state().unwrap())
+            .unwrap();
+        assert!(e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(!e.enforce(("bob", "data2", "read")).unwrap());
+
+        let state = plain.export_state().unwrap();
+        e.import_state(&state).unwrap();
+        assert!(e.enforce(("alice", "data2", "read")).unwrap());
+
+        e.enable_auto_build_role_links(false);
+        e.import_state(&state).unwrap();
+        assert!(e
+            .get_role_manager()
+            .read()
+            .get_roles("alice", None)
+            .is_empty());
+        e.build_role_links().unwrap();
+        assert!(e.enforce(("alice", "data2", "read")).unwrap());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_policy_filter() {
+        use crate::{Error, FieldMatch};
+
+        let adapter = FileAdapter::new_filtered_adapter(
+            "examples/rbac_with_domains_policy.csv",
+        );
+        let mut e =
+            Enforcer::new("examples/rbac_with_domains_model.conf", adapter)
+                .await
+                .unwrap();
+
+        let filter = PolicyFilter::new()
+            .field("p", "dom", FieldMatch::one_of(["domain2"]))
+            .field("p", "act", FieldMatch::Glob("r*d".to_owned()))
+            .field("g", 2, FieldMatch::Prefix("domain2".to_owned()));
+
+        e.load_filtered_policy(filter).await.unwrap();
+        assert!(e.is_filtered());
+        assert_eq!(
+            e.get_policy(),
+            vec![vec!["admin", "domain2", "data2", "read"]]
+        );
+        assert_eq!(
+            e.get_grouping_policy(),
+            vec![vec!["bob", "admin", "domain2"]]
+        );
+        assert!(e.enforce(("bob", "domain2", "data2", "read")).unwrap());
+        assert!(!e.enforce(("alice", "domain1", "data1", "read")).unwrap());
+
+        let filter = PolicyFilter::new().field(
+            "p",
+            "tenant",
+            FieldMatch::Prefix("".to_owned()),
+        );
+        assert!(matches!(
+            e.load_filtered_policy(filter).await,
+            Err(Error::PolicyError(PolicyError::FieldNotFound(..)))
+        ));
+
+        let mut e = Enforcer::new(
+            "examples/rbac_with_domains_model.conf",
+            MemoryAdapter::default(),
+        )
+        .await
+        .unwrap();
+        e.add_policy(
+            vec!["admin", "domain1", "data1", "read"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        )
+        .await
+        .unwrap();
+        e.add_grouping_policy(
+            vec!["alice", "admin", "domain1"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+        e.load_filtered_policy(PolicyFilter::new().ptypes(["p"]))
+            .await
+            .unwrap();
+        assert!(e.is_filtered());
+        assert_eq!(e.get_policy().len(), 1);
+        assert!(e.get_grouping_policy().is_empty());
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[cfg_attr(
         all(feature = "runtime-async-std", not(target_arch = "wasm32")),
@@ -1523,6 +2192,150 @@ mod tests {
         assert!(e.enforce(("bob", "domain2", "data2", "write")).unwrap());
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_multiple_domains() {
+        let policy =
+            TempPolicy::copy("examples/rbac_with_multiple_domains_policy.csv");
+        let mut e = Enforcer::new(
+            "examples/rbac_with_multiple_domains_model.conf",
+            policy.adapter(),
+        )
+        .await
+        .unwrap();
+
+        assert!(e
+            .enforce(("alice", "tenant1", "eu", "data1", "read"))
+            .unwrap());
+        assert!(!e
+            .enforce(("alice", "tenant1", "us", "data1", "read"))
+            .unwrap());
+        assert!(e
+            .enforce(("bob", "tenant1", "us", "data1", "read"))
+            .unwrap());
+        assert!(e
+            .enforce(("bob", "tenant2", "eu", "data2", "write"))
+            .unwrap());
+        assert!(!e
+            .enforce(("bob", "tenant2", "us", "data2", "write"))
+            .unwrap());
+
+        e.remove_grouping_policy(
+            vec!["bob", "admin", "tenant1", "us"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+        .await
+        .unwrap();
+        assert!(!e
+            .enforce(("bob", "tenant1", "us", "data1", "read"))
+            .unwrap());
+
+        e.add_grouping_policy(
+            vec!["alice", "admin", "tenant1", "us"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+        .await
+        .unwrap();
+        assert!(e
+            .enforce(("alice", "tenant1", "us", "data1", "read"))
+            .unwrap());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_role_definition_arity() {
+        let mut m = DefaultModel::default();
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("g", "g", "_, _, _, _, _, _, _").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
+        m.add_def("m", "m", "g(r.sub, p.sub) && r.obj == p.obj")
+            .unwrap();
+
+        let err = Enforcer::new(m, MemoryAdapter::default()).await.err();
+        assert!(err.unwrap().to_string().contains("at most 6"));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_conditional_role_links() {
+        let policy =
+            TempPolicy::copy("examples/rbac_with_conditions_policy.csv");
+        let mut e = Enforcer::new(
+            "examples/rbac_with_conditions_model.conf",
+            policy.adapter(),
+        )
+        .await
+        .unwrap();
+
+        e.get_role_manager()
+            .write()
+            .add_link_condition_fn("onCall", |params| params == ["carol"])
+            .unwrap();
+
+        assert!(e.enforce(("alice", "data2", "read")).unwrap());
+        assert!(e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(!e.enforce(("bob", "data2", "read")).unwrap());
+        assert!(e.enforce(("carol", "data1", "read")).unwrap());
+        assert!(!e.enforce(("dave", "data1", "read")).unwrap());
+
+        e.add_grouping_policy(
+            vec!["bob", "contractor", "2000-01-01", "", ""]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+        .await
+        .unwrap();
+        assert!(e.enforce(("bob", "data2", "read")).unwrap());
+
+        // the expired rule of bob is still there
+        e.remove_grouping_policy(
+            ["bob", "contractor", "2000-01-01", "", ""]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+        .await
+        .unwrap();
+        assert!(!e.enforce(("bob", "data2", "read")).unwrap());
+
+        let err = DefaultRoleManager::new(10)
+            .add_conditional_link("bob", "contractor", &[], &["", "2001-01-01"])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            crate::Error::RbacError(
+                crate::error::RbacError::ConditionNotSupported
+            )
+        ));
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[cfg_attr(
         all(feature = "runtime-async-std", not(target_arch = "wasm32")),
@@ -1536,14 +2349,15 @@ mod tests {
         use serde::Serialize;
 
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub_rule, obj, act");
-        m.add_def("e", "e", "some(where (p.eft == allow))");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub_rule, obj, act").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
         m.add_def(
             "m",
             "m",
             "eval(p.sub_rule) && r.obj == p.obj && r.act == p.act",
-        );
+        )
+        .unwrap();
 
         let a = MemoryAdapter::default();
 
@@ -1590,6 +2404,55 @@ mod tests {
         );
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_abac_rule_model() {
+        use serde::Serialize;
+
+        #[derive(Serialize, Hash)]
+        pub struct Person<'a> {
+            name: &'a str,
+            age: u8,
+        }
+
+        let mut e = Enforcer::new(
+            "examples/abac_rule_model.conf",
+            "examples/abac_rule_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let alice = Person {
+            name: "alice",
+            age: 16,
+        };
+        let bob = Person {
+            name: "bob",
+            age: 19,
+        };
+
+        // twice, the second time with the rules already compiled
+        for _ in 0..2 {
+            assert!(!e.enforce((&alice, "/data1", "read")).unwrap());
+            assert!(e.enforce((&alice, "/data2", "write")).unwrap());
+            assert!(e.enforce((&bob, "/data1", "read")).unwrap());
+            assert!(!e.enforce((&bob, "/data2", "write")).unwrap());
+        }
+        assert_eq!(2, e.eval_cache.lock().len());
+
+        // rules of a former policy aren't kept
+        e.load_policy().await.unwrap();
+        assert!(e.eval_cache.lock().is_empty());
+        assert!(e.enforce((&bob, "/data1", "read")).unwrap());
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[cfg_attr(
         all(feature = "runtime-async-std", not(target_arch = "wasm32")),
@@ -1603,15 +2466,16 @@ mod tests {
         use serde::Serialize;
 
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub, obj, act");
-        m.add_def("e", "e", "some(where (p.eft == allow))");
-        m.add_def("g", "g", "_, _");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
+        m.add_def("g", "g", "_, _").unwrap();
         m.add_def(
             "m",
             "m",
             "(g(r.sub, p.sub) || eval(p.sub) == true) && r.act == p.act",
-        );
+        )
+        .unwrap();
 
         let a = MemoryAdapter::default();
 
@@ -1674,6 +2538,15 @@ mod tests {
     async fn test_enforce_ex() {
         use crate::adapter;
 
+        fn enforce_ex(
+            e: &Enforcer,
+            rvals: (&str, &str, &str),
+        ) -> Result<(bool, Vec<Vec<String>>)> {
+            e.enforce_ex(rvals).map(|x| {
+                (x.allowed, x.rules.into_iter().map(|y| y.rule).collect())
+            })
+        }
+
         let model = DefaultModel::from_file("examples/basic_model.conf")
             .await
             .unwrap();
@@ -1683,7 +2556,7 @@ mod tests {
         let e = Enforcer::new(model, adapter).await.unwrap();
 
         assert_eq!(
-            e.enforce_ex(("alice", "data1", "read")).unwrap(),
+            enforce_ex(&e, ("alice", "data1", "read")).unwrap(),
             (
                 true,
                 vec![vec![
@@ -1694,31 +2567,31 @@ mod tests {
             )
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data1", "write")).unwrap(),
+            enforce_ex(&e, ("alice", "data1", "write")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data2", "read")).unwrap(),
+            enforce_ex(&e, ("alice", "data2", "read")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data2", "write")).unwrap(),
+            enforce_ex(&e, ("alice", "data2", "write")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data1", "read")).unwrap(),
+            enforce_ex(&e, ("bob", "data1", "read")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data1", "write")).unwrap(),
+            enforce_ex(&e, ("bob", "data1", "write")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data2", "read")).unwrap(),
+            enforce_ex(&e, ("bob", "data2", "read")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data2", "write")).unwrap(),
+            enforce_ex(&e, ("bob", "data2", "write")).unwrap(),
             (
                 true,
                 vec![vec![
@@ -1737,7 +2610,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            e.enforce_ex(("alice", "data1", "read")).unwrap(),
+            enforce_ex(&e, ("alice", "data1", "read")).unwrap(),
             (
                 true,
                 vec![vec![
@@ -1748,11 +2621,11 @@ mod tests {
             )
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data1", "write")).unwrap(),
+            enforce_ex(&e, ("alice", "data1", "write")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data2", "read")).unwrap(),
+            enforce_ex(&e, ("alice", "data2", "read")).unwrap(),
             (
                 true,
                 vec![vec![
@@ -1763,7 +2636,7 @@ mod tests {
             )
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data2", "write")).unwrap(),
+            enforce_ex(&e, ("alice", "data2", "write")).unwrap(),
             (
                 true,
                 vec![vec![
@@ -1774,19 +2647,19 @@ mod tests {
             )
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data1", "read")).unwrap(),
+            enforce_ex(&e, ("bob", "data1", "read")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data1", "write")).unwrap(),
+            enforce_ex(&e, ("bob", "data1", "write")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data2", "read")).unwrap(),
+            enforce_ex(&e, ("bob", "data2", "read")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data2", "write")).unwrap(),
+            enforce_ex(&e, ("bob", "data2", "write")).unwrap(),
             (
                 true,
                 vec![vec![
@@ -1805,7 +2678,7 @@ mod tests {
         .unwrap();
 
         assert_eq!(
-            e.enforce_ex(("alice", "data1", "read")).unwrap(),
+            enforce_ex(&e, ("alice", "data1", "read")).unwrap(),
             (
                 true,
                 vec![vec![
@@ -1817,7 +2690,7 @@ mod tests {
             )
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data1", "write")).unwrap(),
+            enforce_ex(&e, ("alice", "data1", "write")).unwrap(),
             (
                 false,
                 vec![vec![
@@ -1829,19 +2702,19 @@ mod tests {
             )
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data2", "read")).unwrap(),
+            enforce_ex(&e, ("alice", "data2", "read")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("alice", "data2", "write")).unwrap(),
+            enforce_ex(&e, ("alice", "data2", "write")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data1", "write")).unwrap(),
+            enforce_ex(&e, ("bob", "data1", "write")).unwrap(),
             (false, vec![])
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data2", "read")).unwrap(),
+            enforce_ex(&e, ("bob", "data2", "read")).unwrap(),
             (
                 true,
                 vec![vec![
@@ -1853,7 +2726,7 @@ mod tests {
             )
         );
         assert_eq!(
-            e.enforce_ex(("bob", "data2", "write")).unwrap(),
+            enforce_ex(&e, ("bob", "data2", "write")).unwrap(),
             (
                 false,
                 vec![vec![
@@ -1866,6 +2739,159 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "explain")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_explanation() {
+        let e = Enforcer::new(
+            "examples/rbac_with_deny_model.conf",
+            "examples/rbac_with_deny_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let explanation = e.enforce_ex(("alice", "data1", "read")).unwrap();
+        assert_eq!(EffectKind::Allow, explanation.effect);
+        assert_eq!("p", explanation.rules[0].ptype);
+        assert_eq!(0, explanation.rules[0].index);
+        assert!(!explanation.cached);
+
+        let explanation = e.enforce_ex(("alice", "data2", "write")).unwrap();
+        assert!(!explanation.allowed);
+        assert_eq!(EffectKind::Deny, explanation.effect);
+        assert!(explanation
+            .rules
+            .iter()
+            .any(|x| x.index == 4
+                && x.rule == ["alice", "data2", "write", "deny"]));
+
+        let explanation = e.enforce_ex(("bob", "data1", "read")).unwrap();
+        assert_eq!(EffectKind::Indeterminate, explanation.effect);
+        assert!(explanation.rules.is_empty());
+
+        let e = Enforcer::new(
+            "examples/multi_section_model.conf",
+            "examples/multi_section_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let ctx = EnforceContext::new("2");
+        let explanation = e
+            .enforce_ex_with_context(ctx.clone(), ("james", "execute"))
+            .unwrap();
+        assert!(explanation.allowed);
+        assert_eq!(ctx, explanation.context);
+        assert_eq!("p2", explanation.rules[0].ptype);
+        assert_eq!(vec!["james", "execute"], explanation.rules[0].rule);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_enforce_trace() {
+        let e = Enforcer::new(
+            "examples/priority_model.conf",
+            "examples/priority_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let trace = e.enforce_trace(("alice", "data1", "write")).unwrap();
+        assert_eq!(Some(false), trace.allowed);
+        assert_eq!(vec!["alice", "data1", "write"], trace.request);
+
+        let steps = &trace.steps;
+        assert_eq!(3, steps.len());
+        assert_eq!(Some(0), steps[0].index);
+        assert_eq!("alice", steps[0].values["p_sub"]);
+        assert_eq!(Some(false), steps[0].matched);
+        assert_eq!(Some("indeterminate"), steps[0].effect.as_deref());
+        assert!(!steps[0].done);
+        assert_eq!(Some(true), steps[2].matched);
+        assert_eq!(Some("deny"), steps[2].effect.as_deref());
+        assert!(steps[2].done);
+
+        let json = serde_json::to_string(&trace).unwrap();
+        assert_eq!(trace, serde_json::from_str(&json).unwrap());
+
+        // `keyMatchCustom` is never registered
+        let e = Enforcer::new(
+            "examples/keymatch_custom_model.conf",
+            "examples/keymatch_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let trace = e.enforce_trace(("alice", "/alice_data/1", "GET")).unwrap();
+        assert_eq!(None, trace.allowed);
+        assert!(trace.error.is_some());
+        assert_eq!(1, trace.steps.len());
+        assert_eq!(None, trace.steps[0].matched);
+        assert!(trace.steps[0].error.is_some());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_batch_enforce() {
+        let mut e = Enforcer::new(
+            "examples/rbac_model.conf",
+            "examples/rbac_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let requests = vec![
+            ("alice", "data1", "read"),
+            ("alice", "data1", "write"),
+            ("alice", "data2", "read"),
+            ("alice", "data2", "write"),
+            ("bob", "data1", "read"),
+            ("bob", "data2", "write"),
+        ];
+        let expected = requests
+            .iter()
+            .map(|r| e.enforce(*r).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![true, false, true, true, false, true],
+            e.batch_enforce(requests.clone()).unwrap()
+        );
+        assert_eq!(expected, e.batch_enforce(requests.clone()).unwrap());
+        #[cfg(feature = "parallel")]
+        assert_eq!(expected, e.par_batch_enforce(requests.clone()).unwrap());
+
+        assert!(e
+            .batch_enforce(Vec::<(&str, &str)>::new())
+            .unwrap()
+            .is_empty());
+        assert!(e.batch_enforce(vec![("alice", "data1")]).is_err());
+
+        e.enable_enforce(false);
+        assert_eq!(vec![true; 6], e.batch_enforce(requests).unwrap());
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[cfg_attr(
         all(feature = "runtime-async-std", not(target_arch = "wasm32")),
@@ -16,6 +16,23 @@ pub enum ModelError {
     M(String),
     #[error("Other: `{0}`")]
     Other(String),
+    #[error("Invalid model: {}", join_errors(.0))]
+    Validation(Vec<ModelError>),
+}
+
+fn join_errors(errors: &[ModelError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// ConfigError represents any type of errors in a config file
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Invalid config at {0}:{1}: {2}")]
+    Parse(String, usize, String),
 }
 
 /// RequestError represents any type of errors in coming request
@@ -30,6 +47,8 @@ pub enum RequestError {
 pub enum PolicyError {
     #[error("Policy doesn't match policy definition. expected length: {0}, found length {1}")]
     UnmatchPolicyDefinition(usize, usize),
+    #[error("Field `{1}` not found in policy definition `{0}`")]
+    FieldNotFound(String, String),
 }
 
 /// RBAC error represents any type of errors in RBAC role manager
@@ -37,6 +56,8 @@ pub enum PolicyError {
 pub enum RbacError {
     #[error("Role `{0}` not found")]
     NotFound(String),
+    #[error("Role manager doesn't support link conditions")]
+    ConditionNotSupported,
 }
 /// AdapterError error represents any type of errors in adapter's execution
 #[derive(Error, Debug)]
@@ -49,6 +70,9 @@ pub enum Error {
     #[error("Casbin Io Error: `{0:?}`")]
     IoError(#[from] IoError),
 
+    #[error("Casbin Config Error: `{0:?}`")]
+    ConfigError(#[from] ConfigError),
+
     #[error("Casbin Model Error: `{0:?}`")]
     ModelError(#[from] ModelError),
 
@@ -71,6 +95,7 @@ pub enum Error {
 }
 
 #[cfg(test)]
+#[allow(clippy::extra_unused_type_parameters)]
 mod tests {
     use super::*;
 
@@ -0,0 +1,69 @@
+use crate::{effector::EffectKind, enforcer::EnforceContext};
+
+use std::fmt;
+
+/// A policy rule that took part in an enforcement decision.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchedRule {
+    /// Policy type of the rule, e.g. `p` or `p2`.
+    pub ptype: String,
+    /// Position of the rule among the policies of its `ptype`.
+    pub index: usize,
+    pub rule: Vec<String>,
+}
+
+/// Why an enforcement came to its decision, as returned by `enforce_ex`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    pub allowed: bool,
+    /// `Allow` when allowed, `Deny` when a matched `deny` rule refused the
+    /// request and `Indeterminate` when no rule allowed it.
+    pub effect: EffectKind,
+    pub rules: Vec<MatchedRule>,
+    pub context: EnforceContext,
+    /// Whether the decision was answered from the cache of a
+    /// `CachedEnforcer`.
+    pub cached: bool,
+}
+
+impl Explanation {
+    pub(crate) fn new(
+        allowed: bool,
+        rules: Vec<MatchedRule>,
+        eft_index: Option<usize>,
+        context: EnforceContext,
+    ) -> Self {
+        let denied = eft_index.is_some_and(|j| {
+            rules
+                .iter()
+                .any(|x| x.rule.get(j).is_some_and(|eft| eft == "deny"))
+        });
+        let effect = if allowed {
+            EffectKind::Allow
+        } else if denied {
+            EffectKind::Deny
+        } else {
+            EffectKind::Indeterminate
+        };
+
+        Explanation {
+            allowed,
+            effect,
+            rules,
+            context,
+            cached: false,
+        }
+    }
+}
+
+impl fmt::Display for MatchedRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}",
+            self.ptype,
+            self.index,
+            self.rule.join(", ")
+        )
+    }
+}
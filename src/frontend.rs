@@ -1,49 +1,164 @@
-use crate::{CoreApi, Enforcer};
-use std::collections::HashMap;
+use crate::{CoreApi, DomainRbacApi, Enforcer, MgmtApi, RbacApi};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
+// The subjects `user` acts as, that is itself and its implicit roles, for
+// every domain the role manager knows of
+struct Reach<'a> {
+    user: &'a str,
+    scopes: Vec<(Option<String>, HashSet<String>)>,
+}
+
+impl<'a> Reach<'a> {
+    fn new(e: &Enforcer, user: &'a str) -> Self {
+        let domains = e.get_all_domains();
+        let domains = if domains.is_empty() {
+            vec![None]
+        } else {
+            domains.into_iter().map(Some).collect()
+        };
+
+        let scopes = domains
+            .into_iter()
+            .map(|domain| {
+                let roles =
+                    e.get_implicit_roles_for_user(user, domain.as_deref());
+                (domain, roles.into_iter().collect())
+            })
+            .collect();
+
+        Self { user, scopes }
+    }
+
+    fn contains(&self, sub: &str, domain: Option<&str>) -> bool {
+        sub == self.user
+            || self.scopes.iter().any(|(scope, roles)| {
+                roles.contains(sub)
+                    && scope.as_deref().is_none_or(|scope| {
+                        domain.is_none_or(|domain| scope == domain)
+                    })
+            })
+    }
+}
+
+// Rules of `sec` prefixed with their ptype, keeping only those `keep`
+// accepts
+fn rules<F>(e: &Enforcer, sec: &str, mut keep: F) -> Vec<Vec<String>>
+where
+    F: FnMut(&str, &[String]) -> bool,
+{
+    let model = e.get_model();
+    let mut res = vec![];
+    for ptype in model
+        .get_model()
+        .get(sec)
+        .into_iter()
+        .flat_map(|x| x.keys())
+    {
+        for rule in model.get_policy(sec, ptype) {
+            if keep(ptype, &rule) {
+                let mut line = vec![ptype.to_owned()];
+                line.extend(rule);
+                res.push(line);
+            }
+        }
+    }
+    res
+}
+
+// The `p` rules granted to `user` directly or through one of its roles
+fn user_policies(e: &Enforcer, reach: &Reach) -> Vec<Vec<String>> {
+    rules(e, "p", |ptype, rule| {
+        let sub = e.get_field_index(ptype, "sub").unwrap_or(0);
+        let domain = e
+            .get_field_index(ptype, "dom")
+            .ok()
+            .and_then(|i| rule.get(i))
+            .map(String::as_str);
+        rule.get(sub).is_some_and(|sub| reach.contains(sub, domain))
+    })
+}
+
+/// Exports the model together with the policies reachable for `user`, in
+/// the shape the casbin.js authorizer loads.
+///
+/// Only `p` rules whose subject is the user or one of its implicit roles
+/// are kept, and only the `g` links starting from them. Other grouping
+/// types, such as `g2` for resources, are exported whole. The model is
+/// sent as its [normalized text](crate::Model::to_normalized_text), so the
+/// comments of the model file stay on the server.
 pub fn casbin_js_get_permission_for_user(
     e: &Enforcer,
-    _user: &str,
+    user: &str,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let model = e.get_model();
+    let reach = Reach::new(e, user);
     let mut m = HashMap::new();
 
-    m.insert("m", serde_json::Value::from(model.to_text()));
+    m.insert(
+        "m",
+        serde_json::Value::from(e.get_model().to_normalized_text()),
+    );
+    m.insert("p", serde_json::Value::from(user_policies(e, &reach)));
 
-    let mut p_rules = Vec::new();
-    if let Some(assertions) = model.get_model().get("p") {
-        for (ptype, _assertion) in assertions {
-            let policies = model.get_policy("p", ptype);
-            for rules in policies {
-                let mut rule = vec![ptype.to_string()];
-                rule.extend(rules);
-                p_rules.push(rule);
-            }
+    let g_rules = rules(e, "g", |ptype, rule| {
+        ptype != "g"
+            || rule.first().is_some_and(|sub| {
+                reach.contains(sub, rule.get(2).map(String::as_str))
+            })
+    });
+    m.insert("g", serde_json::Value::from(g_rules));
+
+    let result = serde_json::to_string(&m)?;
+    Ok(result)
+}
+
+/// Computes the permissions of `user` as a `{action: [objects]}` map, the
+/// format casbin.js `Authorizer.setPermission` expects.
+///
+/// A rule with an `eft` of `deny` removes its object from the map, as under
+/// the usual deny-override effect.
+pub fn casbin_js_get_permission_map(
+    e: &Enforcer,
+    user: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let reach = Reach::new(e, user);
+    let mut permissions: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    let mut denied = HashSet::new();
+
+    let policies = user_policies(e, &reach);
+    for line in &policies {
+        let (ptype, rule) = (&line[0], &line[1..]);
+        let obj = e.get_field_index(ptype, "obj")?;
+        let act = e.get_field_index(ptype, "act")?;
+        let (Some(obj), Some(act)) = (rule.get(obj), rule.get(act)) else {
+            continue;
+        };
+
+        let eft = e.get_field_index(ptype, "eft").ok();
+        if eft.and_then(|i| rule.get(i)).is_some_and(|x| x == "deny") {
+            denied.insert((act.as_str(), obj.as_str()));
+        } else {
+            permissions.entry(act).or_default().insert(obj);
         }
     }
-    m.insert("p", serde_json::Value::from(p_rules));
-
-    let mut g_rules = Vec::new();
-    if let Some(assertions) = model.get_model().get("g") {
-        for (ptype, _assertion) in assertions {
-            let policies = model.get_policy("g", ptype);
-            for rules in policies {
-                let mut rule = vec![ptype.to_string()];
-                rule.extend(rules);
-                g_rules.push(rule);
-            }
+
+    for (act, obj) in denied {
+        if let Some(objs) = permissions.get_mut(act) {
+            objs.remove(obj);
         }
     }
-    m.insert("g", serde_json::Value::from(g_rules));
+    permissions.retain(|_, objs| !objs.is_empty());
 
-    let result = serde_json::to_string(&m)?;
+    let result = serde_json::to_string(&permissions)?;
     Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::frontend::casbin_js_get_permission_for_user;
+    use crate::frontend::{
+        casbin_js_get_permission_for_user, casbin_js_get_permission_map,
+    };
     use crate::prelude::*;
+    use serde_json::{json, Value};
 
     #[cfg(not(target_arch = "wasm32"))]
     #[cfg_attr(
@@ -55,7 +170,6 @@ mod tests {
         tokio::test
     )]
     async fn test_casbin_js_get_permission_for_user() {
-        use serde_json::Value;
         use std::fs;
         use std::io::Read;
 
@@ -72,31 +186,109 @@ mod tests {
             .unwrap()
             .read_to_string(&mut expected_model)
             .unwrap();
-        let expected_model_str =
-            expected_model.replace("\r\n", "\n").replace("\n\n", "\n");
+        assert_eq!(
+            received["m"].as_str().unwrap(),
+            format!("{}\n", expected_model.trim_end())
+        );
 
         assert_eq!(
-            received["m"].as_str().unwrap().trim(),
-            expected_model_str.trim()
+            received["p"],
+            json!([
+                ["p", "alice", "data1", "read"],
+                ["p", "data1_admin", "data1", "read"],
+                ["p", "data1_admin", "data1", "write"],
+                ["p", "data2_admin", "data2", "read"],
+                ["p", "data2_admin", "data2", "write"],
+            ])
+        );
+        assert_eq!(
+            received["g"],
+            json!([
+                ["g", "alice", "admin"],
+                ["g", "admin", "data1_admin"],
+                ["g", "admin", "data2_admin"],
+            ])
         );
 
-        let mut expected_policies = String::new();
-        fs::File::open(policy_path)
-            .unwrap()
-            .read_to_string(&mut expected_policies)
-            .unwrap();
-        let expected_policies_items: Vec<&str> =
-            expected_policies.split(&[',', '\n'][..]).collect();
-
-        let mut i = 0;
-        for s_arr in received["p"].as_array().unwrap() {
-            for s in s_arr.as_array().unwrap() {
-                assert_eq!(
-                    s.as_str().unwrap().trim(),
-                    expected_policies_items[i].trim()
-                );
-                i += 1;
-            }
-        }
+        let received: Value = serde_json::from_str(
+            &casbin_js_get_permission_for_user(&e, "bob").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(received["p"], json!([["p", "bob", "data2", "write"]]));
+        assert_eq!(received["g"], json!([]));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_casbin_js_get_permission_for_user_with_domains() {
+        let mut e = Enforcer::new(
+            "examples/rbac_with_domains_model.conf",
+            "examples/rbac_with_domains_policy.csv",
+        )
+        .await
+        .unwrap();
+        e.enable_auto_save(false);
+        e.add_policy(
+            vec!["alice", "domain3", "data3", "read"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+        let received: Value = serde_json::from_str(
+            &casbin_js_get_permission_for_user(&e, "alice").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            received["p"],
+            json!([
+                ["p", "admin", "domain1", "data1", "read"],
+                ["p", "admin", "domain1", "data1", "write"],
+                ["p", "alice", "domain3", "data3", "read"],
+            ])
+        );
+        assert_eq!(received["g"], json!([["g", "alice", "admin", "domain1"]]));
+
+        let received: Value = serde_json::from_str(
+            &casbin_js_get_permission_map(&e, "alice").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            received,
+            json!({"read": ["data1", "data3"], "write": ["data1"]})
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_casbin_js_get_permission_map() {
+        let e = Enforcer::new(
+            "examples/rbac_with_deny_model.conf",
+            "examples/rbac_with_deny_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let received: Value = serde_json::from_str(
+            &casbin_js_get_permission_map(&e, "alice").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(received, json!({"read": ["data1", "data2"]}));
     }
 }
@@ -1,4 +1,6 @@
-use crate::{core_api::IEnforcer, Result};
+use crate::{core_api::IEnforcer, Model, Result};
+
+use std::collections::HashSet;
 
 #[cfg(any(
     feature = "watcher",
@@ -46,6 +48,28 @@ pub trait InternalApi: IEnforcer {
         field_index: usize,
         field_values: Vec<String>,
     ) -> Result<(bool, Vec<Vec<String>>)>;
+    async fn update_policy_internal(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> Result<bool>;
+    async fn update_policies_internal(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> Result<bool>;
+    async fn update_filtered_policies_internal(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        new_rules: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<(bool, Vec<Vec<String>>)>;
 }
 
 #[async_trait]
@@ -459,4 +483,401 @@ where
 
         Ok((rules_removed, rules))
     }
+
+    async fn update_policy_internal(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> Result<bool> {
+        if !can_update(
+            self.get_model(),
+            sec,
+            ptype,
+            std::slice::from_ref(&old_rule),
+            std::slice::from_ref(&new_rule),
+        ) {
+            return Ok(false);
+        }
+
+        if self.has_auto_save_enabled() {
+            let adapter_updated = match self
+                .get_mut_adapter()
+                .as_updatable_mut()
+            {
+                Some(adapter) => {
+                    adapter
+                        .update_policy(
+                            sec,
+                            ptype,
+                            old_rule.clone(),
+                            new_rule.clone(),
+                        )
+                        .await?
+                }
+                None => {
+                    let adapter = self.get_mut_adapter();
+                    if adapter
+                        .remove_policy(sec, ptype, old_rule.clone())
+                        .await?
+                    {
+                        match adapter
+                            .add_policy(sec, ptype, new_rule.clone())
+                            .await
+                        {
+                            Ok(true) => true,
+                            res => {
+                                // put the old rule back
+                                adapter
+                                    .add_policy(sec, ptype, old_rule.clone())
+                                    .await?;
+                                res?
+                            }
+                        }
+                    } else {
+                        false
+                    }
+                }
+            };
+            if !adapter_updated {
+                return Ok(false);
+            }
+        }
+
+        let rule_updated = self.get_mut_model().update_policy(
+            sec,
+            ptype,
+            old_rule.clone(),
+            new_rule.clone(),
+        );
+        #[cfg(any(feature = "watcher", feature = "logging"))]
+        {
+            let event_data = EventData::UpdatePolicy(
+                sec.to_owned(),
+                ptype.to_owned(),
+                old_rule.clone(),
+                new_rule.clone(),
+            );
+            #[cfg(feature = "watcher")]
+            {
+                if rule_updated && self.has_auto_notify_watcher_enabled() {
+                    self.emit(Event::PolicyChange, event_data);
+                }
+            }
+            #[cfg(not(feature = "watcher"))]
+            {
+                if rule_updated {
+                    self.emit(Event::PolicyChange, event_data);
+                }
+            }
+        }
+        #[cfg(feature = "cached")]
+        {
+            if rule_updated {
+                self.emit(Event::ClearCache, EventData::ClearCache);
+            }
+        }
+        if !rule_updated
+            || sec != "g"
+            || !self.has_auto_build_role_links_enabled()
+        {
+            return Ok(rule_updated);
+        }
+        #[cfg(not(feature = "incremental"))]
+        {
+            self.build_role_links()?;
+        }
+        #[cfg(feature = "incremental")]
+        {
+            self.build_incremental_role_links(EventData::UpdatePolicy(
+                sec.to_owned(),
+                ptype.to_owned(),
+                old_rule,
+                new_rule,
+            ))?;
+        }
+
+        Ok(rule_updated)
+    }
+
+    async fn update_policies_internal(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        if old_rules.len() != new_rules.len()
+            || !can_update(self.get_model(), sec, ptype, &old_rules, &new_rules)
+        {
+            return Ok(false);
+        }
+
+        if self.has_auto_save_enabled() {
+            let adapter_updated = match self
+                .get_mut_adapter()
+                .as_updatable_mut()
+            {
+                Some(adapter) => {
+                    adapter
+                        .update_policies(
+                            sec,
+                            ptype,
+                            old_rules.clone(),
+                            new_rules.clone(),
+                        )
+                        .await?
+                }
+                None => {
+                    let adapter = self.get_mut_adapter();
+                    if adapter
+                        .remove_policies(sec, ptype, old_rules.clone())
+                        .await?
+                    {
+                        match adapter
+                            .add_policies(sec, ptype, new_rules.clone())
+                            .await
+                        {
+                            Ok(true) => true,
+                            res => {
+                                // put the old rules back
+                                adapter
+                                    .add_policies(sec, ptype, old_rules.clone())
+                                    .await?;
+                                res?
+                            }
+                        }
+                    } else {
+                        false
+                    }
+                }
+            };
+            if !adapter_updated {
+                return Ok(false);
+            }
+        }
+
+        let rules_updated = self.get_mut_model().update_policies(
+            sec,
+            ptype,
+            old_rules.clone(),
+            new_rules.clone(),
+        );
+        // the filter may match more or fewer rules than replace them, which
+        // an `UpdatePolicies` event can't carry
+        #[cfg(any(feature = "watcher", feature = "logging"))]
+        {
+            let event_data = EventData::Transaction(vec![
+                EventData::RemovePolicies(
+                    sec.to_owned(),
+                    ptype.to_owned(),
+                    old_rules.clone(),
+                ),
+                EventData::AddPolicies(
+                    sec.to_owned(),
+                    ptype.to_owned(),
+                    new_rules.clone(),
+                ),
+            ]);
+            #[cfg(feature = "watcher")]
+            {
+                if rules_updated && self.has_auto_notify_watcher_enabled() {
+                    self.emit(Event::PolicyChange, event_data);
+                }
+            }
+            #[cfg(not(feature = "watcher"))]
+            {
+                if rules_updated {
+                    self.emit(Event::PolicyChange, event_data);
+                }
+            }
+        }
+        #[cfg(feature = "cached")]
+        {
+            if rules_updated {
+                self.emit(Event::ClearCache, EventData::ClearCache);
+            }
+        }
+        if !rules_updated
+            || sec != "g"
+            || !self.has_auto_build_role_links_enabled()
+        {
+            return Ok(rules_updated);
+        }
+        #[cfg(not(feature = "incremental"))]
+        {
+            self.build_role_links()?;
+        }
+        #[cfg(feature = "incremental")]
+        {
+            self.build_incremental_role_links(EventData::UpdatePolicies(
+                sec.to_owned(),
+                ptype.to_owned(),
+                old_rules,
+                new_rules,
+            ))?;
+        }
+
+        Ok(rules_updated)
+    }
+
+    async fn update_filtered_policies_internal(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        new_rules: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<(bool, Vec<Vec<String>>)> {
+        let filtered = self.get_model().get_filtered_policy(
+            sec,
+            ptype,
+            field_index,
+            field_values.clone(),
+        );
+        if filtered.is_empty()
+            || !can_update(self.get_model(), sec, ptype, &filtered, &new_rules)
+        {
+            return Ok((false, vec![]));
+        }
+
+        if self.has_auto_save_enabled() {
+            let adapter_updated =
+                match self.get_mut_adapter().as_updatable_mut() {
+                    Some(adapter) => {
+                        adapter
+                            .update_filtered_policies(
+                                sec,
+                                ptype,
+                                new_rules.clone(),
+                                field_index,
+                                field_values.clone(),
+                            )
+                            .await?
+                    }
+                    None => {
+                        let adapter = self.get_mut_adapter();
+                        if adapter
+                            .remove_filtered_policy(
+                                sec,
+                                ptype,
+                                field_index,
+                                field_values.clone(),
+                            )
+                            .await?
+                        {
+                            match adapter
+                                .add_policies(sec, ptype, new_rules.clone())
+                                .await
+                            {
+                                Ok(true) => true,
+                                res => {
+                                    // put the filtered rules back
+                                    adapter
+                                        .add_policies(sec, ptype, filtered)
+                                        .await?;
+                                    res?
+                                }
+                            }
+                        } else {
+                            false
+                        }
+                    }
+                };
+            if !adapter_updated {
+                return Ok((false, vec![]));
+            }
+        }
+
+        let model = self.get_mut_model();
+        let (rules_removed, old_rules) =
+            model.remove_filtered_policy(sec, ptype, field_index, field_values);
+        if !rules_removed {
+            return Ok((false, vec![]));
+        }
+        if !model.add_policies(sec, ptype, new_rules.clone()) {
+            // put the filtered rules back so the model is left untouched
+            model.add_policies(sec, ptype, old_rules);
+            return Ok((false, vec![]));
+        }
+        // the filter may match more or fewer rules than replace them, which
+        // an `UpdatePolicies` event can't carry
+        #[cfg(any(feature = "watcher", feature = "logging"))]
+        {
+            let event_data = EventData::Transaction(vec![
+                EventData::RemovePolicies(
+                    sec.to_owned(),
+                    ptype.to_owned(),
+                    old_rules.clone(),
+                ),
+                EventData::AddPolicies(
+                    sec.to_owned(),
+                    ptype.to_owned(),
+                    new_rules.clone(),
+                ),
+            ]);
+            #[cfg(feature = "watcher")]
+            {
+                if self.has_auto_notify_watcher_enabled() {
+                    self.emit(Event::PolicyChange, event_data);
+                }
+            }
+            #[cfg(not(feature = "watcher"))]
+            {
+                self.emit(Event::PolicyChange, event_data);
+            }
+        }
+        #[cfg(feature = "cached")]
+        {
+            self.emit(Event::ClearCache, EventData::ClearCache);
+        }
+        if sec != "g" || !self.has_auto_build_role_links_enabled() {
+            return Ok((true, old_rules));
+        }
+        #[cfg(not(feature = "incremental"))]
+        {
+            self.build_role_links()?;
+        }
+        #[cfg(feature = "incremental")]
+        {
+            self.build_incremental_role_links(EventData::RemovePolicies(
+                sec.to_owned(),
+                ptype.to_owned(),
+                old_rules.clone(),
+            ))?;
+            self.build_incremental_role_links(EventData::AddPolicies(
+                sec.to_owned(),
+                ptype.to_owned(),
+                new_rules,
+            ))?;
+        }
+
+        Ok((true, old_rules))
+    }
+}
+
+// `old_rules` must all exist and `new_rules` must be distinct rules that
+// don't collide with any rule left in place, so that the model can take the
+// update once the adapter has
+fn can_update(
+    model: &dyn Model,
+    sec: &str,
+    ptype: &str,
+    old_rules: &[Vec<String>],
+    new_rules: &[Vec<String>],
+) -> bool {
+    if !old_rules
+        .iter()
+        .all(|rule| model.has_policy(sec, ptype, rule.clone()))
+    {
+        return false;
+    }
+
+    let mut seen = HashSet::new();
+    new_rules.iter().all(|rule| {
+        seen.insert(rule)
+            && (old_rules.contains(rule)
+                || !model.has_policy(sec, ptype, rule.clone()))
+    })
 }
@@ -1,8 +1,3 @@
-#[cfg(all(not(feature = "logging"), feature = "explain"))]
-compile_error!(
-    "'logging' feature must be enabled along with 'explain' feature"
-);
-
 mod adapter;
 #[cfg(feature = "cached")]
 mod cache;
@@ -13,9 +8,12 @@ mod cached_enforcer;
 mod config;
 mod convert;
 mod core_api;
+mod domain_rbac_api;
 mod effector;
 mod emitter;
 mod enforcer;
+#[cfg(feature = "explain")]
+mod explanation;
 mod internal_api;
 #[cfg(feature = "logging")]
 mod logger;
@@ -24,6 +22,13 @@ mod management_api;
 mod model;
 mod rbac;
 mod rbac_api;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "runtime-tokio", feature = "runtime-async-std")
+))]
+mod synced_enforcer;
+mod trace;
+mod transaction;
 mod util;
 #[cfg(feature = "watcher")]
 mod watcher;
@@ -34,7 +39,13 @@ pub mod prelude;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub use adapter::FileAdapter;
-pub use adapter::{Adapter, Filter, MemoryAdapter, NullAdapter, StringAdapter};
+pub use adapter::{
+    Adapter, DocumentAdapter, DocumentFormat, Field, FieldMatch, Filter,
+    JsonAdapter, JsonFormat, MemoryAdapter, NullAdapter, PolicyFilter,
+    PolicyRecord, StringAdapter, UpdatableAdapter,
+};
+#[cfg(feature = "yaml")]
+pub use adapter::{YamlAdapter, YamlFormat};
 
 #[cfg(feature = "cached")]
 pub use cache::{Cache, DefaultCache};
@@ -42,24 +53,41 @@ pub use cache::{Cache, DefaultCache};
 pub use cached_api::CachedApi;
 #[cfg(feature = "cached")]
 pub use cached_enforcer::CachedEnforcer;
+pub use config::{Config, ConfigOptions};
 pub use convert::{EnforceArgs, TryIntoAdapter, TryIntoModel};
 pub use core_api::{CoreApi, IEnforcer};
+pub use domain_rbac_api::DomainRbacApi;
 pub use effector::{
-    DefaultEffectStream, DefaultEffector, EffectKind, Effector, EffectorStream,
+    DefaultEffectStream, DefaultEffector, EffectExpr, EffectKind, Effector,
+    EffectorStream, ParsedEffect,
+};
+pub use emitter::{
+    Event, EventData, EventEmitter, EventKey, EVENT_DATA_VERSION,
 };
-pub use emitter::{Event, EventData, EventEmitter, EventKey};
 pub use enforcer::EnforceContext;
 pub use enforcer::Enforcer;
 pub use error::Error;
+#[cfg(feature = "explain")]
+pub use explanation::{Explanation, MatchedRule};
 pub use internal_api::InternalApi;
 #[cfg(feature = "logging")]
 pub use logger::{DefaultLogger, Logger};
 pub use management_api::MgmtApi;
-pub use model::{function_map, Assertion, DefaultModel, Model};
-pub use rbac::{DefaultRoleManager, MatchingFn, RoleManager};
+pub use model::{function_map, Assertion, DefaultModel, Model, ModelBuilder};
+pub use rbac::{
+    ConditionalRoleManager, DefaultRoleManager, LinkConditionFn, MatchingFn,
+    RoleManager,
+};
 pub use rbac_api::RbacApi;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "runtime-tokio", feature = "runtime-async-std")
+))]
+pub use synced_enforcer::SyncedEnforcer;
+pub use trace::{EnforceTrace, PolicyTrace};
+pub use transaction::Transaction;
 #[cfg(feature = "watcher")]
-pub use watcher::Watcher;
+pub use watcher::{Watcher, WatcherEx};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -48,13 +48,13 @@ macro_rules! get_or_err_with_context {
 macro_rules! register_g_function {
     ($enforcer:ident, $fname:ident, $ast:ident) => {{
         let rm = Arc::clone(&$enforcer.rm);
-        let count = $ast.value.matches('_').count();
+        let count = $ast.role_arity()?;
 
         if count == 2 {
             $enforcer.engine.register_fn(
                 $fname,
                 move |arg1: ImmutableString, arg2: ImmutableString| {
-                    rm.read().has_link(&arg1, &arg2, None)
+                    rm.read().has_link(&arg1, &arg2, &[])
                 },
             );
         } else if count == 3 {
@@ -63,15 +63,46 @@ macro_rules! register_g_function {
                 move |arg1: ImmutableString,
                       arg2: ImmutableString,
                       arg3: ImmutableString| {
-                    rm.read().has_link(&arg1, &arg2, Some(&arg3))
+                    rm.read().has_link(&arg1, &arg2, &[&arg3])
+                },
+            );
+        } else if count == 4 {
+            $enforcer.engine.register_fn(
+                $fname,
+                move |arg1: ImmutableString,
+                      arg2: ImmutableString,
+                      arg3: ImmutableString,
+                      arg4: ImmutableString| {
+                    rm.read().has_link(&arg1, &arg2, &[&arg3, &arg4])
+                },
+            );
+        } else if count == 5 {
+            $enforcer.engine.register_fn(
+                $fname,
+                move |arg1: ImmutableString,
+                      arg2: ImmutableString,
+                      arg3: ImmutableString,
+                      arg4: ImmutableString,
+                      arg5: ImmutableString| {
+                    rm.read().has_link(&arg1, &arg2, &[&arg3, &arg4, &arg5])
                 },
             );
         } else {
-            return Err(ModelError::P(
-                r#"the number of "_" in role definition should be at least 2"#
-                    .to_owned(),
-            )
-            .into());
+            $enforcer.engine.register_fn(
+                $fname,
+                move |arg1: ImmutableString,
+                      arg2: ImmutableString,
+                      arg3: ImmutableString,
+                      arg4: ImmutableString,
+                      arg5: ImmutableString,
+                      arg6: ImmutableString| {
+                    rm.read().has_link(
+                        &arg1,
+                        &arg2,
+                        &[&arg3, &arg4, &arg5, &arg6],
+                    )
+                },
+            );
         }
     }};
 }
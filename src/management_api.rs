@@ -1,4 +1,11 @@
-use crate::{InternalApi, Result};
+use crate::{
+    emitter::EventData, error::PolicyError, InternalApi, Result, Transaction,
+};
+
+#[cfg(feature = "cached")]
+use crate::emitter::Event;
+
+use std::collections::HashMap;
 
 use async_trait::async_trait;
 
@@ -131,6 +138,171 @@ pub trait MgmtApi: InternalApi {
         field_values: Vec<String>,
     ) -> Result<bool>;
 
+    /// Removes the `p` policies whose named fields, such as `("sub", "alice")`,
+    /// hold the given values.
+    async fn remove_filtered_policy_by_fields(
+        &mut self,
+        fields: &[(&str, &str)],
+    ) -> Result<bool> {
+        self.remove_filtered_named_policy_by_fields("p", fields)
+            .await
+    }
+
+    async fn remove_filtered_named_policy_by_fields(
+        &mut self,
+        ptype: &str,
+        fields: &[(&str, &str)],
+    ) -> Result<bool> {
+        let (field_index, field_values) = fields_filter(self, ptype, fields)?;
+        self.remove_filtered_named_policy(ptype, field_index, field_values)
+            .await
+    }
+
+    async fn update_policy(
+        &mut self,
+        old_params: Vec<String>,
+        new_params: Vec<String>,
+    ) -> Result<bool> {
+        self.update_named_policy("p", old_params, new_params).await
+    }
+
+    async fn update_policies(
+        &mut self,
+        old_paramss: Vec<Vec<String>>,
+        new_paramss: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        self.update_named_policies("p", old_paramss, new_paramss)
+            .await
+    }
+
+    async fn update_filtered_policies(
+        &mut self,
+        new_paramss: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        self.update_filtered_named_policies(
+            "p",
+            new_paramss,
+            field_index,
+            field_values,
+        )
+        .await
+    }
+
+    async fn update_named_policy(
+        &mut self,
+        ptype: &str,
+        old_params: Vec<String>,
+        new_params: Vec<String>,
+    ) -> Result<bool>;
+    async fn update_named_policies(
+        &mut self,
+        ptype: &str,
+        old_paramss: Vec<Vec<String>>,
+        new_paramss: Vec<Vec<String>>,
+    ) -> Result<bool>;
+    async fn update_filtered_named_policies(
+        &mut self,
+        ptype: &str,
+        new_paramss: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool>;
+
+    async fn update_grouping_policy(
+        &mut self,
+        old_params: Vec<String>,
+        new_params: Vec<String>,
+    ) -> Result<bool> {
+        self.update_named_grouping_policy("g", old_params, new_params)
+            .await
+    }
+
+    async fn update_grouping_policies(
+        &mut self,
+        old_paramss: Vec<Vec<String>>,
+        new_paramss: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        self.update_named_grouping_policies("g", old_paramss, new_paramss)
+            .await
+    }
+
+    async fn update_named_grouping_policy(
+        &mut self,
+        ptype: &str,
+        old_params: Vec<String>,
+        new_params: Vec<String>,
+    ) -> Result<bool>;
+    async fn update_named_grouping_policies(
+        &mut self,
+        ptype: &str,
+        old_paramss: Vec<Vec<String>>,
+        new_paramss: Vec<Vec<String>>,
+    ) -> Result<bool>;
+
+    /// Applies a change received from a peer without notifying the
+    /// watcher, the same goes for the other `self_*` methods.
+    async fn self_add_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> Result<bool>;
+    async fn self_add_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> Result<bool>;
+    async fn self_remove_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> Result<bool>;
+    async fn self_remove_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> Result<bool>;
+    async fn self_remove_filtered_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool>;
+    async fn self_update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> Result<bool>;
+    async fn self_update_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> Result<bool>;
+
+    /// Applies a change made by a peer, as received from its watcher, to
+    /// the model and the role manager only. Neither the adapter nor the
+    /// watcher get involved. Returns whether the model changed.
+    fn apply_event(&mut self, d: EventData) -> Result<bool>;
+
+    /// Starts buffering policy changes which are applied together by
+    /// [`Transaction::commit`].
+    fn begin_transaction(&mut self) -> Transaction<'_, Self>
+    where
+        Self: Sized,
+    {
+        Transaction::new(self)
+    }
+
     fn get_policy(&self) -> Vec<Vec<String>> {
         self.get_named_policy("p")
     }
@@ -153,6 +325,52 @@ pub trait MgmtApi: InternalApi {
         field_values: Vec<String>,
     ) -> Vec<Vec<String>>;
 
+    /// Gets the `p` policies whose named fields, such as `("sub", "alice")`,
+    /// hold the given values.
+    fn get_filtered_policy_by_fields(
+        &self,
+        fields: &[(&str, &str)],
+    ) -> Result<Vec<Vec<String>>> {
+        self.get_filtered_named_policy_by_fields("p", fields)
+    }
+
+    fn get_filtered_named_policy_by_fields(
+        &self,
+        ptype: &str,
+        fields: &[(&str, &str)],
+    ) -> Result<Vec<Vec<String>>> {
+        let (field_index, field_values) = fields_filter(self, ptype, fields)?;
+        Ok(self.get_filtered_named_policy(ptype, field_index, field_values))
+    }
+
+    /// Gets the distinct values of a named field of the `ptype` policies.
+    fn get_values_for_field_by_name(
+        &self,
+        ptype: &str,
+        field: &str,
+    ) -> Result<Vec<String>> {
+        let field_index = self.get_field_index(ptype, field)?;
+        Ok(self.get_model().get_values_for_field_in_policy(
+            "p",
+            ptype,
+            field_index,
+        ))
+    }
+
+    /// Position of `field`, e.g. `dom`, in the definition of `ptype`.
+    fn get_field_index(&self, ptype: &str, field: &str) -> Result<usize> {
+        let token = format!("{}_{}", ptype, field);
+        self.get_model()
+            .get_model()
+            .get("p")
+            .and_then(|ast_map| ast_map.get(ptype))
+            .and_then(|ast| ast.tokens.iter().position(|x| *x == token))
+            .ok_or_else(|| {
+                PolicyError::FieldNotFound(ptype.to_owned(), field.to_owned())
+                    .into()
+            })
+    }
+
     fn has_policy(&self, params: Vec<String>) -> bool {
         self.has_named_policy("p", params)
     }
@@ -214,6 +432,57 @@ pub trait MgmtApi: InternalApi {
     fn get_all_named_roles(&self, ptype: &str) -> Vec<String>;
 }
 
+// the `field_index`, `field_values` filter selecting the named `fields`
+fn fields_filter<T: MgmtApi + ?Sized>(
+    e: &T,
+    ptype: &str,
+    fields: &[(&str, &str)],
+) -> Result<(usize, Vec<String>)> {
+    let fields = fields
+        .iter()
+        .map(|(field, value)| Ok((e.get_field_index(ptype, field)?, *value)))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(index_filter(&fields))
+}
+
+// the `field_index`, `field_values` filter selecting the values at the
+// given positions, the ones in between are left blank
+pub(crate) fn index_filter(fields: &[(usize, &str)]) -> (usize, Vec<String>) {
+    let field_index = fields.iter().map(|(i, _)| *i).min().unwrap_or(0);
+    let mut field_values = vec![];
+    for (i, value) in fields {
+        let j = i - field_index;
+        if field_values.len() <= j {
+            field_values.resize(j + 1, String::new());
+        }
+        field_values[j] = (*value).to_owned();
+    }
+
+    (field_index, field_values)
+}
+
+// turns off watcher notifications, returning whether they were on
+#[cfg(feature = "watcher")]
+fn mute_watcher<T: InternalApi>(e: &mut T) -> bool {
+    let notify = e.has_auto_notify_watcher_enabled();
+    e.enable_auto_notify_watcher(false);
+    notify
+}
+
+#[cfg(not(feature = "watcher"))]
+fn mute_watcher<T: InternalApi>(_e: &mut T) -> bool {
+    false
+}
+
+#[cfg(feature = "watcher")]
+fn unmute_watcher<T: InternalApi>(e: &mut T, notify: bool) {
+    e.enable_auto_notify_watcher(notify);
+}
+
+#[cfg(not(feature = "watcher"))]
+fn unmute_watcher<T: InternalApi>(_e: &mut T, _notify: bool) {}
+
 #[async_trait]
 impl<T> MgmtApi for T
 where
@@ -324,6 +593,251 @@ where
             .0)
     }
 
+    async fn update_named_policy(
+        &mut self,
+        ptype: &str,
+        old_params: Vec<String>,
+        new_params: Vec<String>,
+    ) -> Result<bool> {
+        self.update_policy_internal("p", ptype, old_params, new_params)
+            .await
+    }
+
+    async fn update_named_policies(
+        &mut self,
+        ptype: &str,
+        old_paramss: Vec<Vec<String>>,
+        new_paramss: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        self.update_policies_internal("p", ptype, old_paramss, new_paramss)
+            .await
+    }
+
+    async fn update_filtered_named_policies(
+        &mut self,
+        ptype: &str,
+        new_paramss: Vec<Vec<String>>,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        Ok(self
+            .update_filtered_policies_internal(
+                "p",
+                ptype,
+                new_paramss,
+                field_index,
+                field_values,
+            )
+            .await?
+            .0)
+    }
+
+    async fn update_named_grouping_policy(
+        &mut self,
+        ptype: &str,
+        old_params: Vec<String>,
+        new_params: Vec<String>,
+    ) -> Result<bool> {
+        self.update_policy_internal("g", ptype, old_params, new_params)
+            .await
+    }
+
+    async fn update_named_grouping_policies(
+        &mut self,
+        ptype: &str,
+        old_paramss: Vec<Vec<String>>,
+        new_paramss: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        self.update_policies_internal("g", ptype, old_paramss, new_paramss)
+            .await
+    }
+
+    async fn self_add_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> Result<bool> {
+        let notify = mute_watcher(self);
+        let res = self.add_policy_internal(sec, ptype, rule).await;
+        unmute_watcher(self, notify);
+        res
+    }
+
+    async fn self_add_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        let notify = mute_watcher(self);
+        let res = self.add_policies_internal(sec, ptype, rules).await;
+        unmute_watcher(self, notify);
+        res
+    }
+
+    async fn self_remove_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> Result<bool> {
+        let notify = mute_watcher(self);
+        let res = self.remove_policy_internal(sec, ptype, rule).await;
+        unmute_watcher(self, notify);
+        res
+    }
+
+    async fn self_remove_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        let notify = mute_watcher(self);
+        let res = self.remove_policies_internal(sec, ptype, rules).await;
+        unmute_watcher(self, notify);
+        res
+    }
+
+    async fn self_remove_filtered_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> Result<bool> {
+        let notify = mute_watcher(self);
+        let res = self
+            .remove_filtered_policy_internal(
+                sec,
+                ptype,
+                field_index,
+                field_values,
+            )
+            .await;
+        unmute_watcher(self, notify);
+        Ok(res?.0)
+    }
+
+    async fn self_update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> Result<bool> {
+        let notify = mute_watcher(self);
+        let res = self
+            .update_policy_internal(sec, ptype, old_rule, new_rule)
+            .await;
+        unmute_watcher(self, notify);
+        res
+    }
+
+    async fn self_update_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> Result<bool> {
+        let notify = mute_watcher(self);
+        let res = self
+            .update_policies_internal(sec, ptype, old_rules, new_rules)
+            .await;
+        unmute_watcher(self, notify);
+        res
+    }
+
+    fn apply_event(&mut self, d: EventData) -> Result<bool> {
+        if let EventData::Transaction(changes) = d {
+            let mut changed = false;
+            for d in changes {
+                changed |= self.apply_event(d)?;
+            }
+            return Ok(changed);
+        }
+
+        let model = self.get_mut_model();
+        let (changed, sec) = match &d {
+            EventData::AddPolicy(sec, ptype, rule) => {
+                (model.add_policy(sec, ptype, rule.clone()), sec.as_str())
+            }
+            EventData::AddPolicies(sec, ptype, rules) => {
+                (model.add_policies(sec, ptype, rules.clone()), sec.as_str())
+            }
+            EventData::RemovePolicy(sec, ptype, rule) => {
+                (model.remove_policy(sec, ptype, rule.clone()), sec.as_str())
+            }
+            EventData::RemovePolicies(sec, ptype, rules)
+            | EventData::RemoveFilteredPolicy(sec, ptype, rules) => (
+                model.remove_policies(sec, ptype, rules.clone()),
+                sec.as_str(),
+            ),
+            EventData::UpdatePolicy(sec, ptype, old_rule, new_rule) => (
+                model.update_policy(
+                    sec,
+                    ptype,
+                    old_rule.clone(),
+                    new_rule.clone(),
+                ),
+                sec.as_str(),
+            ),
+            EventData::UpdatePolicies(sec, ptype, old_rules, new_rules) => (
+                model.update_policies(
+                    sec,
+                    ptype,
+                    old_rules.clone(),
+                    new_rules.clone(),
+                ),
+                sec.as_str(),
+            ),
+            // the rules of a saved policy are prefixed by `sec, ptype`
+            EventData::SavePolicy(rules) => {
+                let mut grouped: HashMap<(&str, &str), Vec<Vec<String>>> =
+                    HashMap::new();
+                for rule in rules.iter().filter(|rule| rule.len() > 2) {
+                    grouped
+                        .entry((&rule[0], &rule[1]))
+                        .or_default()
+                        .push(rule[2..].to_vec());
+                }
+                model.clear_policy();
+                for ((sec, ptype), rules) in grouped {
+                    model.add_policies(sec, ptype, rules);
+                }
+                (true, "")
+            }
+            EventData::ClearPolicy => {
+                model.clear_policy();
+                (true, "")
+            }
+            EventData::ClearCache | EventData::Transaction(_) => (false, ""),
+        };
+
+        #[cfg(feature = "cached")]
+        {
+            if changed || matches!(d, EventData::ClearCache) {
+                self.emit(Event::ClearCache, EventData::ClearCache);
+            }
+        }
+        if !changed || !self.has_auto_build_role_links_enabled() {
+            return Ok(changed);
+        }
+        match sec {
+            // a whole new policy
+            "" => self.build_role_links()?,
+            #[cfg(feature = "incremental")]
+            "g" => self.build_incremental_role_links(d)?,
+            #[cfg(not(feature = "incremental"))]
+            "g" => self.build_role_links()?,
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
     fn get_named_policy(&self, ptype: &str) -> Vec<Vec<String>> {
         self.get_model().get_policy("p", ptype)
     }
@@ -405,18 +919,21 @@ where
     }
 
     fn get_all_named_subjects(&self, ptype: &str) -> Vec<String> {
+        let field_index = self.get_field_index(ptype, "sub").unwrap_or(0);
         self.get_model()
-            .get_values_for_field_in_policy("p", ptype, 0)
+            .get_values_for_field_in_policy("p", ptype, field_index)
     }
 
     fn get_all_named_objects(&self, ptype: &str) -> Vec<String> {
+        let field_index = self.get_field_index(ptype, "obj").unwrap_or(1);
         self.get_model()
-            .get_values_for_field_in_policy("p", ptype, 1)
+            .get_values_for_field_in_policy("p", ptype, field_index)
     }
 
     fn get_all_named_actions(&self, ptype: &str) -> Vec<String> {
+        let field_index = self.get_field_index(ptype, "act").unwrap_or(2);
         self.get_model()
-            .get_values_for_field_in_policy("p", ptype, 2)
+            .get_values_for_field_in_policy("p", ptype, field_index)
     }
 
     fn get_all_named_roles(&self, ptype: &str) -> Vec<String> {
@@ -426,9 +943,18 @@ where
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::useless_vec,
+    clippy::zero_repeat_side_effects
+)]
 mod tests {
+    use crate::adapter::testing::PickyAdapter;
     use crate::prelude::*;
 
+    #[cfg(not(target_arch = "wasm32"))]
+    use crate::adapter::file_adapter::TempPolicy;
+
     fn sort_unstable<T: Ord>(mut v: Vec<T>) -> Vec<T> {
         v.sort_unstable();
         v
@@ -448,7 +974,8 @@ mod tests {
             .await
             .unwrap();
 
-        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
 
         assert_eq!(vec!["data2_admin"], e.get_roles_for_user("alice", None));
@@ -567,7 +1094,8 @@ mod tests {
             .await
             .unwrap();
 
-        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
 
         assert_eq!(
@@ -912,7 +1440,8 @@ mod tests {
             .await
             .unwrap();
 
-        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
 
         assert_eq!(
@@ -1084,7 +1613,8 @@ mod tests {
             .await
             .unwrap();
 
-        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
 
         assert_eq!(vec!["data2_admin"], e.get_roles_for_user("alice", None));
@@ -1183,4 +1713,515 @@ mod tests {
         );
         assert_eq!(vec!["eve"], e.get_users_for_role("data3_admin", None));
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_update_policy_api() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let adapter = policy.adapter();
+        let mut e = Enforcer::new(m, adapter).await.unwrap();
+
+        assert!(e
+            .update_policy(
+                vec!["alice", "data1", "read"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+                vec!["alice", "data1", "write"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            )
+            .await
+            .unwrap());
+        // the updated rule keeps its position
+        assert_eq!(
+            vec![
+                vec!["alice", "data1", "write"],
+                vec!["bob", "data2", "write"],
+                vec!["data2_admin", "data2", "read"],
+                vec!["data2_admin", "data2", "write"],
+            ],
+            e.get_policy()
+        );
+        assert!(!e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(e.enforce(("alice", "data1", "write")).unwrap());
+
+        // updating a missing rule or colliding with an existing one fails
+        assert!(!e
+            .update_policy(
+                vec!["alice", "data1", "read"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+                vec!["alice", "data3", "read"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            )
+            .await
+            .unwrap());
+        assert!(!e
+            .update_policy(
+                vec!["alice", "data1", "write"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+                vec!["bob", "data2", "write"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            )
+            .await
+            .unwrap());
+
+        assert!(e
+            .update_policies(
+                vec![
+                    vec!["alice", "data1", "write"]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                    vec!["bob", "data2", "write"]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                ],
+                vec![
+                    vec!["alice", "data1", "read"]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                    vec!["bob", "data2", "read"]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                ],
+            )
+            .await
+            .unwrap());
+        assert!(e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(e.enforce(("bob", "data2", "read")).unwrap());
+        assert!(!e.enforce(("bob", "data2", "write")).unwrap());
+
+        assert!(e
+            .update_filtered_policies(
+                vec![vec!["data3_admin", "data3", "read"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect()],
+                0,
+                vec!["data2_admin"].iter().map(|s| s.to_string()).collect(),
+            )
+            .await
+            .unwrap());
+        assert_eq!(
+            vec![
+                vec!["alice", "data1", "read"],
+                vec!["bob", "data2", "read"],
+                vec!["data3_admin", "data3", "read"],
+            ],
+            e.get_policy()
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_update_grouping_policy_api() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let adapter = MemoryAdapter::default();
+        let mut e = Enforcer::new(m, adapter).await.unwrap();
+
+        e.add_grouping_policies(vec![
+            vec!["alice", "data2_admin"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            vec!["bob", "data1_admin"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        ])
+        .await
+        .unwrap();
+
+        assert!(e
+            .update_grouping_policy(
+                vec!["alice", "data2_admin"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+                vec!["alice", "data3_admin"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            )
+            .await
+            .unwrap());
+        assert_eq!(vec!["data3_admin"], e.get_roles_for_user("alice", None));
+        assert_eq!(
+            vec![String::new(); 0],
+            e.get_users_for_role("data2_admin", None)
+        );
+
+        assert!(e
+            .update_grouping_policies(
+                vec![
+                    vec!["alice", "data3_admin"]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                    vec!["bob", "data1_admin"]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                ],
+                vec![
+                    vec!["alice", "data1_admin"]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                    vec!["bob", "data3_admin"]
+                        .iter()
+                        .map(|s| s.to_string())
+                        .collect(),
+                ],
+            )
+            .await
+            .unwrap());
+        assert_eq!(vec!["data1_admin"], e.get_roles_for_user("alice", None));
+        assert_eq!(vec!["data3_admin"], e.get_roles_for_user("bob", None));
+
+        // the adapter was updated in place as well
+        e.load_policy().await.unwrap();
+        assert_eq!(
+            vec![vec!["alice", "data1_admin"], vec!["bob", "data3_admin"]],
+            e.get_grouping_policy()
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_failed_update_keeps_policy() {
+        let m = DefaultModel::from_file("examples/basic_model.conf")
+            .await
+            .unwrap();
+
+        let rule = |r: [&str; 3]| -> Vec<String> {
+            r.iter().map(|s| s.to_string()).collect()
+        };
+        let mut e = Enforcer::new(m, PickyAdapter::refusing("bad"))
+            .await
+            .unwrap();
+        e.add_policies(vec![
+            rule(["alice", "data1", "read"]),
+            rule(["bob", "data2", "write"]),
+        ])
+        .await
+        .unwrap();
+        let expected = e.get_policy();
+
+        // a collision is caught before the adapter is touched
+        assert!(!e
+            .update_policy(
+                rule(["alice", "data1", "read"]),
+                rule(["bob", "data2", "write"])
+            )
+            .await
+            .unwrap());
+        assert!(!e
+            .update_filtered_policies(
+                vec![rule(["bob", "data2", "write"])],
+                0,
+                vec!["alice".to_owned()],
+            )
+            .await
+            .unwrap());
+
+        // the adapter refuses the new rule, so the old one is restored
+        assert!(!e
+            .update_policy(
+                rule(["alice", "data1", "read"]),
+                rule(["alice", "bad", "read"])
+            )
+            .await
+            .unwrap());
+        assert!(!e
+            .update_policies(
+                vec![rule(["alice", "data1", "read"])],
+                vec![rule(["alice", "bad", "read"])],
+            )
+            .await
+            .unwrap());
+        assert!(!e
+            .update_filtered_policies(
+                vec![rule(["bob", "bad", "write"])],
+                0,
+                vec!["bob".to_owned()],
+            )
+            .await
+            .unwrap());
+
+        assert_eq!(expected, e.get_policy());
+        e.load_policy().await.unwrap();
+        assert_eq!(expected, e.get_policy());
+    }
+
+    #[cfg(feature = "watcher")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_replay_filtered_update() {
+        use crate::adapter::testing::{to_owned, SampleWatcher};
+        use crate::InternalApi;
+
+        let mut source = Enforcer::new(
+            "examples/rbac_model.conf",
+            "examples/rbac_policy.csv",
+        )
+        .await
+        .unwrap();
+        source.enable_auto_save(false);
+        let mut peer = Enforcer::new(
+            "examples/rbac_model.conf",
+            "examples/rbac_policy.csv",
+        )
+        .await
+        .unwrap();
+
+        let watcher = SampleWatcher::default();
+        let events = std::sync::Arc::clone(&watcher.0);
+        source.set_watcher(Box::new(watcher));
+
+        // two rules of data2_admin are replaced by three
+        assert!(source
+            .update_filtered_policies(
+                vec![
+                    to_owned(vec!["data2_admin", "data2", "read"]),
+                    to_owned(vec!["data2_admin", "data3", "read"]),
+                    to_owned(vec!["data2_admin", "data3", "write"]),
+                ],
+                0,
+                vec!["data2_admin".to_owned()],
+            )
+            .await
+            .unwrap());
+        assert!(
+            source
+                .update_filtered_policies_internal(
+                    "g",
+                    "g",
+                    vec![
+                        to_owned(vec!["bob", "data2_admin"]),
+                        to_owned(vec!["eve", "data2_admin"]),
+                    ],
+                    0,
+                    vec!["alice".to_owned()],
+                )
+                .await
+                .unwrap()
+                .0
+        );
+
+        for d in events.lock().unwrap().drain(..) {
+            let json = serde_json::to_string(&d).unwrap();
+            assert!(peer
+                .apply_event(serde_json::from_str(&json).unwrap())
+                .unwrap());
+        }
+
+        assert_eq!(source.get_policy(), peer.get_policy());
+        assert_eq!(source.get_grouping_policy(), peer.get_grouping_policy());
+        assert!(peer.enforce(("eve", "data3", "write")).unwrap());
+        assert!(!peer.enforce(("alice", "data2", "read")).unwrap());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_apply_event() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+        let mut source =
+            Enforcer::new(m, MemoryAdapter::default()).await.unwrap();
+
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let mut e = Enforcer::new(m, adapter).await.unwrap();
+
+        // events travel through their wire format
+        let mut apply = |d: EventData| {
+            let json = serde_json::to_string(&d).unwrap();
+            e.apply_event(serde_json::from_str(&json).unwrap()).unwrap()
+        };
+
+        assert!(apply(EventData::AddPolicy(
+            "g".to_owned(),
+            "g".to_owned(),
+            vec!["bob".to_owned(), "data2_admin".to_owned()],
+        )));
+        assert!(apply(EventData::RemoveFilteredPolicy(
+            "p".to_owned(),
+            "p".to_owned(),
+            vec![vec![
+                "alice".to_owned(),
+                "data1".to_owned(),
+                "read".to_owned(),
+            ]],
+        )));
+        assert!(apply(EventData::Transaction(vec![
+            EventData::UpdatePolicy(
+                "g".to_owned(),
+                "g".to_owned(),
+                vec!["alice".to_owned(), "data2_admin".to_owned()],
+                vec!["eve".to_owned(), "data2_admin".to_owned()],
+            ),
+            EventData::ClearCache,
+        ])));
+        assert!(!apply(EventData::RemovePolicy(
+            "p".to_owned(),
+            "p".to_owned(),
+            vec!["alice".to_owned(), "data1".to_owned(), "read".to_owned()],
+        )));
+
+        assert!(!e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(!e.enforce(("alice", "data2", "read")).unwrap());
+        assert!(e.enforce(("bob", "data2", "write")).unwrap());
+        assert!(e.enforce(("eve", "data2", "read")).unwrap());
+        assert_eq!(
+            vec!["bob", "eve"],
+            sort_unstable(e.get_users_for_role("data2_admin", None))
+        );
+
+        // a saved policy replaces everything
+        source
+            .add_policy(vec!["admin".into(), "data3".into(), "read".into()])
+            .await
+            .unwrap();
+        source
+            .add_grouping_policy(vec!["carol".into(), "admin".into()])
+            .await
+            .unwrap();
+        let mut rules = source.get_all_policy();
+        rules.extend(source.get_all_grouping_policy());
+        assert!(e.apply_event(EventData::SavePolicy(rules)).unwrap());
+
+        assert_eq!(source.get_policy(), e.get_policy());
+        assert_eq!(source.get_grouping_policy(), e.get_grouping_policy());
+        assert!(e.enforce(("carol", "data3", "read")).unwrap());
+        assert!(!e.enforce(("bob", "data2", "write")).unwrap());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_policy_api_by_fields() {
+        let m =
+            DefaultModel::from_file("examples/rbac_with_domains_model.conf")
+                .await
+                .unwrap();
+        let adapter = MemoryAdapter::default();
+        let mut e = Enforcer::new(m, adapter).await.unwrap();
+        e.add_policies(
+            vec![
+                vec!["admin", "domain1", "data1", "read"],
+                vec!["admin", "domain1", "data1", "write"],
+                vec!["admin", "domain2", "data2", "read"],
+                vec!["admin", "domain2", "data2", "write"],
+            ]
+            .into_iter()
+            .map(|x| x.into_iter().map(String::from).collect())
+            .collect(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(1, e.get_field_index("p", "dom").unwrap());
+        assert_eq!(3, e.get_field_index("p", "act").unwrap());
+        assert!(e.get_field_index("p", "eft").is_err());
+
+        assert_eq!(
+            vec![
+                vec!["admin", "domain1", "data1", "read"],
+                vec!["admin", "domain2", "data2", "read"],
+            ],
+            e.get_filtered_policy_by_fields(&[
+                ("sub", "admin"),
+                ("act", "read")
+            ])
+            .unwrap()
+        );
+        assert_eq!(
+            vec!["data1", "data2"],
+            sort_unstable(e.get_values_for_field_by_name("p", "obj").unwrap())
+        );
+        assert_eq!(vec!["data1", "data2"], sort_unstable(e.get_all_objects()));
+        assert!(e
+            .get_filtered_policy_by_fields(&[("tenant", "domain1")])
+            .is_err());
+
+        assert!(e
+            .remove_filtered_policy_by_fields(&[("dom", "domain2")])
+            .await
+            .unwrap());
+        assert_eq!(
+            vec![
+                vec!["admin", "domain1", "data1", "read"],
+                vec!["admin", "domain1", "data1", "write"],
+            ],
+            e.get_permissions_for_user("admin", Some("domain1"))
+        );
+        assert!(e
+            .get_permissions_for_user("admin", Some("domain2"))
+            .is_empty());
+    }
 }
@@ -9,17 +9,23 @@ use crate::emitter::EventData;
 
 use hashlink::{LinkedHashMap, LinkedHashSet};
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 
 use std::sync::Arc;
 
 pub type AssertionMap = LinkedHashMap<String, Assertion>;
 
-#[derive(Clone)]
+const MAX_ROLE_ARITY: usize = 6;
+
+/// A definition of the model with the rules of its policy. The role
+/// manager is not serialized, it is rebuilt from the rules.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Assertion {
     pub key: String,
     pub value: String,
     pub tokens: Vec<String>,
     pub policy: LinkedHashSet<Vec<String>>,
+    #[serde(skip, default = "default_role_manager")]
     pub rm: Arc<RwLock<dyn RoleManager>>,
 }
 
@@ -30,12 +36,50 @@ impl Default for Assertion {
             value: String::new(),
             tokens: vec![],
             policy: LinkedHashSet::new(),
-            rm: Arc::new(RwLock::new(DefaultRoleManager::new(0))),
+            rm: default_role_manager(),
         }
     }
 }
 
+fn default_role_manager() -> Arc<RwLock<dyn RoleManager>> {
+    Arc::new(RwLock::new(DefaultRoleManager::new(0)))
+}
+
 impl Assertion {
+    /// number of `_` in a role definition, between 2 and 6,
+    /// not counting the parenthesized condition fields
+    pub(crate) fn role_arity(&self) -> Result<usize> {
+        let count = self
+            .value
+            .split('(')
+            .next()
+            .unwrap_or_default()
+            .matches('_')
+            .count();
+        if count < 2 {
+            return Err(ModelError::P(
+                r#"the number of "_" in role definition should be at least 2"#
+                    .to_owned(),
+            )
+            .into());
+        }
+        if count > MAX_ROLE_ARITY {
+            return Err(ModelError::P(format!(
+                r#"the number of "_" in role definition should be at most {}"#,
+                MAX_ROLE_ARITY
+            ))
+            .into());
+        }
+        Ok(count)
+    }
+
+    /// number of condition fields, as in `g = _, _, (_, _)`
+    pub(crate) fn condition_arity(&self) -> usize {
+        self.value
+            .split_once('(')
+            .map_or(0, |(_, conditions)| conditions.matches('_').count())
+    }
+
     #[inline]
     pub fn get_policy(&self) -> &LinkedHashSet<Vec<String>> {
         &self.policy
@@ -46,18 +90,76 @@ impl Assertion {
         &mut self.policy
     }
 
+    /// Orders the policies by the `priority` field when the assertion
+    /// defines one, lower values first and ties in insertion order.
+    pub fn sort_policies_by_priority(&mut self) {
+        let priority = match self.priority_fn() {
+            Some(priority) => priority,
+            None => return,
+        };
+
+        let mut last = i64::MIN;
+        let sorted = self.policy.iter().all(|rule| {
+            let current = priority(rule);
+            let in_order = last <= current;
+            last = current;
+            in_order
+        });
+        if sorted {
+            return;
+        }
+
+        let mut rules: Vec<Vec<String>> =
+            std::mem::take(&mut self.policy).into_iter().collect();
+        rules.sort_by_key(priority);
+        self.policy = rules.into_iter().collect();
+    }
+
+    /// Adds `rule` after the policies of lower or equal priority, keeping
+    /// sorted policies sorted. Returns false if the rule already exists.
+    pub fn insert_policy(&mut self, rule: Vec<String>) -> bool {
+        if self.policy.contains(&rule) {
+            return false;
+        }
+        let priority = match self.priority_fn() {
+            Some(priority) => priority,
+            None => return self.policy.insert(rule),
+        };
+
+        // the rules to move behind the new one, usually none
+        let current = priority(&rule);
+        let mut tail: Vec<Vec<String>> = self
+            .policy
+            .iter()
+            .rev()
+            .take_while(|x| priority(x) > current)
+            .cloned()
+            .collect();
+        tail.reverse();
+
+        self.policy.insert(rule);
+        for x in &tail {
+            self.policy.to_back(x);
+        }
+        true
+    }
+
+    pub(crate) fn priority_fn(&self) -> Option<impl Fn(&Vec<String>) -> i64> {
+        let token = format!("{}_priority", self.key);
+        let idx = self.tokens.iter().position(|x| *x == token)?;
+        Some(move |rule: &Vec<String>| {
+            rule.get(idx)
+                .and_then(|x| x.parse::<i64>().ok())
+                .unwrap_or(i64::MAX)
+        })
+    }
+
     pub fn build_role_links(
         &mut self,
         rm: Arc<RwLock<dyn RoleManager>>,
     ) -> Result<()> {
-        let count = self.value.matches('_').count();
-        if count < 2 {
-            return Err(ModelError::P(
-                r#"the number of "_" in role definition should be at least 2"#
-                    .to_owned(),
-            )
-            .into());
-        }
+        let count = self.role_arity()?;
+        let conditions = self.condition_arity();
         for rule in &self.policy {
             if rule.len() < count {
                 return Err(PolicyError::UnmatchPolicyDefinition(
@@ -66,16 +168,7 @@ impl Assertion {
                 )
                 .into());
             }
-            if count == 2 {
-                rm.write().add_link(&rule[0], &rule[1], None);
-            } else if count == 3 {
-                rm.write().add_link(&rule[0], &rule[1], Some(&rule[2]));
-            } else if count >= 4 {
-                return Err(ModelError::P(
-                    "Multiple domains are not supported".to_owned(),
-                )
-                .into());
-            }
+            add_rule_link(&rm, rule, count, conditions)?;
         }
         self.rm = Arc::clone(&rm);
         Ok(())
@@ -87,60 +180,103 @@ impl Assertion {
         rm: Arc<RwLock<dyn RoleManager>>,
         d: EventData,
     ) -> Result<()> {
-        let count = self.value.matches('_').count();
-        if count < 2 {
-            return Err(ModelError::P(
-                r#"the number of "_" in role definition should be at least 2"#
-                    .to_owned(),
-            )
-            .into());
-        }
+        let count = self.role_arity()?;
+        let conditions = self.condition_arity();
 
-        if let Some((insert, rules)) = match d {
-            EventData::AddPolicy(_, _, rule) => Some((true, vec![rule])),
-            EventData::AddPolicies(_, _, rules) => Some((true, rules)),
-            EventData::RemovePolicy(_, _, rule) => Some((false, vec![rule])),
-            EventData::RemovePolicies(_, _, rules) => Some((false, rules)),
-            EventData::RemoveFilteredPolicy(_, _, rules) => {
-                Some((false, rules))
+        let (removed, added) = match d {
+            EventData::AddPolicy(_, _, rule) => (vec![], vec![rule]),
+            EventData::AddPolicies(_, _, rules) => (vec![], rules),
+            EventData::RemovePolicy(_, _, rule) => (vec![rule], vec![]),
+            EventData::RemovePolicies(_, _, rules) => (rules, vec![]),
+            EventData::RemoveFilteredPolicy(_, _, rules) => (rules, vec![]),
+            EventData::UpdatePolicy(_, _, old_rule, new_rule) => {
+                (vec![old_rule], vec![new_rule])
+            }
+            EventData::UpdatePolicies(_, _, old_rules, new_rules) => {
+                (old_rules, new_rules)
             }
-            _ => None,
-        } {
-            for rule in rules {
-                if rule.len() < count {
-                    return Err(PolicyError::UnmatchPolicyDefinition(
-                        count,
-                        rule.len(),
-                    )
-                    .into());
-                }
-                if count == 2 {
-                    if insert {
-                        rm.write().add_link(&rule[0], &rule[1], None);
-                    } else {
-                        rm.write().delete_link(&rule[0], &rule[1], None)?;
-                    }
-                } else if count == 3 {
-                    if insert {
-                        rm.write().add_link(&rule[0], &rule[1], Some(&rule[2]));
-                    } else {
-                        rm.write().delete_link(
-                            &rule[0],
-                            &rule[1],
-                            Some(&rule[2]),
-                        )?;
-                    }
-                } else if count >= 4 {
-                    return Err(ModelError::P(
-                        "Multiple domains are not supported".to_owned(),
-                    )
-                    .into());
-                }
+            _ => return Ok(()),
+        };
+
+        for rule in removed.iter().chain(added.iter()) {
+            if rule.len() < count {
+                return Err(PolicyError::UnmatchPolicyDefinition(
+                    count,
+                    rule.len(),
+                )
+                .into());
             }
+        }
+
+        for rule in removed {
+            delete_rule_link(&rm, &rule, count, conditions)?;
+        }
 
-            self.rm = Arc::clone(&rm);
+        for rule in added {
+            add_rule_link(&rm, &rule, count, conditions)?;
         }
 
+        self.rm = Arc::clone(&rm);
+
         Ok(())
     }
 }
+
+// the domain fields of a grouping rule, following the two names
+fn domains(rule: &[String], count: usize) -> Vec<&str> {
+    rule[2..count].iter().map(String::as_str).collect()
+}
+
+// the condition fields of a grouping rule, following its domains
+fn params(rule: &[String], count: usize, conditions: usize) -> Vec<&str> {
+    rule.iter()
+        .skip(count)
+        .take(conditions)
+        .map(String::as_str)
+        .collect()
+}
+
+// adds the link of a grouping rule, along with its condition fields if any
+fn add_rule_link(
+    rm: &RwLock<dyn RoleManager>,
+    rule: &[String],
+    count: usize,
+    conditions: usize,
+) -> Result<()> {
+    if conditions == 0 {
+        rm.write()
+            .add_link(&rule[0], &rule[1], &domains(rule, count));
+        return Ok(());
+    }
+
+    rm.write().add_conditional_link(
+        &rule[0],
+        &rule[1],
+        &domains(rule, count),
+        &params(rule, count, conditions),
+    )
+}
+
+// removes the link of a grouping rule, only for its own condition fields
+#[cfg(feature = "incremental")]
+fn delete_rule_link(
+    rm: &RwLock<dyn RoleManager>,
+    rule: &[String],
+    count: usize,
+    conditions: usize,
+) -> Result<()> {
+    if conditions == 0 {
+        return rm.write().delete_link(
+            &rule[0],
+            &rule[1],
+            &domains(rule, count),
+        );
+    }
+
+    rm.write().delete_conditional_link(
+        &rule[0],
+        &rule[1],
+        &domains(rule, count),
+        &params(rule, count, conditions),
+    )
+}
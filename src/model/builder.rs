@@ -0,0 +1,259 @@
+use crate::{
+    error::{Error, ModelError},
+    model::{DefaultModel, Model},
+    util::escape_assertion,
+    Result,
+};
+
+use rhai::Engine;
+
+/// Builds a [`DefaultModel`] section by section, without going through the
+/// INI text format.
+///
+/// Every definition is checked as it is added, and the first problem found
+/// is returned by [`build`](ModelBuilder::build). Keys of a section must be
+/// added in order, e.g. `p` before `p2`.
+///
+/// # Examples
+///
+/// ```
+/// use casbin::DefaultModel;
+///
+/// let m = DefaultModel::builder()
+///     .request("r", ["sub", "obj", "act"])
+///     .policy("p", ["sub", "obj", "act"])
+///     .role("g", 2)
+///     .effect("e", "some(where (p.eft == allow))")
+///     .matcher("m", "g(r.sub, p.sub) && r.obj == p.obj && r.act == p.act")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct ModelBuilder {
+    model: DefaultModel,
+    error: Option<Error>,
+}
+
+impl ModelBuilder {
+    /// Adds a request definition made of `tokens`, like `r = sub, obj, act`.
+    pub fn request<I, S>(self, key: &str, tokens: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.add("r", key, |_| {
+            join_tokens(tokens).map_err(|e| ModelError::R(e).into())
+        })
+    }
+
+    /// Adds a policy definition made of `tokens`, like `p = sub, obj, act`.
+    pub fn policy<I, S>(self, key: &str, tokens: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.add("p", key, |_| {
+            join_tokens(tokens).map_err(|e| ModelError::P(e).into())
+        })
+    }
+
+    /// Adds a role definition linking `arity` values, like `g = _, _` for an
+    /// arity of 2 or `g = _, _, _` when roles are scoped to a domain.
+    pub fn role(self, key: &str, arity: usize) -> Self {
+        self.add("g", key, |key| {
+            if arity < 2 {
+                return Err(ModelError::Other(format!(
+                    "role definition `{}` must link at least 2 values",
+                    key
+                ))
+                .into());
+            }
+            Ok(vec!["_"; arity].join(", "))
+        })
+    }
+
+    /// Adds a policy effect, like `some(where (p.eft == allow))`.
+    pub fn effect(self, key: &str, expr: &str) -> Self {
+        // parsed by `add_def`
+        self.add("e", key, |_| Ok(expr.to_owned()))
+    }
+
+    /// Adds a matcher expression, which must at least compile.
+    pub fn matcher(self, key: &str, expr: &str) -> Self {
+        self.add("m", key, |key| {
+            Engine::new_raw()
+                .compile_expression(escape_assertion(expr))
+                .map_err(|e| {
+                    ModelError::M(format!(
+                        "Failed to compile matcher '{}': {}",
+                        key, e
+                    ))
+                })?;
+            Ok(expr.to_owned())
+        })
+    }
+
+    /// Returns the model once it has request, policy, effect and matcher
+    /// definitions that agree with each other.
+    pub fn build(self) -> Result<DefaultModel> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        for (sec, name) in [
+            ("r", "request definition"),
+            ("p", "policy definition"),
+            ("e", "policy effect"),
+            ("m", "matcher"),
+        ] {
+            if !self.model.model.contains_key(sec) {
+                return Err(
+                    ModelError::Other(format!("missing {}", name)).into()
+                );
+            }
+        }
+        self.model.check_matchers(None)?;
+
+        Ok(self.model)
+    }
+
+    // `value` turns the definition into the text `add_def` expects, so the
+    // model ends up as if loaded from a file
+    fn add<F>(mut self, sec: &str, key: &str, value: F) -> Self
+    where
+        F: FnOnce(&str) -> Result<String>,
+    {
+        if self.error.is_none() {
+            if let Err(err) = self.try_add(sec, key, value) {
+                self.error = Some(err);
+            }
+        }
+        self
+    }
+
+    fn try_add<F>(&mut self, sec: &str, key: &str, value: F) -> Result<()>
+    where
+        F: FnOnce(&str) -> Result<String>,
+    {
+        let count = self.model.model.get(sec).map_or(0, |x| x.len());
+        let expected = match count {
+            0 => sec.to_owned(),
+            n => format!("{}{}", sec, n + 1),
+        };
+        if key != expected {
+            return Err(ModelError::Other(format!(
+                "expected key `{}`, got `{}`",
+                expected, key
+            ))
+            .into());
+        }
+
+        let value = value(key)?;
+        if !self.model.add_def(sec, key, &value)? {
+            return Err(ModelError::Other(format!(
+                "definition `{}` is empty",
+                key
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+fn join_tokens<I, S>(tokens: I) -> std::result::Result<String, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut res: Vec<&str> = vec![];
+    let tokens: Vec<S> = tokens.into_iter().collect();
+
+    for token in &tokens {
+        let token = token.as_ref().trim();
+        if token.is_empty()
+            || !token.chars().all(|c| c.is_alphanumeric() || c == '_')
+        {
+            return Err(format!("invalid token `{}`", token));
+        }
+        if res.contains(&token) {
+            return Err(format!("duplicate token `{}`", token));
+        }
+        res.push(token);
+    }
+
+    if res.is_empty() {
+        return Err("no tokens".to_owned());
+    }
+    Ok(res.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_builder_matches_file() {
+        let m = DefaultModel::builder()
+            .request("r", ["sub", "dom", "obj", "act"])
+            .policy("p", ["sub", "dom", "obj", "act"])
+            .role("g", 3)
+            .effect("e", "some(where (p.eft == allow))")
+            .matcher(
+                "m",
+                "g(r.sub, p.sub, r.dom) && r.dom == p.dom && r.obj == p.obj \
+                 && r.act == p.act",
+            )
+            .build()
+            .unwrap();
+
+        let expected =
+            DefaultModel::from_file("examples/rbac_with_domains_model.conf")
+                .await
+                .unwrap();
+        assert_eq!(m.to_json().unwrap(), expected.to_json().unwrap());
+
+        let e = Enforcer::new(m, "examples/rbac_with_domains_policy.csv")
+            .await
+            .unwrap();
+        assert!(e.enforce(("alice", "domain1", "data1", "read")).unwrap());
+        assert!(!e.enforce(("alice", "domain2", "data2", "read")).unwrap());
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let basic = || {
+            DefaultModel::builder()
+                .request("r", ["sub", "obj", "act"])
+                .policy("p", ["sub", "obj", "act"])
+                .effect("e", "some(where (p.eft == allow))")
+        };
+        let err = |b: crate::ModelBuilder| b.build().err().unwrap().to_string();
+
+        assert!(basic()
+            .matcher("m", "r.sub == p.sub")
+            .policy("p2", ["sub", "act"])
+            .build()
+            .is_ok());
+
+        assert!(err(basic().matcher("m", "r.sub ==")).contains("compile"));
+        assert!(err(basic().matcher("m", "r.user == p.sub"))
+            .contains("undefined `r.user`"));
+        assert!(err(basic()).contains("missing matcher"));
+        assert!(err(basic().policy("p3", ["sub"])).contains("`p2`"));
+        assert!(
+            err(basic().request("r2", ["sub", "sub"])).contains("duplicate")
+        );
+        assert!(err(basic().request("r2", ["a b"])).contains("invalid token"));
+        assert!(err(basic().role("g", 1)).contains("at least 2"));
+        assert!(err(basic().effect("e2", "maybe")).contains("maybe"));
+    }
+}
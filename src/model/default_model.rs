@@ -1,7 +1,10 @@
 use crate::{
     config::Config,
-    error::ModelError,
-    model::{Assertion, AssertionMap, Model},
+    effector::ParsedEffect,
+    error::{Error, ModelError},
+    model::{
+        function_map::FunctionMap, Assertion, AssertionMap, Model, ModelBuilder,
+    },
     rbac::RoleManager,
     util::*,
     Result,
@@ -20,7 +23,11 @@ use async_std::path::Path as ioPath;
 use std::path::Path as ioPath;
 
 use rhai::{Engine, AST};
-use std::{collections::HashMap, sync::Arc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 #[derive(Clone, Default)]
 pub struct DefaultModel {
@@ -28,8 +35,23 @@ pub struct DefaultModel {
     // Precompiled matcher expressions - compiled during Model initialization
     // Keys are full matcher names (e.g., "m", "m2", "m3")
     compiled_matchers: HashMap<String, AST>,
+    // Effect expressions parsed in `add_def`, keyed like the matchers
+    effects: HashMap<String, Arc<ParsedEffect>>,
+    // The config the model was read from, written back by `to_config`
+    config: Option<Config>,
+    // Every definition as given to `add_def`, before escaping
+    texts: HashMap<String, String>,
 }
 
+// Sections of a model file, in the order they are written
+const SECTION_NAMES: [(&str, &str); 5] = [
+    ("r", "request_definition"),
+    ("p", "policy_definition"),
+    ("g", "role_definition"),
+    ("e", "policy_effect"),
+    ("m", "matchers"),
+];
+
 impl DefaultModel {
     // Compiles all matcher expressions after Model loading is complete
     // Should be called before Enforcer is used
@@ -40,9 +62,7 @@ impl DefaultModel {
         if let Some(assertions) = self.model.get("m") {
             for (key, assertion) in assertions {
                 let compiled = engine
-                    .compile_expression(crate::util::escape_eval(
-                        &assertion.value,
-                    ))
+                    .compile_expression(&assertion.value)
                     .map_err(|e| {
                         crate::error::Error::ModelError(
                             crate::error::ModelError::M(format!(
@@ -64,10 +84,105 @@ impl DefaultModel {
     pub fn get_compiled_matcher(&self, key: &str) -> Option<&AST> {
         self.compiled_matchers.get(key)
     }
+
+    #[inline]
+    pub fn get_effect(&self, key: &str) -> Option<&Arc<ParsedEffect>> {
+        self.effects.get(key)
+    }
+
+    /// Checks the matchers against the rest of the model: every `r.*` and
+    /// `p.*` they reference must be defined, every function they call must
+    /// be a built-in one or a role definition, and role definitions must be
+    /// called with as many arguments as they take.
+    ///
+    /// All problems found are returned at once in a
+    /// [`ModelError::Validation`]. Only built-in functions are known here,
+    /// use [`validate_with_functions`](DefaultModel::validate_with_functions)
+    /// when the matchers call custom ones.
+    ///
+    /// An enforcer runs this check when built, leaving out function calls,
+    /// which are checked against its registered functions on the first
+    /// enforce.
+    pub fn validate(&self) -> Result<()> {
+        self.validate_with_functions(&FunctionMap::default())
+    }
+
+    /// Same as [`validate`](DefaultModel::validate), with the functions
+    /// registered in `fm` as the known ones.
+    pub fn validate_with_functions(&self, fm: &FunctionMap) -> Result<()> {
+        self.check_matchers(Some(fm))
+    }
+
+    // without `fm` function calls aren't checked, as when building an
+    // enforcer, which can still have custom functions added
+    pub(crate) fn check_matchers(
+        &self,
+        fm: Option<&FunctionMap>,
+    ) -> Result<()> {
+        let mut problems = vec![];
+        let empty = AssertionMap::new();
+        let matchers = self.model.get("m").unwrap_or(&empty);
+        let roles = self.model.get("g").unwrap_or(&empty);
+
+        for (key, m) in matchers {
+            let (refs, calls) = scan_matcher(&m.value);
+
+            for name in refs {
+                let (sec, _) = name.split_once('_').unwrap_or_default();
+                let defined = ["r", "p"].iter().any(|x| {
+                    self.model
+                        .get(*x)
+                        .and_then(|ast_map| ast_map.get(sec))
+                        .is_some_and(|ast| ast.tokens.iter().any(|t| t == name))
+                });
+                if !defined {
+                    problems.push(ModelError::M(format!(
+                        "matcher `{}` references undefined `{}`",
+                        key,
+                        name.replacen('_', ".", 1)
+                    )));
+                }
+            }
+
+            for (name, args) in calls {
+                if let Some(ast) = roles.get(name) {
+                    match ast.role_arity() {
+                        Ok(arity) if arity != args => {
+                            problems.push(ModelError::M(format!(
+                                "matcher `{}` calls `{}` with {} arguments, \
+                                 but its role definition takes {}",
+                                key, name, args, arity
+                            )));
+                        }
+                        Err(Error::ModelError(err)) => problems.push(err),
+                        _ => {}
+                    }
+                } else if fm.is_some_and(|fm| {
+                    !BUILTIN_FUNCTIONS.contains(&name)
+                        && !fm.get_functions().any(|(f, _)| f == name)
+                }) {
+                    problems.push(ModelError::M(format!(
+                        "matcher `{}` calls unknown function `{}`",
+                        key, name
+                    )));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ModelError::Validation(problems).into())
+        }
+    }
     #[cfg(not(target_arch = "wasm32"))]
     pub async fn from_file<P: AsRef<ioPath>>(p: P) -> Result<DefaultModel> {
-        let cfg = Config::from_file(p).await?;
+        DefaultModel::from_config(Config::from_file(p).await?)
+    }
 
+    /// Reads the definitions of a parsed model file, e.g. one read with
+    /// includes enabled through [`ConfigOptions`](crate::ConfigOptions).
+    pub fn from_config(cfg: Config) -> Result<DefaultModel> {
         let mut model = DefaultModel::default();
 
         model.load_section(&cfg, "r")?;
@@ -76,24 +191,99 @@ impl DefaultModel {
         model.load_section(&cfg, "m")?;
 
         model.load_section(&cfg, "g")?;
+        model.config = Some(cfg);
 
         Ok(model)
     }
 
-    #[allow(clippy::should_implement_trait)]
-    pub async fn from_str(s: &str) -> Result<DefaultModel> {
-        let cfg = Config::from_str(s).await?;
+    /// Returns the model as a config, ready to be written as a model file.
+    ///
+    /// A model read from a file or string keeps that text, with only the
+    /// definitions added, changed or removed since then rewritten.
+    pub fn to_config(&self) -> Config {
+        self.write_definitions(self.config.clone().unwrap_or_default())
+    }
 
-        let mut model = DefaultModel::default();
+    // Sets the definitions of the model on `cfg`, leaving the rest of it as
+    // it was
+    fn write_definitions(&self, mut cfg: Config) -> Config {
+        let empty = AssertionMap::new();
+
+        for (sec, sec_name) in SECTION_NAMES {
+            let asts = self.model.get(sec).unwrap_or(&empty);
+            for key in self.texts.keys() {
+                let key_sec =
+                    key.trim_end_matches(|c: char| c.is_ascii_digit());
+                if key_sec == sec && !asts.contains_key(key) {
+                    cfg.remove(&format!("{}::{}", sec_name, key));
+                }
+            }
 
-        model.load_section(&cfg, "r")?;
-        model.load_section(&cfg, "p")?;
-        model.load_section(&cfg, "e")?;
-        model.load_section(&cfg, "m")?;
+            for (key, ast) in asts {
+                let escape = |x: &str| match sec {
+                    "r" | "p" => x.to_owned(),
+                    _ => escape_assertion(x),
+                };
+                let text = match self.texts.get(key) {
+                    Some(text) if escape(text) == ast.value => text.clone(),
+                    _ => unescape_assertion(&ast.value),
+                };
+
+                let path = format!("{}::{}", sec_name, key);
+                if cfg.get(&path).map(remove_comment).as_ref() != Some(&text) {
+                    cfg.set(&path, &text);
+                }
+            }
+        }
 
-        model.load_section(&cfg, "g")?;
+        cfg
+    }
 
-        Ok(model)
+    /// Writes the model to a model file, see
+    /// [`to_config`](DefaultModel::to_config).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn save_to_file<P: AsRef<ioPath>>(&self, p: P) -> Result<()> {
+        self.to_config().save(p).await
+    }
+
+    /// Starts a [`ModelBuilder`] to define a model in code.
+    pub fn builder() -> ModelBuilder {
+        ModelBuilder::default()
+    }
+
+    /// Reads a model serialized to JSON with its policies, as produced by
+    /// [`to_json`](DefaultModel::to_json).
+    pub fn from_json(s: &str) -> Result<DefaultModel> {
+        serde_json::from_str(s).map_err(|e| {
+            ModelError::Other(format!("Invalid model JSON: {}", e)).into()
+        })
+    }
+
+    /// Serializes the definitions, their tokens and the policies of every
+    /// ptype to JSON. Equal models give identical documents.
+    pub fn to_json(&self) -> Result<String> {
+        sections_to_json(&self.model)
+    }
+
+    fn from_sections(model: HashMap<String, AssertionMap>) -> Result<Self> {
+        let mut effects = HashMap::new();
+        for (key, ast) in model.get("e").into_iter().flatten() {
+            effects.insert(
+                key.clone(),
+                Arc::new(ParsedEffect::parse(&ast.value)?),
+            );
+        }
+
+        Ok(DefaultModel {
+            model,
+            effects,
+            ..Default::default()
+        })
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub async fn from_str(s: &str) -> Result<DefaultModel> {
+        DefaultModel::from_config(Config::from_str(s).await?)
     }
 
     fn load_section(&mut self, cfg: &Config, sec: &str) -> Result<()> {
@@ -118,23 +308,17 @@ impl DefaultModel {
         sec: &str,
         key: &str,
     ) -> Result<bool> {
-        let sec_name = match sec {
-            "r" => "request_definition",
-            "p" => "policy_definition",
-            "g" => "role_definition",
-            "e" => "policy_effect",
-            "m" => "matchers",
-            _ => {
-                return Err(ModelError::Other(format!(
-                    "Unknown section: `{}`",
-                    sec
-                ))
-                .into());
-            }
+        let Some((_, sec_name)) = SECTION_NAMES.iter().find(|x| x.0 == sec)
+        else {
+            return Err(ModelError::Other(format!(
+                "Unknown section: `{}`",
+                sec
+            ))
+            .into());
         };
 
         if let Some(val) = cfg.get_str(&format!("{}::{}", sec_name, key)) {
-            Ok(self.add_def(sec, key, val))
+            self.add_def(sec, key, val)
         } else {
             Ok(false)
         }
@@ -149,8 +333,144 @@ impl DefaultModel {
     }
 }
 
+pub(crate) fn sections_to_json(
+    model: &HashMap<String, AssertionMap>,
+) -> Result<String> {
+    serde_json::to_string(&model.iter().collect::<BTreeMap<_, _>>())
+        .map_err(|e| ModelError::Other(e.to_string()).into())
+}
+
+impl Serialize for DefaultModel {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        // sections are sorted so that equal models serialize identically
+        self.model
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DefaultModel {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let model = HashMap::<String, AssertionMap>::deserialize(deserializer)?;
+        DefaultModel::from_sections(model).map_err(de::Error::custom)
+    }
+}
+
+// functions available to every matcher besides the function map
+const BUILTIN_FUNCTIONS: &[&str] = &["eval", "escape_assertion"];
+
+// Finds the `r*_*`/`p*_*` references and the function calls, with their
+// number of arguments, of an escaped matcher. Method calls and string
+// literals are skipped.
+fn scan_matcher(m: &str) -> (Vec<&str>, Vec<(&str, usize)>) {
+    let bytes = m.as_bytes();
+    let mut refs = vec![];
+    let mut calls = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'"' || c == b'\'' || c == b'`' {
+            i = skip_string(bytes, i);
+            continue;
+        }
+        if !(c.is_ascii_alphabetic() || c == b'_') {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < bytes.len()
+            && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_')
+        {
+            i += 1;
+        }
+        let ident = &m[start..i];
+        let is_member = m[..start].trim_end().ends_with('.');
+        let rest = m[i..].trim_start();
+
+        if is_member {
+            continue;
+        }
+        if rest.starts_with('(') {
+            let open = m.len() - rest.len();
+            calls.push((ident, count_args(bytes, open)));
+        } else if is_reference(ident) && !refs.contains(&ident) {
+            refs.push(ident);
+        }
+    }
+
+    (refs, calls)
+}
+
+// `r_sub`, `p2_obj`...
+fn is_reference(ident: &str) -> bool {
+    let (sec, token) = match ident.split_once('_') {
+        Some(x) => x,
+        None => return false,
+    };
+
+    !token.is_empty()
+        && (sec.starts_with('r') || sec.starts_with('p'))
+        && sec[1..].bytes().all(|c| c.is_ascii_digit())
+}
+
+// index right after the string literal starting at `start`
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() && bytes[i] != quote {
+        if bytes[i] == b'\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    i + 1
+}
+
+// number of arguments of the call whose `(` is at `open`
+fn count_args(bytes: &[u8], open: usize) -> usize {
+    let mut depth = 0;
+    let mut args = 0;
+    let mut empty = true;
+    let mut i = open;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' | b'`' => {
+                i = skip_string(bytes, i);
+                empty = false;
+                continue;
+            }
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            b',' if depth == 1 => args += 1,
+            c if !c.is_ascii_whitespace() && depth >= 1 => empty = false,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if empty {
+        0
+    } else {
+        args + 1
+    }
+}
+
 impl Model for DefaultModel {
-    fn add_def(&mut self, sec: &str, key: &str, value: &str) -> bool {
+    fn add_def(&mut self, sec: &str, key: &str, value: &str) -> Result<bool> {
         let mut ast = Assertion {
             key: key.to_owned(),
             value: remove_comment(value),
@@ -158,8 +478,9 @@ impl Model for DefaultModel {
         };
 
         if ast.value.is_empty() {
-            return false;
+            return Ok(false);
         }
+        self.texts.insert(key.to_owned(), ast.value.clone());
 
         if sec == "r" || sec == "p" {
             ast.tokens = ast
@@ -171,6 +492,13 @@ impl Model for DefaultModel {
             ast.value = escape_assertion(&ast.value);
         }
 
+        if sec == "e" {
+            self.effects.insert(
+                key.to_owned(),
+                Arc::new(ParsedEffect::parse(&ast.value)?),
+            );
+        }
+
         if let Some(new_model) = self.model.get_mut(sec) {
             new_model.insert(key.to_owned(), ast);
         } else {
@@ -179,7 +507,7 @@ impl Model for DefaultModel {
             self.model.insert(sec.to_owned(), new_ast_map);
         }
 
-        true
+        Ok(true)
     }
 
     #[inline]
@@ -216,6 +544,8 @@ impl Model for DefaultModel {
             | EventData::RemovePolicy(ref sec, ref ptype, _)
             | EventData::RemovePolicies(ref sec, ref ptype, _)
             | EventData::RemoveFilteredPolicy(ref sec, ref ptype, _)
+            | EventData::UpdatePolicy(ref sec, ref ptype, _, _)
+            | EventData::UpdatePolicies(ref sec, ref ptype, _, _)
                 if sec == "g" =>
             {
                 self.model
@@ -240,7 +570,7 @@ impl Model for DefaultModel {
     ) -> bool {
         if let Some(ast_map) = self.model.get_mut(sec) {
             if let Some(ast) = ast_map.get_mut(ptype) {
-                return ast.policy.insert(rule);
+                return ast.insert_policy(rule);
             }
         }
         false
@@ -261,7 +591,9 @@ impl Model for DefaultModel {
                         return all_added;
                     }
                 }
-                ast.policy.extend(rules);
+                for rule in rules {
+                    ast.insert_policy(rule);
+                }
             }
         }
         all_added
@@ -368,6 +700,74 @@ impl Model for DefaultModel {
         all_removed
     }
 
+    fn update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> bool {
+        self.update_policies(sec, ptype, vec![old_rule], vec![new_rule])
+    }
+
+    fn update_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> bool {
+        if old_rules.len() != new_rules.len() {
+            return false;
+        }
+
+        if let Some(ast_map) = self.model.get_mut(sec) {
+            if let Some(ast) = ast_map.get_mut(ptype) {
+                for rule in &old_rules {
+                    if !ast.policy.contains(rule) {
+                        return false;
+                    }
+                }
+
+                let replacements: HashMap<&Vec<String>, &Vec<String>> =
+                    old_rules.iter().zip(new_rules.iter()).collect();
+
+                // rebuild the set so that updated rules keep their position
+                let policy: LinkedHashSet<Vec<String>> = ast
+                    .policy
+                    .iter()
+                    .map(|rule| {
+                        (*replacements.get(rule).unwrap_or(&rule)).clone()
+                    })
+                    .collect();
+
+                // a new rule collided with an existing one
+                if policy.len() != ast.policy.len() {
+                    return false;
+                }
+
+                ast.policy = policy;
+                // move the rules whose priority changed to their new place
+                if let Some(priority) = ast.priority_fn() {
+                    let moved: Vec<&Vec<String>> = old_rules
+                        .iter()
+                        .zip(new_rules.iter())
+                        .filter(|(old, new)| priority(old) != priority(new))
+                        .map(|(_, new)| new)
+                        .collect();
+                    for rule in &moved {
+                        ast.policy.remove(*rule);
+                    }
+                    for rule in moved {
+                        ast.insert_policy(rule.clone());
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+
     fn clear_policy(&mut self) {
         if let Some(model_p) = self.model.get_mut("p") {
             for ast in model_p.values_mut() {
@@ -423,63 +823,11 @@ impl Model for DefaultModel {
     }
 
     fn to_text(&self) -> String {
-        let mut token_patterns = HashMap::new();
-        let p_pattern = regex::Regex::new(r"^p_").unwrap();
-        let r_pattern = regex::Regex::new(r"^r_").unwrap();
-
-        for ptype in ["r", "p"] {
-            if let Some(assertion) = self.model.get(ptype) {
-                for token in &assertion[ptype].tokens {
-                    let new_token = p_pattern.replace_all(token, "p.");
-                    let new_token = r_pattern.replace_all(&new_token, "r.");
-                    token_patterns.insert(token.clone(), new_token.to_string());
-                }
-            }
-        }
-
-        if let Some(assertions) = self.model.get("e") {
-            if let Some(assertion) = assertions.get("e") {
-                if assertion.value.contains("p_eft") {
-                    token_patterns
-                        .insert("p_eft".to_string(), "p.eft".to_string());
-                }
-            }
-        }
-
-        let mut s = String::new();
-
-        let write_string = |sec: &str, s: &mut String| {
-            if let Some(assertions) = self.model.get(sec) {
-                for (_ptype, assertion) in assertions {
-                    let mut value = assertion.value.clone();
-                    for (token_pattern, new_token) in &token_patterns {
-                        value = value.replace(token_pattern, new_token);
-                    }
-                    s.push_str(&format!("{} = {}\n", sec, value));
-                }
-            }
-        };
-
-        s.push_str("[request_definition]\n");
-        write_string("r", &mut s);
-        s.push_str("[policy_definition]\n");
-        write_string("p", &mut s);
-
-        if self.model.contains_key("g") {
-            s.push_str("[role_definition]\n");
-            if let Some(assertions) = self.model.get("g") {
-                for (ptype, assertion) in assertions {
-                    s.push_str(&format!("{} = {}\n", ptype, assertion.value));
-                }
-            }
-        }
-
-        s.push_str("[policy_effect]\n");
-        write_string("e", &mut s);
-        s.push_str("[matchers]\n");
-        write_string("m", &mut s);
+        self.to_config().to_string()
+    }
 
-        s
+    fn to_normalized_text(&self) -> String {
+        self.write_definitions(Config::default()).to_string()
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -492,9 +840,13 @@ impl Model for DefaultModel {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::useless_vec)]
 mod tests {
     use crate::prelude::*;
 
+    #[cfg(not(target_arch = "wasm32"))]
+    use crate::adapter::file_adapter::TempPolicy;
+
     #[cfg(not(target_arch = "wasm32"))]
     #[cfg_attr(
         all(feature = "runtime-async-std", not(target_arch = "wasm32")),
@@ -978,7 +1330,8 @@ mod tests {
                 .await
                 .unwrap();
 
-        let adapter = FileAdapter::new("examples/rbac_with_domains_policy.csv");
+        let policy = TempPolicy::copy("examples/rbac_with_domains_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
 
         e.add_policy(
@@ -1101,7 +1454,8 @@ mod tests {
             .await
             .unwrap();
 
-        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
 
         e.add_grouping_policy(
@@ -1204,4 +1558,260 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_validate_examples() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "conf")
+                || path.ends_with("keymatch_custom_model.conf")
+                || (cfg!(not(feature = "ip"))
+                    && path.ends_with("ipmatch_model.conf"))
+            {
+                continue;
+            }
+
+            let m = DefaultModel::from_file(&path).await.unwrap();
+            assert!(m.validate().is_ok(), "{}", path.display());
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_validate_reports_every_problem() {
+        use crate::function_map::{FunctionMap, OperatorFunction};
+
+        let text = r#"
+[request_definition]
+r = sub, obj, act
+
+[policy_definition]
+p = sub, obj, act
+
+[role_definition]
+g = _, _
+
+[policy_effect]
+e = some(where (p.eft == allow))
+
+[matchers]
+m = g(r.subject, p.sub, r.dom) && fooMatch(r.obj, p.obj) && r.act == "p.x"
+"#;
+        let m = DefaultModel::from_str(text).await.unwrap();
+        let err = m.validate().unwrap_err().to_string();
+
+        assert!(err.contains("references undefined `r.subject`"), "{}", err);
+        assert!(err.contains("calls `g` with 3 arguments"), "{}", err);
+        assert!(err.contains("unknown function `fooMatch`"), "{}", err);
+        assert!(!err.contains("p.x"), "{}", err);
+
+        let mut fm = FunctionMap::default();
+        fm.add_function("fooMatch", OperatorFunction::Arg2(|_, _| true.into()));
+        let err = m.validate_with_functions(&fm).unwrap_err().to_string();
+        assert!(!err.contains("fooMatch"), "{}", err);
+
+        // unknown functions may still be added once the enforcer exists
+        let adapter = MemoryAdapter::default();
+        assert!(Enforcer::new(m, adapter).await.is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_functions_checked_on_enforce() {
+        use crate::function_map::OperatorFunction;
+
+        let text = r#"
+[request_definition]
+r = sub, obj, act
+
+[policy_definition]
+p = sub, obj, act
+
+[policy_effect]
+e = some(where (p.eft == allow))
+
+[matchers]
+m = r.sub == p.sub && fooMatch(r.obj, p.obj) && r.act == p.act
+"#;
+        let m = DefaultModel::from_str(text).await.unwrap();
+        let mut e = Enforcer::new(m, MemoryAdapter::default()).await.unwrap();
+        e.add_policy(vec!["alice".into(), "data1".into(), "read".into()])
+            .await
+            .unwrap();
+
+        let err = e.enforce(("bob", "data1", "read")).unwrap_err();
+        assert!(err.to_string().contains("unknown function `fooMatch`"));
+
+        e.add_function("fooMatch", OperatorFunction::Arg2(|_, _| true.into()));
+        assert!(e.enforce(("alice", "data2", "read")).unwrap());
+        assert!(!e.enforce(("bob", "data1", "read")).unwrap());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_model_json() {
+        let mut m =
+            DefaultModel::from_file("examples/rbac_with_deny_model.conf")
+                .await
+                .unwrap();
+        m.add_policy(
+            "p",
+            "p",
+            vec!["alice", "data1", "read", "allow"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        );
+
+        let json = m.to_json().unwrap();
+        let restored = DefaultModel::from_json(&json).unwrap();
+        assert_eq!(json, restored.to_json().unwrap());
+        assert_eq!(
+            restored.get_policy("p", "p"),
+            vec![vec!["alice", "data1", "read", "allow"]]
+        );
+        assert_eq!(
+            m.get_model()["e"]["e"].value,
+            restored.get_model()["e"]["e"].value
+        );
+
+        assert!(DefaultModel::from_json("{").is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_to_text_keeps_layout() {
+        let text = r#"# operator notes
+[request_definition]
+r = sub, obj, act
+
+[policy_definition]
+p = sub, obj, act
+p2 = sub, act
+
+[policy_effect]
+e = some(where (p.eft == allow)) # allow wins
+
+[matchers]
+# keep in sync with the API gateway
+m = r.sub == p.sub \
+    && r.obj == p.obj \
+    && r.act == p.act
+"#;
+        let mut m = DefaultModel::from_str(text).await.unwrap();
+        assert_eq!(text, m.to_text());
+        assert_eq!(
+            m.to_normalized_text(),
+            "[request_definition]\n\
+             r = sub, obj, act\n\
+             \n\
+             [policy_definition]\n\
+             p = sub, obj, act\n\
+             p2 = sub, act\n\
+             \n\
+             [policy_effect]\n\
+             e = some(where (p.eft == allow))\n\
+             \n\
+             [matchers]\n\
+             m = r.sub == p.sub&& r.obj == p.obj&& r.act == p.act\n"
+        );
+
+        m.add_def("m", "m", "r.sub == p.sub && keyMatch(r.obj, p.obj)")
+            .unwrap();
+        m.add_def("g", "g", "_, _").unwrap();
+        m.get_mut_model().get_mut("p").unwrap().remove("p2");
+        assert_eq!(
+            m.to_text(),
+            r#"# operator notes
+[request_definition]
+r = sub, obj, act
+
+[policy_definition]
+p = sub, obj, act
+
+[policy_effect]
+e = some(where (p.eft == allow)) # allow wins
+
+[matchers]
+# keep in sync with the API gateway
+m = r.sub == p.sub && keyMatch(r.obj, p.obj)
+
+[role_definition]
+g = _, _
+"#
+        );
+
+        let path = std::env::temp_dir()
+            .join(format!("casbin_model_{}.conf", std::process::id()));
+        m.save_to_file(&path).await.unwrap();
+        let saved = DefaultModel::from_file(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(m.to_json().unwrap(), saved.to_json().unwrap());
+    }
+
+    #[test]
+    fn test_to_text_without_source() {
+        let m = DefaultModel::builder()
+            .request("r", ["sub", "obj", "act"])
+            .policy("p", ["sub", "obj", "act"])
+            .role("g", 2)
+            .effect("e", "some(where (p.eft == allow))")
+            .matcher("m", "g(r.sub, p.sub) && r.obj == p.obj")
+            .build()
+            .unwrap();
+        let text = "[request_definition]\n\
+                    r = sub, obj, act\n\
+                    \n\
+                    [policy_definition]\n\
+                    p = sub, obj, act\n\
+                    \n\
+                    [role_definition]\n\
+                    g = _, _\n\
+                    \n\
+                    [policy_effect]\n\
+                    e = some(where (p.eft == allow))\n\
+                    \n\
+                    [matchers]\n\
+                    m = g(r.sub, p.sub) && r.obj == p.obj\n";
+        assert_eq!(m.to_text(), text);
+
+        let restored = DefaultModel::from_json(&m.to_json().unwrap()).unwrap();
+        assert_eq!(restored.to_text(), text);
+    }
 }
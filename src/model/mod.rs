@@ -8,15 +8,18 @@ use parking_lot::RwLock;
 use std::{collections::HashMap, sync::Arc};
 
 mod assertion;
+mod builder;
 mod default_model;
 pub mod function_map;
 
 pub use assertion::{Assertion, AssertionMap};
+pub use builder::ModelBuilder;
+pub(crate) use default_model::sections_to_json;
 pub use default_model::DefaultModel;
 pub use function_map::*;
 
 pub trait Model: Send + Sync {
-    fn add_def(&mut self, sec: &str, key: &str, value: &str) -> bool;
+    fn add_def(&mut self, sec: &str, key: &str, value: &str) -> Result<bool>;
     fn get_model(&self) -> &HashMap<String, AssertionMap>;
     fn get_mut_model(&mut self) -> &mut HashMap<String, AssertionMap>;
     fn build_role_links(
@@ -64,6 +67,56 @@ pub trait Model: Send + Sync {
         ptype: &str,
         rules: Vec<Vec<String>>,
     ) -> bool;
+    /// Replaces `old_rule` with `new_rule`, leaving the model untouched if
+    /// `old_rule` is missing or `new_rule` already exists.
+    fn update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) -> bool {
+        if old_rule == new_rule {
+            return self.has_policy(sec, ptype, old_rule);
+        }
+        if !self.has_policy(sec, ptype, old_rule.clone())
+            || self.has_policy(sec, ptype, new_rule.clone())
+        {
+            return false;
+        }
+
+        self.remove_policy(sec, ptype, old_rule)
+            && self.add_policy(sec, ptype, new_rule)
+    }
+    /// Replaces every rule of `old_rules` with the matching rule of
+    /// `new_rules`, leaving the model untouched if any of them can't be.
+    fn update_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) -> bool {
+        if old_rules.len() != new_rules.len()
+            || !old_rules
+                .iter()
+                .all(|rule| self.has_policy(sec, ptype, rule.clone()))
+        {
+            return false;
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for rule in &new_rules {
+            let collides = !old_rules.contains(rule)
+                && self.has_policy(sec, ptype, rule.clone());
+            if collides || !seen.insert(rule) {
+                return false;
+            }
+        }
+
+        self.remove_policies(sec, ptype, old_rules)
+            && self.add_policies(sec, ptype, new_rules)
+    }
     fn clear_policy(&mut self);
     fn remove_filtered_policy(
         &mut self,
@@ -72,8 +125,23 @@ pub trait Model: Send + Sync {
         field_index: usize,
         field_values: Vec<String>,
     ) -> (bool, Vec<Vec<String>>);
+    /// The model as the text of a model file, keeping the layout and
+    /// comments of the text it was read from.
     fn to_text(&self) -> String;
+    /// The definitions alone as the text of a model file, without the
+    /// comments, includes or layout of the text it was read from.
+    fn to_normalized_text(&self) -> String {
+        self.to_text()
+    }
     // Downcast support for performance
     fn as_any(&self) -> &dyn std::any::Any;
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
+    /// Orders the policies of every `p` assertion by its `priority` field.
+    fn sort_policies_by_priority(&mut self) {
+        if let Some(ast_map) = self.get_mut_model().get_mut("p") {
+            for ast in ast_map.values_mut() {
+                ast.sort_policies_by_priority();
+            }
+        }
+    }
 }
@@ -1,7 +1,8 @@
 pub use crate::{
-    CoreApi, DefaultModel, Enforcer, Event, EventData, EventEmitter, Filter,
-    IEnforcer, InternalApi, MemoryAdapter, MgmtApi, Model, NullAdapter,
-    RbacApi, Result, StringAdapter, TryIntoAdapter, TryIntoModel,
+    CoreApi, DefaultModel, DomainRbacApi, Enforcer, Event, EventData,
+    EventEmitter, Filter, IEnforcer, InternalApi, MemoryAdapter, MgmtApi,
+    Model, NullAdapter, PolicyFilter, RbacApi, Result, StringAdapter,
+    TryIntoAdapter, TryIntoModel, UpdatableAdapter,
 };
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -11,4 +12,10 @@ pub use crate::FileAdapter;
 pub use crate::{CachedApi, CachedEnforcer};
 
 #[cfg(feature = "watcher")]
-pub use crate::Watcher;
+pub use crate::{Watcher, WatcherEx};
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "runtime-tokio", feature = "runtime-async-std")
+))]
+pub use crate::SyncedEnforcer;
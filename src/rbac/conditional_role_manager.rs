@@ -0,0 +1,434 @@
+use crate::{
+    error::RbacError,
+    rbac::{
+        default_role_manager::{
+            domain_key, domain_matches, key_domains, single_key,
+        },
+        LinkConditionFn, MatchingFn, RoleManager,
+    },
+    Result,
+};
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// user -> role -> condition parameters of each rule linking them
+type Links = HashMap<String, HashMap<String, HashSet<Vec<String>>>>;
+
+/// A role manager whose links can carry condition parameters, taken from
+/// the parenthesized fields of a role definition such as `g = _, _, (_, _)`.
+///
+/// A link is only followed while the condition of one of its rules holds. When the first
+/// parameter names a function registered with `add_link_condition_fn`, that
+/// function is called with the remaining parameters, otherwise the
+/// parameters are read as a `start, end` time window in the
+/// `YYYY-MM-DD HH:MM:SS` (UTC) or unix seconds format, where an empty value
+/// or `_` leaves that side unbounded.
+pub struct ConditionalRoleManager {
+    all_domains: HashMap<Vec<String>, Links>,
+    condition_fns: HashMap<String, LinkConditionFn>,
+    max_hierarchy_level: usize,
+    role_matching_fn: Option<MatchingFn>,
+    domain_matching_fn: Option<MatchingFn>,
+}
+
+impl ConditionalRoleManager {
+    pub fn new(max_hierarchy_level: usize) -> Self {
+        ConditionalRoleManager {
+            all_domains: HashMap::new(),
+            condition_fns: HashMap::new(),
+            max_hierarchy_level,
+            role_matching_fn: None,
+            domain_matching_fn: None,
+        }
+    }
+
+    fn matched_domains(&self, domain: &[String]) -> Vec<&Vec<String>> {
+        self.all_domains
+            .keys()
+            .filter(|key| {
+                key.as_slice() == domain
+                    || self
+                        .domain_matching_fn
+                        .map(|f| domain_matches(f, domain, key))
+                        .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    fn is_link_active(&self, params: &[String]) -> bool {
+        if params.iter().all(|x| x.is_empty()) {
+            return true;
+        }
+
+        let params: Vec<&str> = params.iter().map(String::as_str).collect();
+        match self.condition_fns.get(params[0]) {
+            Some(f) => f(&params[1..]),
+            None => time_match(&params),
+        }
+    }
+
+    fn is_any_active(&self, params: &HashSet<Vec<String>>) -> bool {
+        params.iter().any(|x| self.is_link_active(x))
+    }
+
+    // roles directly reachable from `name` through active links
+    fn active_roles<'a>(
+        &'a self,
+        links: &'a Links,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a String> + 'a {
+        links
+            .iter()
+            .filter(move |(user, _)| {
+                user.as_str() == name
+                    || self
+                        .role_matching_fn
+                        .map(|f| f(name, user))
+                        .unwrap_or_default()
+            })
+            .flat_map(|(_, roles)| roles.iter())
+            .filter(|(_, params)| self.is_any_active(params))
+            .map(|(role, _)| role)
+    }
+}
+
+impl RoleManager for ConditionalRoleManager {
+    fn clear(&mut self) {
+        self.all_domains.clear();
+    }
+
+    fn add_link(&mut self, name1: &str, name2: &str, domain: &[&str]) {
+        // a link without parameters is always active
+        let _ = self.add_conditional_link(name1, name2, domain, &[]);
+    }
+
+    fn matching_fn(
+        &mut self,
+        role_matching_fn: Option<MatchingFn>,
+        domain_matching_fn: Option<MatchingFn>,
+    ) {
+        self.role_matching_fn = role_matching_fn;
+        self.domain_matching_fn = domain_matching_fn;
+    }
+
+    fn delete_link(
+        &mut self,
+        name1: &str,
+        name2: &str,
+        domain: &[&str],
+    ) -> Result<()> {
+        let removed = self
+            .all_domains
+            .get_mut(&domain_key(domain))
+            .and_then(|links| links.get_mut(name1))
+            .and_then(|roles| roles.remove(name2));
+
+        if removed.is_none() {
+            return Err(
+                RbacError::NotFound(format!("{} OR {}", name1, name2)).into()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn has_link(&self, name1: &str, name2: &str, domain: &[&str]) -> bool {
+        if name1 == name2 {
+            return true;
+        }
+
+        self.matched_domains(&domain_key(domain))
+            .into_iter()
+            .any(|d| {
+                let links = &self.all_domains[d];
+                let mut visited = HashSet::new();
+                let mut queue = VecDeque::from([(name1, 0)]);
+
+                while let Some((name, level)) = queue.pop_front() {
+                    if level >= self.max_hierarchy_level
+                        || !visited.insert(name)
+                    {
+                        continue;
+                    }
+
+                    for role in self.active_roles(links, name) {
+                        if role == name2
+                            || self
+                                .role_matching_fn
+                                .map(|f| f(role, name2))
+                                .unwrap_or_default()
+                        {
+                            return true;
+                        }
+                        queue.push_back((role, level + 1));
+                    }
+                }
+
+                false
+            })
+    }
+
+    fn get_roles(&self, name: &str, domain: Option<&str>) -> Vec<String> {
+        let roles: HashSet<&String> = self
+            .matched_domains(&single_key(domain))
+            .into_iter()
+            .flat_map(|d| self.active_roles(&self.all_domains[d], name))
+            .collect();
+
+        roles.into_iter().cloned().collect()
+    }
+
+    fn get_users(&self, name: &str, domain: Option<&str>) -> Vec<String> {
+        let users: HashSet<&String> = self
+            .matched_domains(&single_key(domain))
+            .into_iter()
+            .flat_map(|d| self.all_domains[d].iter())
+            .filter(|(_, roles)| {
+                roles
+                    .get(name)
+                    .map(|params| self.is_any_active(params))
+                    .unwrap_or_default()
+            })
+            .map(|(user, _)| user)
+            .collect();
+
+        users.into_iter().cloned().collect()
+    }
+
+    fn get_all_domains(&self) -> Vec<String> {
+        key_domains(self.all_domains.keys())
+    }
+
+    fn delete_domain(&mut self, domain: &str) -> Result<()> {
+        self.all_domains
+            .retain(|key, _| !key.iter().any(|d| d == domain));
+        Ok(())
+    }
+
+    fn add_conditional_link(
+        &mut self,
+        name1: &str,
+        name2: &str,
+        domain: &[&str],
+        params: &[&str],
+    ) -> Result<()> {
+        if name1 == name2 {
+            return Ok(());
+        }
+
+        self.all_domains
+            .entry(domain_key(domain))
+            .or_default()
+            .entry(name1.to_owned())
+            .or_default()
+            .entry(name2.to_owned())
+            .or_default()
+            .insert(params.iter().map(|x| (*x).to_owned()).collect());
+
+        Ok(())
+    }
+
+    fn delete_conditional_link(
+        &mut self,
+        name1: &str,
+        name2: &str,
+        domain: &[&str],
+        params: &[&str],
+    ) -> Result<()> {
+        let params: Vec<String> =
+            params.iter().map(|x| (*x).to_owned()).collect();
+        let roles = self
+            .all_domains
+            .get_mut(&domain_key(domain))
+            .and_then(|links| links.get_mut(name1));
+
+        let removed = match roles {
+            Some(roles) => {
+                let removed = roles
+                    .get_mut(name2)
+                    .is_some_and(|rules| rules.remove(&params));
+                // the link goes once its last rule does
+                if roles.get(name2).is_some_and(HashSet::is_empty) {
+                    roles.remove(name2);
+                }
+                removed
+            }
+            None => false,
+        };
+
+        if !removed {
+            return Err(
+                RbacError::NotFound(format!("{} OR {}", name1, name2)).into()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn add_link_condition_fn(
+        &mut self,
+        name: &str,
+        f: LinkConditionFn,
+    ) -> Result<()> {
+        self.condition_fns.insert(name.to_owned(), f);
+        Ok(())
+    }
+}
+
+/// Checks that now lies within the `start, end` window given by `params`.
+fn time_match(params: &[&str]) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+
+    let bound = |param: Option<&&str>| match param.map(|x| x.trim()) {
+        None | Some("") | Some("_") => Some(None),
+        Some(param) => parse_time(param).map(Some),
+    };
+
+    match (bound(params.first()), bound(params.get(1))) {
+        (Some(start), Some(end)) => {
+            start.is_none_or(|start| start <= now)
+                && end.is_none_or(|end| now < end)
+        }
+        // an unreadable bound never matches
+        _ => false,
+    }
+}
+
+// unix seconds of `YYYY-MM-DD HH:MM:SS` in UTC, or of a plain integer
+fn parse_time(s: &str) -> Option<i64> {
+    if let Ok(secs) = s.parse::<i64>() {
+        return Some(secs);
+    }
+
+    let (date, time) = s.split_once(' ').unwrap_or((s, "00:00:00"));
+    let mut date = date.splitn(3, '-').map(|x| x.parse::<i64>().ok());
+    let (y, m, d) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.splitn(3, ':').map(|x| x.parse::<i64>().ok());
+    let (hh, mm, ss) = (time.next()??, time.next()??, time.next()??);
+
+    if !(1..=12).contains(&m)
+        || !(1..=31).contains(&d)
+        || !(0..24).contains(&hh)
+        || !(0..60).contains(&mm)
+        || !(0..60).contains(&ss)
+    {
+        return None;
+    }
+
+    // days since 1970-01-01 in the proleptic gregorian calendar
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Some(days * 86400 + hh * 3600 + mm * 60 + ss)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort_unstable<T: Ord>(mut v: Vec<T>) -> Vec<T> {
+        v.sort_unstable();
+        v
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(Some(0), parse_time("1970-01-01 00:00:00"));
+        assert_eq!(Some(951782400), parse_time("2000-02-29 00:00:00"));
+        assert_eq!(Some(1704067199), parse_time("2023-12-31 23:59:59"));
+        assert_eq!(Some(1704067200), parse_time("1704067200"));
+        assert_eq!(None, parse_time("2023-13-01 00:00:00"));
+        assert_eq!(None, parse_time("yesterday"));
+    }
+
+    #[test]
+    fn test_time_window() {
+        let mut rm = ConditionalRoleManager::new(10);
+        rm.add_conditional_link(
+            "alice",
+            "contractor",
+            &[],
+            &["2000-01-01 00:00:00", "2999-01-01 00:00:00"],
+        )
+        .unwrap();
+        rm.add_conditional_link("bob", "contractor", &[], &["_", "2000-01-01"])
+            .unwrap();
+        rm.add_conditional_link(
+            "carol",
+            "contractor",
+            &[],
+            &["2999-01-01", ""],
+        )
+        .unwrap();
+        rm.add_conditional_link("dave", "contractor", &[], &["soon", ""])
+            .unwrap();
+        rm.add_link("contractor", "staff", &[]);
+
+        assert!(rm.has_link("alice", "contractor", &[]));
+        assert!(rm.has_link("alice", "staff", &[]));
+        assert!(!rm.has_link("bob", "contractor", &[]));
+        assert!(!rm.has_link("carol", "staff", &[]));
+        assert!(!rm.has_link("dave", "staff", &[]));
+
+        assert_eq!(vec!["contractor"], rm.get_roles("alice", None));
+        assert!(rm.get_roles("bob", None).is_empty());
+        assert_eq!(vec!["alice"], rm.get_users("contractor", None));
+    }
+
+    #[test]
+    fn test_named_condition() {
+        let mut rm = ConditionalRoleManager::new(10);
+        rm.add_link_condition_fn("enabled", |params| params == ["on"])
+            .unwrap();
+        rm.add_conditional_link("u1", "g1", &["domain1"], &["enabled", "on"])
+            .unwrap();
+        rm.add_conditional_link("u2", "g1", &["domain1"], &["enabled", "off"])
+            .unwrap();
+
+        assert!(rm.has_link("u1", "g1", &["domain1"]));
+        assert!(!rm.has_link("u1", "g1", &["domain2"]));
+        assert!(!rm.has_link("u2", "g1", &["domain1"]));
+        assert_eq!(vec!["domain1"], rm.get_all_domains());
+
+        rm.add_link("u2", "g1", &["domain1"]);
+        assert_eq!(
+            vec!["u1", "u2"],
+            sort_unstable(rm.get_users("g1", Some("domain1")))
+        );
+
+        rm.delete_link("u1", "g1", &["domain1"]).unwrap();
+        assert!(!rm.has_link("u1", "g1", &["domain1"]));
+        assert!(rm.delete_link("u1", "g1", &["domain1"]).is_err());
+    }
+
+    #[test]
+    fn test_rules_of_one_link() {
+        let mut rm = ConditionalRoleManager::new(10);
+        rm.add_conditional_link("alice", "staff", &[], &["_", "2000-01-01"])
+            .unwrap();
+        rm.add_conditional_link("alice", "staff", &[], &["2000-01-01", "_"])
+            .unwrap();
+        assert!(rm.has_link("alice", "staff", &[]));
+
+        rm.delete_conditional_link("alice", "staff", &[], &["2000-01-01", "_"])
+            .unwrap();
+        assert!(!rm.has_link("alice", "staff", &[]));
+        assert!(rm
+            .delete_conditional_link("alice", "staff", &[], &["_", "_"])
+            .is_err());
+
+        rm.delete_conditional_link("alice", "staff", &[], &["_", "2000-01-01"])
+            .unwrap();
+        assert!(rm.delete_link("alice", "staff", &[]).is_err());
+    }
+}
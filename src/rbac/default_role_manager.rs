@@ -3,7 +3,10 @@ use crate::{
     rbac::{MatchingFn, RoleManager},
     Result,
 };
-use petgraph::stable_graph::{NodeIndex, StableDiGraph};
+use petgraph::{
+    stable_graph::{NodeIndex, StableDiGraph},
+    visit::EdgeRef,
+};
 use std::collections::{hash_map::Entry, HashMap, HashSet};
 
 #[cfg(feature = "cached")]
@@ -15,11 +18,11 @@ use std::{
     hash::{Hash, Hasher},
 };
 
-const DEFAULT_DOMAIN: &str = "DEFAULT";
+pub(super) const DEFAULT_DOMAIN: &str = "DEFAULT";
 
 pub struct DefaultRoleManager {
-    all_domains: HashMap<String, StableDiGraph<String, EdgeVariant>>,
-    all_domains_indices: HashMap<String, HashMap<String, NodeIndex<u32>>>,
+    all_domains: HashMap<Vec<String>, StableDiGraph<String, EdgeVariant>>,
+    all_domains_indices: HashMap<Vec<String>, HashMap<String, NodeIndex<u32>>>,
     #[cfg(feature = "cached")]
     cache: DefaultCache<u64, bool>,
     max_hierarchy_level: usize,
@@ -49,18 +52,16 @@ impl DefaultRoleManager {
     fn get_or_create_role(
         &mut self,
         name: &str,
-        domain: Option<&str>,
+        domain: &[String],
     ) -> NodeIndex<u32> {
-        let domain = domain.unwrap_or(DEFAULT_DOMAIN);
-
         // detect whether this is a new domain creation
         let is_new_domain = !self.all_domains.contains_key(domain);
 
-        let graph = self.all_domains.entry(domain.into()).or_default();
+        let graph = self.all_domains.entry(domain.to_vec()).or_default();
 
         let role_entry = self
             .all_domains_indices
-            .entry(domain.into())
+            .entry(domain.to_vec())
             .or_default()
             .entry(name.into());
 
@@ -105,10 +106,12 @@ impl DefaultRoleManager {
         // that BFS will see inherited links in this domain's graph.
         if is_new_domain {
             if let Some(domain_matching_fn) = self.domain_matching_fn {
-                let keys: Vec<String> =
+                let keys: Vec<Vec<String>> =
                     self.all_domains.keys().cloned().collect();
                 for d in keys {
-                    if d != domain && (domain_matching_fn)(domain, &d) {
+                    if d != domain
+                        && domain_matches(domain_matching_fn, domain, &d)
+                    {
                         self.copy_from_domain(&d, domain);
                     }
                 }
@@ -125,7 +128,7 @@ impl DefaultRoleManager {
         &mut self,
         name1: &str,
         name2: &str,
-        domain: &str,
+        domain: &[String],
     ) {
         let name1_owned = name1.to_string();
         let name2_owned = name2.to_string();
@@ -172,11 +175,13 @@ impl DefaultRoleManager {
 
     // return the list of affected domain names (immutable) to avoid nested
     // mutable borrows when performing operations across domains
-    fn affected_domain_names(&self, domain: &str) -> Vec<String> {
+    fn affected_domain_names(&self, domain: &[String]) -> Vec<Vec<String>> {
         if let Some(matcher) = self.domain_matching_fn {
             self.all_domains
                 .keys()
-                .filter(|d| *d != domain && matcher(d, domain))
+                .filter(|d| {
+                    d.as_slice() != domain && domain_matches(matcher, d, domain)
+                })
                 .cloned()
                 .collect()
         } else {
@@ -185,7 +190,11 @@ impl DefaultRoleManager {
     }
 
     // copy all role links and nodes from `src_domain` graph into `dst_domain` graph
-    fn copy_from_domain(&mut self, src_domain: &str, dst_domain: &str) {
+    fn copy_from_domain(
+        &mut self,
+        src_domain: &[String],
+        dst_domain: &[String],
+    ) {
         if src_domain == dst_domain {
             return;
         }
@@ -203,10 +212,11 @@ impl DefaultRoleManager {
         // ensure dst indices map exists
         let dst_indices = self
             .all_domains_indices
-            .entry(dst_domain.into())
+            .entry(dst_domain.to_vec())
             .or_default();
 
-        let dst_graph = self.all_domains.entry(dst_domain.into()).or_default();
+        let dst_graph =
+            self.all_domains.entry(dst_domain.to_vec()).or_default();
 
         // copy nodes: ensure names exist in dst and capture mapping
         let mut id_map: HashMap<NodeIndex<u32>, NodeIndex<u32>> =
@@ -250,27 +260,26 @@ impl DefaultRoleManager {
         self.cache.clear();
     }
 
-    fn matched_domains(&self, domain: Option<&str>) -> Vec<String> {
-        let domain = domain.unwrap_or(DEFAULT_DOMAIN);
+    fn matched_domains(&self, domain: &[String]) -> Vec<Vec<String>> {
         if let Some(domain_matching_fn) = self.domain_matching_fn {
             self.all_domains
                 .keys()
                 .filter_map(|key| {
-                    if domain_matching_fn(domain, key) {
+                    if domain_matches(domain_matching_fn, domain, key) {
                         Some(key.to_owned())
                     } else {
                         None
                     }
                 })
-                .collect::<Vec<String>>()
+                .collect()
         } else {
             self.all_domains
                 .get(domain)
-                .map_or(vec![], |_| vec![domain.to_owned()])
+                .map_or(vec![], |_| vec![domain.to_vec()])
         }
     }
 
-    fn domain_has_role(&self, name: &str, domain: Option<&str>) -> bool {
+    fn domain_has_role(&self, name: &str, domain: &[String]) -> bool {
         let matched_domains = self.matched_domains(domain);
 
         matched_domains.iter().any(|domain| {
@@ -291,6 +300,46 @@ impl DefaultRoleManager {
     }
 }
 
+/// the key of the graph of one or more domains,
+/// an empty slice maps to the default domain
+pub(super) fn domain_key(domain: &[&str]) -> Vec<String> {
+    match domain {
+        [] => vec![DEFAULT_DOMAIN.to_owned()],
+        domains => domains.iter().map(|d| (*d).to_owned()).collect(),
+    }
+}
+
+/// the key of the graph of a single domain, or of the default one
+pub(super) fn single_key(domain: Option<&str>) -> Vec<String> {
+    vec![domain.unwrap_or(DEFAULT_DOMAIN).to_owned()]
+}
+
+/// applies the domain matching function to each domain of a key
+pub(super) fn domain_matches(
+    domain_matching_fn: MatchingFn,
+    domain: &[String],
+    pattern: &[String],
+) -> bool {
+    domain.len() == pattern.len()
+        && domain
+            .iter()
+            .zip(pattern.iter())
+            .all(|(d, p)| domain_matching_fn(d, p))
+}
+
+/// the domains of a key other than the default one, each listed once
+pub(super) fn key_domains<'a, I>(keys: I) -> Vec<String>
+where
+    I: Iterator<Item = &'a Vec<String>>,
+{
+    let mut seen = HashSet::new();
+    keys.filter(|key| key.as_slice() != [DEFAULT_DOMAIN])
+        .flatten()
+        .filter(|domain| seen.insert(domain.as_str()))
+        .cloned()
+        .collect()
+}
+
 /// link node of `not_pattern_id` to `maybe_pattern_id` if
 /// `not_pattern` matches `maybe_pattern`'s pattern and
 /// there doesn't exist a match edge yet
@@ -331,18 +380,18 @@ impl RoleManager for DefaultRoleManager {
         self.cache.clear();
     }
 
-    fn add_link(&mut self, name1: &str, name2: &str, domain: Option<&str>) {
+    fn add_link(&mut self, name1: &str, name2: &str, domain: &[&str]) {
         if name1 == name2 {
             return;
         }
 
-        let role1 = self.get_or_create_role(name1, domain);
-        let role2 = self.get_or_create_role(name2, domain);
+        let has_domain = !domain.is_empty();
+        let domain = domain_key(domain);
+
+        let role1 = self.get_or_create_role(name1, &domain);
+        let role2 = self.get_or_create_role(name2, &domain);
 
-        let graph = self
-            .all_domains
-            .get_mut(domain.unwrap_or(DEFAULT_DOMAIN))
-            .unwrap();
+        let graph = self.all_domains.get_mut(&domain).unwrap();
 
         let add_link = if let Some(edge) = graph.find_edge(role1, role2) {
             !matches!(graph[edge], EdgeVariant::Link)
@@ -353,10 +402,8 @@ impl RoleManager for DefaultRoleManager {
         if add_link {
             graph.add_edge(role1, role2, EdgeVariant::Link);
 
-            if let Some(domain_str) = domain {
-                self.propagate_link_to_affected_domains(
-                    name1, name2, domain_str,
-                );
+            if has_domain {
+                self.propagate_link_to_affected_domains(name1, name2, &domain);
             }
 
             #[cfg(feature = "cached")]
@@ -377,23 +424,22 @@ impl RoleManager for DefaultRoleManager {
         &mut self,
         name1: &str,
         name2: &str,
-        domain: Option<&str>,
+        domain: &[&str],
     ) -> Result<()> {
-        if !self.domain_has_role(name1, domain)
-            || !self.domain_has_role(name2, domain)
+        let domain = domain_key(domain);
+
+        if !self.domain_has_role(name1, &domain)
+            || !self.domain_has_role(name2, &domain)
         {
             return Err(
                 RbacError::NotFound(format!("{} OR {}", name1, name2)).into()
             );
         }
 
-        let role1 = self.get_or_create_role(name1, domain);
-        let role2 = self.get_or_create_role(name2, domain);
+        let role1 = self.get_or_create_role(name1, &domain);
+        let role2 = self.get_or_create_role(name2, &domain);
 
-        let graph = self
-            .all_domains
-            .get_mut(domain.unwrap_or(DEFAULT_DOMAIN))
-            .unwrap();
+        let graph = self.all_domains.get_mut(&domain).unwrap();
 
         if let Some(edge_index) = graph.find_edge(role1, role2) {
             graph.remove_edge(edge_index).unwrap();
@@ -405,17 +451,19 @@ impl RoleManager for DefaultRoleManager {
         Ok(())
     }
 
-    fn has_link(&self, name1: &str, name2: &str, domain: Option<&str>) -> bool {
+    fn has_link(&self, name1: &str, name2: &str, domain: &[&str]) -> bool {
         if name1 == name2 {
             return true;
         }
 
+        let domain = domain_key(domain);
+
         #[cfg(feature = "cached")]
         let cache_key = {
             let mut hasher = DefaultHasher::new();
             name1.hash(&mut hasher);
             name2.hash(&mut hasher);
-            domain.unwrap_or(DEFAULT_DOMAIN).hash(&mut hasher);
+            domain.hash(&mut hasher);
             hasher.finish()
         };
 
@@ -424,7 +472,7 @@ impl RoleManager for DefaultRoleManager {
             return res;
         }
 
-        let matched_domains = self.matched_domains(domain);
+        let matched_domains = self.matched_domains(&domain);
 
         let mut res = false;
 
@@ -481,7 +529,7 @@ impl RoleManager for DefaultRoleManager {
     }
 
     fn get_roles(&self, name: &str, domain: Option<&str>) -> Vec<String> {
-        let matched_domains = self.matched_domains(domain);
+        let matched_domains = self.matched_domains(&single_key(domain));
 
         let res = matched_domains.into_iter().fold(
             HashSet::new(),
@@ -511,7 +559,7 @@ impl RoleManager for DefaultRoleManager {
     }
 
     fn get_users(&self, name: &str, domain: Option<&str>) -> Vec<String> {
-        let matched_domains = self.matched_domains(domain);
+        let matched_domains = self.matched_domains(&single_key(domain));
 
         let res = matched_domains.into_iter().fold(
             HashSet::new(),
@@ -541,6 +589,62 @@ impl RoleManager for DefaultRoleManager {
 
         res.into_iter().collect()
     }
+
+    fn get_depth(&self, name: &str, domain: Option<&str>) -> usize {
+        self.matched_domains(&single_key(domain))
+            .iter()
+            .filter_map(|domain| {
+                let graph = &self.all_domains[domain];
+                let mut frontier =
+                    vec![*self.all_domains_indices[domain].get(name)?];
+                let mut depth = 0;
+
+                while depth < self.max_hierarchy_level {
+                    let mut parents: Vec<NodeIndex<u32>> = frontier
+                        .iter()
+                        .flat_map(|&node| {
+                            graph
+                                .edges_directed(
+                                    node,
+                                    petgraph::Direction::Outgoing,
+                                )
+                                .filter(|edge| {
+                                    matches!(edge.weight(), EdgeVariant::Link)
+                                })
+                                .map(|edge| edge.target())
+                        })
+                        .collect();
+                    if parents.is_empty() {
+                        break;
+                    }
+                    parents.sort_unstable();
+                    parents.dedup();
+
+                    frontier = parents;
+                    depth += 1;
+                }
+
+                Some(depth)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn get_all_domains(&self) -> Vec<String> {
+        key_domains(self.all_domains.keys())
+    }
+
+    fn delete_domain(&mut self, domain: &str) -> Result<()> {
+        self.all_domains
+            .retain(|key, _| !key.iter().any(|d| d == domain));
+        self.all_domains_indices
+            .retain(|key, _| !key.iter().any(|d| d == domain));
+
+        #[cfg(feature = "cached")]
+        self.cache.clear();
+
+        Ok(())
+    }
 }
 
 mod matching_bfs {
@@ -726,6 +830,7 @@ mod matching_bfs {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::zero_repeat_side_effects)]
 mod tests {
     use super::*;
 
@@ -737,25 +842,25 @@ mod tests {
     #[test]
     fn test_role() {
         let mut rm = DefaultRoleManager::new(3);
-        rm.add_link("u1", "g1", None);
-        rm.add_link("u2", "g1", None);
-        rm.add_link("u3", "g2", None);
-        rm.add_link("u4", "g2", None);
-        rm.add_link("u4", "g3", None);
-        rm.add_link("g1", "g3", None);
-
-        assert_eq!(true, rm.has_link("u1", "g1", None));
-        assert_eq!(false, rm.has_link("u1", "g2", None));
-        assert_eq!(true, rm.has_link("u1", "g3", None));
-        assert_eq!(true, rm.has_link("u2", "g1", None));
-        assert_eq!(false, rm.has_link("u2", "g2", None));
-        assert_eq!(true, rm.has_link("u2", "g3", None));
-        assert_eq!(false, rm.has_link("u3", "g1", None));
-        assert_eq!(true, rm.has_link("u3", "g2", None));
-        assert_eq!(false, rm.has_link("u3", "g3", None));
-        assert_eq!(false, rm.has_link("u4", "g1", None));
-        assert_eq!(true, rm.has_link("u4", "g2", None));
-        assert_eq!(true, rm.has_link("u4", "g3", None));
+        rm.add_link("u1", "g1", &[]);
+        rm.add_link("u2", "g1", &[]);
+        rm.add_link("u3", "g2", &[]);
+        rm.add_link("u4", "g2", &[]);
+        rm.add_link("u4", "g3", &[]);
+        rm.add_link("g1", "g3", &[]);
+
+        assert_eq!(true, rm.has_link("u1", "g1", &[]));
+        assert_eq!(false, rm.has_link("u1", "g2", &[]));
+        assert_eq!(true, rm.has_link("u1", "g3", &[]));
+        assert_eq!(true, rm.has_link("u2", "g1", &[]));
+        assert_eq!(false, rm.has_link("u2", "g2", &[]));
+        assert_eq!(true, rm.has_link("u2", "g3", &[]));
+        assert_eq!(false, rm.has_link("u3", "g1", &[]));
+        assert_eq!(true, rm.has_link("u3", "g2", &[]));
+        assert_eq!(false, rm.has_link("u3", "g3", &[]));
+        assert_eq!(false, rm.has_link("u4", "g1", &[]));
+        assert_eq!(true, rm.has_link("u4", "g2", &[]));
+        assert_eq!(true, rm.has_link("u4", "g3", &[]));
 
         // test get_roles
         assert_eq!(vec!["g1"], rm.get_roles("u1", None));
@@ -767,20 +872,20 @@ mod tests {
         assert_eq!(vec![String::new(); 0], rm.get_roles("g3", None));
 
         // test delete_link
-        rm.delete_link("g1", "g3", None).unwrap();
-        rm.delete_link("u4", "g2", None).unwrap();
-        assert_eq!(true, rm.has_link("u1", "g1", None));
-        assert_eq!(false, rm.has_link("u1", "g2", None));
-        assert_eq!(false, rm.has_link("u1", "g3", None));
-        assert_eq!(true, rm.has_link("u2", "g1", None));
-        assert_eq!(false, rm.has_link("u2", "g2", None));
-        assert_eq!(false, rm.has_link("u2", "g3", None));
-        assert_eq!(false, rm.has_link("u3", "g1", None));
-        assert_eq!(true, rm.has_link("u3", "g2", None));
-        assert_eq!(false, rm.has_link("u3", "g3", None));
-        assert_eq!(false, rm.has_link("u4", "g1", None));
-        assert_eq!(false, rm.has_link("u4", "g2", None));
-        assert_eq!(true, rm.has_link("u4", "g3", None));
+        rm.delete_link("g1", "g3", &[]).unwrap();
+        rm.delete_link("u4", "g2", &[]).unwrap();
+        assert_eq!(true, rm.has_link("u1", "g1", &[]));
+        assert_eq!(false, rm.has_link("u1", "g2", &[]));
+        assert_eq!(false, rm.has_link("u1", "g3", &[]));
+        assert_eq!(true, rm.has_link("u2", "g1", &[]));
+        assert_eq!(false, rm.has_link("u2", "g2", &[]));
+        assert_eq!(false, rm.has_link("u2", "g3", &[]));
+        assert_eq!(false, rm.has_link("u3", "g1", &[]));
+        assert_eq!(true, rm.has_link("u3", "g2", &[]));
+        assert_eq!(false, rm.has_link("u3", "g3", &[]));
+        assert_eq!(false, rm.has_link("u4", "g1", &[]));
+        assert_eq!(false, rm.has_link("u4", "g2", &[]));
+        assert_eq!(true, rm.has_link("u4", "g3", &[]));
         assert_eq!(vec!["g1"], rm.get_roles("u1", None));
         assert_eq!(vec!["g1"], rm.get_roles("u2", None));
         assert_eq!(vec!["g2"], rm.get_roles("u3", None));
@@ -793,93 +898,93 @@ mod tests {
     #[test]
     fn test_clear() {
         let mut rm = DefaultRoleManager::new(3);
-        rm.add_link("u1", "g1", None);
-        rm.add_link("u2", "g1", None);
-        rm.add_link("u3", "g2", None);
-        rm.add_link("u4", "g2", None);
-        rm.add_link("u4", "g3", None);
-        rm.add_link("g1", "g3", None);
+        rm.add_link("u1", "g1", &[]);
+        rm.add_link("u2", "g1", &[]);
+        rm.add_link("u3", "g2", &[]);
+        rm.add_link("u4", "g2", &[]);
+        rm.add_link("u4", "g3", &[]);
+        rm.add_link("g1", "g3", &[]);
 
         rm.clear();
-        assert_eq!(false, rm.has_link("u1", "g1", None));
-        assert_eq!(false, rm.has_link("u1", "g2", None));
-        assert_eq!(false, rm.has_link("u1", "g3", None));
-        assert_eq!(false, rm.has_link("u2", "g1", None));
-        assert_eq!(false, rm.has_link("u2", "g2", None));
-        assert_eq!(false, rm.has_link("u2", "g3", None));
-        assert_eq!(false, rm.has_link("u3", "g1", None));
-        assert_eq!(false, rm.has_link("u3", "g2", None));
-        assert_eq!(false, rm.has_link("u3", "g3", None));
-        assert_eq!(false, rm.has_link("u4", "g1", None));
-        assert_eq!(false, rm.has_link("u4", "g2", None));
-        assert_eq!(false, rm.has_link("u4", "g3", None));
+        assert_eq!(false, rm.has_link("u1", "g1", &[]));
+        assert_eq!(false, rm.has_link("u1", "g2", &[]));
+        assert_eq!(false, rm.has_link("u1", "g3", &[]));
+        assert_eq!(false, rm.has_link("u2", "g1", &[]));
+        assert_eq!(false, rm.has_link("u2", "g2", &[]));
+        assert_eq!(false, rm.has_link("u2", "g3", &[]));
+        assert_eq!(false, rm.has_link("u3", "g1", &[]));
+        assert_eq!(false, rm.has_link("u3", "g2", &[]));
+        assert_eq!(false, rm.has_link("u3", "g3", &[]));
+        assert_eq!(false, rm.has_link("u4", "g1", &[]));
+        assert_eq!(false, rm.has_link("u4", "g2", &[]));
+        assert_eq!(false, rm.has_link("u4", "g3", &[]));
     }
 
     #[test]
     fn test_domain_role() {
         let mut rm = DefaultRoleManager::new(3);
-        rm.add_link("u1", "g1", Some("domain1"));
-        rm.add_link("u2", "g1", Some("domain1"));
-        rm.add_link("u3", "admin", Some("domain2"));
-        rm.add_link("u4", "admin", Some("domain2"));
-        rm.add_link("u4", "admin", Some("domain1"));
-        rm.add_link("g1", "admin", Some("domain1"));
-
-        assert_eq!(true, rm.has_link("u1", "g1", Some("domain1")));
-        assert_eq!(false, rm.has_link("u1", "g1", Some("domain2")));
-        assert_eq!(true, rm.has_link("u1", "admin", Some("domain1")));
-        assert_eq!(false, rm.has_link("u1", "admin", Some("domain2")));
-
-        assert_eq!(true, rm.has_link("u2", "g1", Some("domain1")));
-        assert_eq!(false, rm.has_link("u2", "g1", Some("domain2")));
-        assert_eq!(true, rm.has_link("u2", "admin", Some("domain1")));
-        assert_eq!(false, rm.has_link("u2", "admin", Some("domain2")));
-
-        assert_eq!(false, rm.has_link("u3", "g1", Some("domain1")));
-        assert_eq!(false, rm.has_link("u3", "g1", Some("domain2")));
-        assert_eq!(false, rm.has_link("u3", "admin", Some("domain1")));
-        assert_eq!(true, rm.has_link("u3", "admin", Some("domain2")));
-
-        assert_eq!(false, rm.has_link("u4", "g1", Some("domain1")));
-        assert_eq!(false, rm.has_link("u4", "g1", Some("domain2")));
-        assert_eq!(true, rm.has_link("u4", "admin", Some("domain1")));
-        assert_eq!(true, rm.has_link("u4", "admin", Some("domain2")));
-
-        rm.delete_link("g1", "admin", Some("domain1")).unwrap();
-
-        rm.delete_link("u4", "admin", Some("domain2")).unwrap();
-
-        assert_eq!(true, rm.has_link("u1", "g1", Some("domain1")));
-        assert_eq!(false, rm.has_link("u1", "g1", Some("domain2")));
-        assert_eq!(false, rm.has_link("u1", "admin", Some("domain1")));
-        assert_eq!(false, rm.has_link("u1", "admin", Some("domain2")));
-
-        assert_eq!(true, rm.has_link("u2", "g1", Some("domain1")));
-        assert_eq!(false, rm.has_link("u2", "g1", Some("domain2")));
-        assert_eq!(false, rm.has_link("u2", "admin", Some("domain1")));
-        assert_eq!(false, rm.has_link("u2", "admin", Some("domain2")));
-
-        assert_eq!(false, rm.has_link("u3", "g1", Some("domain1")));
-        assert_eq!(false, rm.has_link("u3", "g1", Some("domain2")));
-        assert_eq!(false, rm.has_link("u3", "admin", Some("domain1")));
-        assert_eq!(true, rm.has_link("u3", "admin", Some("domain2")));
-
-        assert_eq!(false, rm.has_link("u4", "g1", Some("domain1")));
-        assert_eq!(false, rm.has_link("u4", "g1", Some("domain2")));
-        assert_eq!(true, rm.has_link("u4", "admin", Some("domain1")));
-        assert_eq!(false, rm.has_link("u4", "admin", Some("domain2")));
+        rm.add_link("u1", "g1", &["domain1"]);
+        rm.add_link("u2", "g1", &["domain1"]);
+        rm.add_link("u3", "admin", &["domain2"]);
+        rm.add_link("u4", "admin", &["domain2"]);
+        rm.add_link("u4", "admin", &["domain1"]);
+        rm.add_link("g1", "admin", &["domain1"]);
+
+        assert_eq!(true, rm.has_link("u1", "g1", &["domain1"]));
+        assert_eq!(false, rm.has_link("u1", "g1", &["domain2"]));
+        assert_eq!(true, rm.has_link("u1", "admin", &["domain1"]));
+        assert_eq!(false, rm.has_link("u1", "admin", &["domain2"]));
+
+        assert_eq!(true, rm.has_link("u2", "g1", &["domain1"]));
+        assert_eq!(false, rm.has_link("u2", "g1", &["domain2"]));
+        assert_eq!(true, rm.has_link("u2", "admin", &["domain1"]));
+        assert_eq!(false, rm.has_link("u2", "admin", &["domain2"]));
+
+        assert_eq!(false, rm.has_link("u3", "g1", &["domain1"]));
+        assert_eq!(false, rm.has_link("u3", "g1", &["domain2"]));
+        assert_eq!(false, rm.has_link("u3", "admin", &["domain1"]));
+        assert_eq!(true, rm.has_link("u3", "admin", &["domain2"]));
+
+        assert_eq!(false, rm.has_link("u4", "g1", &["domain1"]));
+        assert_eq!(false, rm.has_link("u4", "g1", &["domain2"]));
+        assert_eq!(true, rm.has_link("u4", "admin", &["domain1"]));
+        assert_eq!(true, rm.has_link("u4", "admin", &["domain2"]));
+
+        rm.delete_link("g1", "admin", &["domain1"]).unwrap();
+
+        rm.delete_link("u4", "admin", &["domain2"]).unwrap();
+
+        assert_eq!(true, rm.has_link("u1", "g1", &["domain1"]));
+        assert_eq!(false, rm.has_link("u1", "g1", &["domain2"]));
+        assert_eq!(false, rm.has_link("u1", "admin", &["domain1"]));
+        assert_eq!(false, rm.has_link("u1", "admin", &["domain2"]));
+
+        assert_eq!(true, rm.has_link("u2", "g1", &["domain1"]));
+        assert_eq!(false, rm.has_link("u2", "g1", &["domain2"]));
+        assert_eq!(false, rm.has_link("u2", "admin", &["domain1"]));
+        assert_eq!(false, rm.has_link("u2", "admin", &["domain2"]));
+
+        assert_eq!(false, rm.has_link("u3", "g1", &["domain1"]));
+        assert_eq!(false, rm.has_link("u3", "g1", &["domain2"]));
+        assert_eq!(false, rm.has_link("u3", "admin", &["domain1"]));
+        assert_eq!(true, rm.has_link("u3", "admin", &["domain2"]));
+
+        assert_eq!(false, rm.has_link("u4", "g1", &["domain1"]));
+        assert_eq!(false, rm.has_link("u4", "g1", &["domain2"]));
+        assert_eq!(true, rm.has_link("u4", "admin", &["domain1"]));
+        assert_eq!(false, rm.has_link("u4", "admin", &["domain2"]));
     }
 
     #[test]
     fn test_users() {
         let mut rm = DefaultRoleManager::new(3);
-        rm.add_link("u1", "g1", Some("domain1"));
-        rm.add_link("u2", "g1", Some("domain1"));
+        rm.add_link("u1", "g1", &["domain1"]);
+        rm.add_link("u2", "g1", &["domain1"]);
 
-        rm.add_link("u3", "g2", Some("domain2"));
-        rm.add_link("u4", "g2", Some("domain2"));
+        rm.add_link("u3", "g2", &["domain2"]);
+        rm.add_link("u4", "g2", &["domain2"]);
 
-        rm.add_link("u5", "g3", None);
+        rm.add_link("u5", "g3", &[]);
 
         assert_eq!(
             vec!["u1", "u2"],
@@ -892,14 +997,89 @@ mod tests {
         assert_eq!(vec!["u5"], rm.get_users("g3", None));
     }
 
+    #[test]
+    fn test_all_domains() {
+        let mut rm = DefaultRoleManager::new(3);
+        rm.add_link("u1", "g1", &["domain1"]);
+        rm.add_link("u2", "g2", &["domain2"]);
+        rm.add_link("u3", "g3", &[]);
+
+        assert_eq!(
+            vec!["domain1", "domain2"],
+            sort_unstable(rm.get_all_domains())
+        );
+
+        rm.delete_domain("domain1").unwrap();
+        assert_eq!(vec!["domain2"], rm.get_all_domains());
+        assert_eq!(false, rm.has_link("u1", "g1", &["domain1"]));
+        assert_eq!(true, rm.has_link("u2", "g2", &["domain2"]));
+        assert_eq!(true, rm.has_link("u3", "g3", &[]));
+    }
+
+    #[test]
+    fn test_multiple_domains() {
+        use crate::model::key_match;
+        let mut rm = DefaultRoleManager::new(3);
+        rm.add_link("u1", "g1", &["tenant1", "eu"]);
+        rm.add_link("u2", "g1", &["tenant1", "us"]);
+
+        assert_eq!(true, rm.has_link("u1", "g1", &["tenant1", "eu"]));
+        assert_eq!(false, rm.has_link("u1", "g1", &["tenant1", "us"]));
+        assert_eq!(false, rm.has_link("u1", "g1", &["tenant1"]));
+
+        rm.matching_fn(None, Some(key_match));
+        rm.add_link("u3", "g2", &["*", "eu"]);
+        assert_eq!(true, rm.has_link("u3", "g2", &["tenant2", "eu"]));
+        assert_eq!(false, rm.has_link("u3", "g2", &["tenant2", "us"]));
+
+        rm.delete_link("u1", "g1", &["tenant1", "eu"]).unwrap();
+        assert_eq!(false, rm.has_link("u1", "g1", &["tenant1", "eu"]));
+    }
+
+    #[test]
+    fn test_domain_with_separator() {
+        use crate::model::key_match;
+        let mut rm = DefaultRoleManager::new(3);
+        rm.matching_fn(None, Some(key_match));
+        rm.add_link("u1", "g1", &["org::team"]);
+        rm.add_link("u2", "g2", &["org", "team"]);
+
+        assert!(rm.has_link("u1", "g1", &["org::team"]));
+        assert!(!rm.has_link("u1", "g1", &["org", "team"]));
+        assert!(rm.has_link("u2", "g2", &["org", "team"]));
+        assert!(!rm.has_link("u2", "g2", &["org::team"]));
+        assert_eq!(vec!["g1"], rm.get_roles("u1", Some("org::team")));
+        assert_eq!(
+            vec!["org", "org::team", "team"],
+            sort_unstable(rm.get_all_domains())
+        );
+    }
+
+    #[test]
+    fn test_depth() {
+        let mut rm = DefaultRoleManager::new(10);
+        rm.add_link("admin", "root", &[]);
+        rm.add_link("editor", "admin", &[]);
+        rm.add_link("jane", "editor", &[]);
+        rm.add_link("jane", "root", &[]);
+        rm.add_link("u1", "g1", &["domain1"]);
+
+        assert_eq!(0, rm.get_depth("root", None));
+        assert_eq!(1, rm.get_depth("admin", None));
+        assert_eq!(3, rm.get_depth("jane", None));
+        assert_eq!(0, rm.get_depth("bob", None));
+        assert_eq!(1, rm.get_depth("u1", Some("domain1")));
+        assert_eq!(0, rm.get_depth("u1", Some("domain2")));
+    }
+
     #[test]
     fn test_pattern_domain() {
         use crate::model::key_match;
         let mut rm = DefaultRoleManager::new(3);
         rm.matching_fn(None, Some(key_match));
-        rm.add_link("u1", "g1", Some("*"));
+        rm.add_link("u1", "g1", &["*"]);
 
-        assert!(rm.domain_has_role("u1", Some("domain2")));
+        assert!(rm.domain_has_role("u1", &single_key(Some("domain2"))));
     }
 
     #[test]
@@ -907,14 +1087,14 @@ mod tests {
         use crate::model::key_match;
         let mut rm = DefaultRoleManager::new(10);
         rm.matching_fn(Some(key_match), None);
-        rm.add_link("bob", "book_group", None);
-        rm.add_link("*", "book_group", None);
-        rm.add_link("*", "pen_group", None);
-        rm.add_link("eve", "pen_group", None);
+        rm.add_link("bob", "book_group", &[]);
+        rm.add_link("*", "book_group", &[]);
+        rm.add_link("*", "pen_group", &[]);
+        rm.add_link("eve", "pen_group", &[]);
 
-        assert!(rm.has_link("alice", "book_group", None));
-        assert!(rm.has_link("eve", "book_group", None));
-        assert!(rm.has_link("bob", "book_group", None));
+        assert!(rm.has_link("alice", "book_group", &[]));
+        assert!(rm.has_link("eve", "book_group", &[]));
+        assert!(rm.has_link("bob", "book_group", &[]));
 
         assert_eq!(
             vec!["book_group", "pen_group"],
@@ -927,14 +1107,14 @@ mod tests {
         use crate::model::key_match;
         let mut rm = DefaultRoleManager::new(10);
         rm.matching_fn(Some(key_match), None);
-        rm.add_link("alice", "book_group", None);
-        rm.add_link("alice", "*", None);
-        rm.add_link("bob", "pen_group", None);
+        rm.add_link("alice", "book_group", &[]);
+        rm.add_link("alice", "*", &[]);
+        rm.add_link("bob", "pen_group", &[]);
 
-        assert!(rm.has_link("alice", "book_group", None));
-        assert!(rm.has_link("alice", "pen_group", None));
-        assert!(rm.has_link("bob", "pen_group", None));
-        assert!(!rm.has_link("bob", "book_group", None));
+        assert!(rm.has_link("alice", "book_group", &[]));
+        assert!(rm.has_link("alice", "pen_group", &[]));
+        assert!(rm.has_link("bob", "pen_group", &[]));
+        assert!(!rm.has_link("bob", "book_group", &[]));
 
         assert_eq!(
             vec!["*", "alice", "bob", "book_group", "pen_group"],
@@ -950,21 +1130,21 @@ mod tests {
         let mut rm = DefaultRoleManager::new(10);
         rm.matching_fn(None, Some(key_match));
 
-        rm.add_link("editor", "admin", Some("*"));
-        rm.add_link("viewer", "editor", Some("*"));
+        rm.add_link("editor", "admin", &["*"]);
+        rm.add_link("viewer", "editor", &["*"]);
 
-        rm.add_link("alice", "editor", Some("domain1"));
-        rm.add_link("bob", "viewer", Some("domain2"));
+        rm.add_link("alice", "editor", &["domain1"]);
+        rm.add_link("bob", "viewer", &["domain2"]);
 
-        assert!(rm.has_link("alice", "admin", Some("domain1")));
-        assert!(rm.has_link("bob", "editor", Some("domain2")));
-        assert!(rm.has_link("bob", "admin", Some("domain2")));
+        assert!(rm.has_link("alice", "admin", &["domain1"]));
+        assert!(rm.has_link("bob", "editor", &["domain2"]));
+        assert!(rm.has_link("bob", "admin", &["domain2"]));
 
-        rm.add_link("charlie", "viewer", Some("domain3"));
-        assert!(rm.has_link("charlie", "editor", Some("domain3")));
-        assert!(rm.has_link("charlie", "admin", Some("domain3")));
+        rm.add_link("charlie", "viewer", &["domain3"]);
+        assert!(rm.has_link("charlie", "editor", &["domain3"]));
+        assert!(rm.has_link("charlie", "admin", &["domain3"]));
 
-        rm.add_link("super_admin", "admin", Some("domain1"));
-        assert!(rm.has_link("super_admin", "admin", Some("domain1")));
+        rm.add_link("super_admin", "admin", &["domain1"]);
+        assert!(rm.has_link("super_admin", "admin", &["domain1"]));
     }
 }
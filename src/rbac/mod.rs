@@ -1,5 +1,7 @@
+mod conditional_role_manager;
 mod default_role_manager;
 mod role_manager;
 
+pub use conditional_role_manager::ConditionalRoleManager;
 pub use default_role_manager::DefaultRoleManager;
-pub use role_manager::{MatchingFn, RoleManager};
+pub use role_manager::{LinkConditionFn, MatchingFn, RoleManager};
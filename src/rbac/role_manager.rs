@@ -1,10 +1,11 @@
-use crate::Result;
+use crate::{error::RbacError, Result};
 
 pub type MatchingFn = fn(&str, &str) -> bool;
+pub type LinkConditionFn = fn(&[&str]) -> bool;
 
 pub trait RoleManager: Send + Sync {
     fn clear(&mut self);
-    fn add_link(&mut self, name1: &str, name2: &str, domain: Option<&str>);
+    fn add_link(&mut self, name1: &str, name2: &str, domain: &[&str]);
     fn matching_fn(
         &mut self,
         role_matching_fn: Option<MatchingFn>,
@@ -14,9 +15,56 @@ pub trait RoleManager: Send + Sync {
         &mut self,
         name1: &str,
         name2: &str,
-        domain: Option<&str>,
+        domain: &[&str],
     ) -> Result<()>;
-    fn has_link(&self, name1: &str, name2: &str, domain: Option<&str>) -> bool;
+    fn has_link(&self, name1: &str, name2: &str, domain: &[&str]) -> bool;
     fn get_roles(&self, name: &str, domain: Option<&str>) -> Vec<String>;
     fn get_users(&self, name: &str, domain: Option<&str>) -> Vec<String>;
+    /// Length of the longest chain of roles above `name`.
+    fn get_depth(&self, _name: &str, _domain: Option<&str>) -> usize {
+        0
+    }
+    fn get_all_domains(&self) -> Vec<String> {
+        vec![]
+    }
+    fn delete_domain(&mut self, _domain: &str) -> Result<()> {
+        Ok(())
+    }
+    /// Adds a link that is only followed while the condition described by
+    /// `params` holds. Role managers without conditions accept empty ones.
+    fn add_conditional_link(
+        &mut self,
+        name1: &str,
+        name2: &str,
+        domain: &[&str],
+        params: &[&str],
+    ) -> Result<()> {
+        if !params.iter().all(|x| x.is_empty()) {
+            return Err(RbacError::ConditionNotSupported.into());
+        }
+        self.add_link(name1, name2, domain);
+        Ok(())
+    }
+    /// Removes the conditional link added with the same `params`, leaving
+    /// the link in place if other rules still add it.
+    fn delete_conditional_link(
+        &mut self,
+        name1: &str,
+        name2: &str,
+        domain: &[&str],
+        params: &[&str],
+    ) -> Result<()> {
+        if !params.iter().all(|x| x.is_empty()) {
+            return Err(RbacError::ConditionNotSupported.into());
+        }
+        self.delete_link(name1, name2, domain)
+    }
+    /// Registers a condition that links can refer to by name.
+    fn add_link_condition_fn(
+        &mut self,
+        _name: &str,
+        _f: LinkConditionFn,
+    ) -> Result<()> {
+        Err(RbacError::ConditionNotSupported.into())
+    }
 }
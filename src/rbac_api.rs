@@ -1,4 +1,4 @@
-use crate::{MgmtApi, Result};
+use crate::{management_api::index_filter, MgmtApi, Result};
 
 use async_trait::async_trait;
 
@@ -105,6 +105,12 @@ pub trait RbacApi: MgmtApi {
         &self,
         permission: Vec<String>,
     ) -> Vec<String>;
+
+    /// Position of the domain field in `p`, falling back to the
+    /// `p = sub, dom, obj, act` layout when no field is named `dom`.
+    fn policy_domain_index(&self) -> usize {
+        self.get_field_index("p", "dom").unwrap_or(1)
+    }
 }
 
 #[async_trait]
@@ -291,13 +297,14 @@ where
         user: &str,
         domain: Option<&str>,
     ) -> Vec<Vec<String>> {
-        self.get_filtered_policy(0, {
-            if let Some(domain) = domain {
-                [user, domain].iter().map(|s| (*s).to_string()).collect()
-            } else {
-                [user].iter().map(|s| (*s).to_string()).collect()
-            }
-        })
+        let mut fields =
+            vec![(self.get_field_index("p", "sub").unwrap_or(0), user)];
+        if let Some(domain) = domain {
+            fields.push((self.policy_domain_index(), domain));
+        }
+
+        let (field_index, field_values) = index_filter(&fields);
+        self.get_filtered_policy(field_index, field_values)
     }
 
     fn has_permission_for_user(
@@ -377,9 +384,18 @@ where
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::await_holding_lock,
+    clippy::bool_assert_comparison,
+    clippy::useless_vec,
+    clippy::zero_repeat_side_effects
+)]
 mod tests {
     use crate::prelude::*;
 
+    #[cfg(not(target_arch = "wasm32"))]
+    use crate::adapter::file_adapter::TempPolicy;
+
     fn sort_unstable<T: Ord>(mut v: Vec<T>) -> Vec<T> {
         v.sort_unstable();
         v
@@ -399,7 +415,8 @@ mod tests {
             .await
             .unwrap();
 
-        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
 
         assert_eq!(vec!["data2_admin"], e.get_roles_for_user("alice", None));
@@ -523,7 +540,8 @@ mod tests {
             .await
             .unwrap();
 
-        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let adapter = policy.adapter();
         let e = Arc::new(RwLock::new(Enforcer::new(m, adapter).await.unwrap()));
         let ee = e.clone();
 
@@ -785,8 +803,9 @@ mod tests {
         .await
         .unwrap();
 
-        let adapter =
-            FileAdapter::new("examples/basic_without_resources_policy.csv");
+        let policy =
+            TempPolicy::copy("examples/basic_without_resources_policy.csv");
+        let adapter = policy.adapter();
         let mut e = Enforcer::new(m, adapter).await.unwrap();
 
         assert_eq!(true, e.enforce(("alice", "read")).unwrap());
@@ -1208,15 +1227,16 @@ mod tests {
     )]
     async fn test_implicit_users_for_permission() {
         let mut m = DefaultModel::default();
-        m.add_def("r", "r", "sub, obj, act");
-        m.add_def("p", "p", "sub, obj, act");
-        m.add_def("g", "g", "_, _");
-        m.add_def("e", "e", "some(where (p.eft == allow))");
+        m.add_def("r", "r", "sub, obj, act").unwrap();
+        m.add_def("p", "p", "sub, obj, act").unwrap();
+        m.add_def("g", "g", "_, _").unwrap();
+        m.add_def("e", "e", "some(where (p.eft == allow))").unwrap();
         m.add_def(
             "m",
             "m",
             "g(r.sub, p.sub) && r.obj == p.obj && regexMatch(r.act, p.act)",
-        );
+        )
+        .unwrap();
 
         let a = MemoryAdapter::default();
 
@@ -0,0 +1,231 @@
+use crate::{CoreApi, Enforcer, Result, TryIntoAdapter, TryIntoModel};
+
+#[cfg(feature = "runtime-async-std")]
+use async_std::{
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    task::{sleep, spawn},
+};
+
+#[cfg(feature = "runtime-tokio")]
+use tokio::{
+    spawn,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::sleep,
+};
+
+use parking_lot::Mutex;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// SyncedEnforcer shares an [`Enforcer`] between threads and tasks.
+///
+/// Its methods take `&self`, so it can be put in an `Arc` and used from
+/// anywhere. [`read`](SyncedEnforcer::read) and
+/// [`write`](SyncedEnforcer::write) lock the enforcer and give access to
+/// all of [`CoreApi`], [`MgmtApi`](crate::MgmtApi),
+/// [`RbacApi`](crate::RbacApi) and the APIs built on them, so code generic
+/// over those traits takes `&mut *e.write().await` as is. The locks are the
+/// ones of the runtime selected by the `runtime-*` feature, and can be held
+/// while the adapter saves a change.
+pub struct SyncedEnforcer {
+    enforcer: Arc<RwLock<Enforcer>>,
+    auto_load_stop: Mutex<Option<Arc<AtomicBool>>>,
+    // reloads completed by the auto load
+    reloads: Arc<AtomicUsize>,
+}
+
+impl SyncedEnforcer {
+    pub async fn new<M: TryIntoModel, A: TryIntoAdapter>(
+        m: M,
+        a: A,
+    ) -> Result<Self> {
+        Ok(Enforcer::new(m, a).await?.into())
+    }
+
+    /// Locks the enforcer for enforcing and reading the policy.
+    pub async fn read(&self) -> RwLockReadGuard<'_, Enforcer> {
+        self.enforcer.read().await
+    }
+
+    /// Locks the enforcer for changing the policy or the settings.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, Enforcer> {
+        self.enforcer.write().await
+    }
+
+    /// Reloads the policy from the adapter every `interval` in a background
+    /// task, replacing any auto load started before.
+    ///
+    /// Must be called from within the runtime selected by the `runtime-*`
+    /// feature. A failed reload is retried at the next tick.
+    pub fn start_auto_load_policy(&self, interval: Duration) {
+        let stop = Arc::new(AtomicBool::new(false));
+        if let Some(prev) = self.auto_load_stop.lock().replace(stop.clone()) {
+            prev.store(true, Ordering::SeqCst);
+        }
+
+        let enforcer = Arc::clone(&self.enforcer);
+        let reloads = Arc::clone(&self.reloads);
+        spawn(async move {
+            loop {
+                sleep(interval).await;
+                // checked under the lock, so that no reload wipes a change
+                // made after stopping
+                let mut e = enforcer.write().await;
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                let _ = e.load_policy().await;
+                reloads.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+    }
+
+    /// Stops the auto load started by `start_auto_load_policy`, a reload
+    /// already in progress still completes.
+    pub fn stop_auto_load_policy(&self) {
+        if let Some(stop) = self.auto_load_stop.lock().take() {
+            stop.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_auto_load_policy_running(&self) -> bool {
+        self.auto_load_stop.lock().is_some()
+    }
+}
+
+impl From<Enforcer> for SyncedEnforcer {
+    fn from(e: Enforcer) -> Self {
+        SyncedEnforcer {
+            enforcer: Arc::new(RwLock::new(e)),
+            auto_load_stop: Mutex::new(None),
+            reloads: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl Drop for SyncedEnforcer {
+    fn drop(&mut self) {
+        self.stop_auto_load_policy();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::testing::{to_owned, PickyAdapter};
+    use crate::prelude::*;
+
+    // only needs the traits, as any code written for an `Enforcer`
+    async fn grant<T: RbacApi>(e: &mut T, user: &str, role: &str) -> bool {
+        e.add_role_for_user(user, role, None).await.unwrap()
+    }
+
+    #[cfg_attr(feature = "runtime-async-std", async_std::test)]
+    #[cfg_attr(feature = "runtime-tokio", tokio::test)]
+    async fn test_synced_enforcer() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let adapter = FileAdapter::new("examples/rbac_policy.csv");
+        let e = Arc::new(SyncedEnforcer::new(m, adapter).await.unwrap());
+        e.write().await.enable_auto_save(false);
+
+        let ee = Arc::clone(&e);
+        let _ = spawn(async move {
+            assert!(grant(&mut *ee.write().await, "bob", "data2_admin").await);
+        })
+        .await;
+
+        let r = e.read().await;
+        assert!(r.enforce(("alice", "data2", "read")).unwrap());
+        assert!(r.enforce(("bob", "data2", "read")).unwrap());
+        assert_eq!(vec!["data2_admin"], r.get_roles_for_user("bob", None));
+        assert!(r.has_grouping_policy(to_owned(vec!["bob", "data2_admin"])));
+        drop(r);
+
+        e.write().await.load_policy().await.unwrap();
+        assert!(!e.read().await.enforce(("bob", "data2", "read")).unwrap());
+    }
+
+    #[cfg_attr(feature = "runtime-async-std", async_std::test)]
+    #[cfg_attr(feature = "runtime-tokio", tokio::test)]
+    async fn test_synced_enforcer_auto_save() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let e = SyncedEnforcer::new(m, PickyAdapter::refusing("data3"))
+            .await
+            .unwrap();
+        let rule = to_owned(vec!["bob", "data2", "write"]);
+
+        let mut w = e.write().await;
+        assert!(w.add_policy(rule.clone()).await.unwrap());
+        assert!(!w.add_policy(rule.clone()).await.unwrap());
+        assert!(grant(&mut *w, "bob", "admin").await);
+
+        let mut tx = w.begin_transaction();
+        tx.update_policy(rule.clone(), to_owned(vec!["bob", "data2", "read"]))
+            .add_policy(to_owned(vec!["admin", "data1", "read"]));
+        assert!(tx.commit().await.unwrap());
+
+        // refused by the adapter, so nothing of it is kept
+        let mut tx = w.begin_transaction();
+        tx.add_policy(to_owned(vec!["alice", "data1", "read"]))
+            .add_policy(to_owned(vec!["alice", "data3", "read"]));
+        assert!(!tx.commit().await.unwrap());
+        drop(w);
+
+        // the adapter has every change, so reloading keeps them
+        e.write().await.load_policy().await.unwrap();
+        let r = e.read().await;
+        assert!(!r.has_policy(rule));
+        assert!(r.enforce(("bob", "data2", "read")).unwrap());
+        assert!(r.enforce(("bob", "data1", "read")).unwrap());
+        assert!(!r.enforce(("alice", "data1", "read")).unwrap());
+        assert_eq!(vec!["admin"], r.get_roles_for_user("bob", None));
+    }
+
+    #[cfg_attr(feature = "runtime-async-std", async_std::test)]
+    #[cfg_attr(feature = "runtime-tokio", tokio::test)]
+    async fn test_auto_load_policy() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let e = SyncedEnforcer::new(m, MemoryAdapter::default())
+            .await
+            .unwrap();
+        e.write().await.enable_auto_save(false);
+        let rule = to_owned(vec!["alice", "data1", "read"]);
+        let reloads = || e.reloads.load(Ordering::SeqCst);
+
+        e.start_auto_load_policy(Duration::from_millis(1));
+        assert!(e.is_auto_load_policy_running());
+
+        // the rule only lives in the model until a reload after it, which
+        // is counted under the lock
+        e.write().await.add_policy(rule.clone()).await.unwrap();
+        let seen = reloads();
+        while reloads() == seen {
+            sleep(Duration::from_millis(1)).await;
+        }
+        assert!(!e.read().await.has_policy(rule.clone()));
+
+        e.stop_auto_load_policy();
+        assert!(!e.is_auto_load_policy_running());
+        e.write().await.add_policy(rule.clone()).await.unwrap();
+        // the task drops its handle on the enforcer once it stopped
+        while Arc::strong_count(&e.enforcer) > 1 {
+            sleep(Duration::from_millis(1)).await;
+        }
+        assert!(e.read().await.has_policy(rule));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::enforcer::EnforceContext;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+/// Step by step record of an enforcement, as returned by
+/// `Enforcer::enforce_trace`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnforceTrace {
+    pub request: Vec<String>,
+    pub context: EnforceContext,
+    /// One step per policy rule scanned, in order.
+    pub steps: Vec<PolicyTrace>,
+    /// The decision, `None` when the evaluation failed.
+    pub allowed: Option<bool>,
+    pub error: Option<String>,
+}
+
+/// How a single policy rule evaluated.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyTrace {
+    /// Position of the rule among the policies, `None` when there is no
+    /// policy and the matcher ran against blank `p_*` values.
+    pub index: Option<usize>,
+    /// The `p_*` tokens bound to the values of the rule.
+    pub values: BTreeMap<String, String>,
+    /// Result of the matcher, `None` when it failed with `error`.
+    pub matched: Option<bool>,
+    pub error: Option<String>,
+    /// Effect pushed to the effector stream: `allow`, `deny`, a custom
+    /// effect or `indeterminate` when the matcher did not match.
+    pub effect: Option<String>,
+    /// Whether the effector stream settled on this rule, leaving the
+    /// remaining ones unscanned.
+    pub done: bool,
+}
+
+impl PolicyTrace {
+    pub(crate) fn new(
+        index: Option<usize>,
+        tokens: &[String],
+        pvals: &[String],
+    ) -> Self {
+        PolicyTrace {
+            index,
+            values: tokens
+                .iter()
+                .enumerate()
+                .map(|(j, token)| {
+                    (token.clone(), pvals.get(j).cloned().unwrap_or_default())
+                })
+                .collect(),
+            matched: None,
+            error: None,
+            effect: None,
+            done: false,
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use crate::{core_api::IEnforcer, emitter::EventData, Adapter, Model, Result};
+
+#[cfg(any(feature = "watcher", feature = "cached", feature = "logging"))]
+use crate::emitter::Event;
+
+/// A batch of policy changes applied to the model and the adapter as one
+/// unit, see [`MgmtApi::begin_transaction`](crate::MgmtApi::begin_transaction).
+///
+/// Nothing is applied until [`commit`](Transaction::commit), dropping the
+/// transaction or calling [`rollback`](Transaction::rollback) discards the
+/// buffered changes.
+pub struct Transaction<'a, T: IEnforcer> {
+    e: &'a mut T,
+    ops: Vec<EventData>,
+}
+
+impl<'a, T: IEnforcer> Transaction<'a, T> {
+    pub(crate) fn new(e: &'a mut T) -> Self {
+        Transaction { e, ops: vec![] }
+    }
+
+    pub fn add_policy(&mut self, params: Vec<String>) -> &mut Self {
+        self.add_named_policy("p", params)
+    }
+
+    pub fn add_named_policy(
+        &mut self,
+        ptype: &str,
+        params: Vec<String>,
+    ) -> &mut Self {
+        self.push(EventData::AddPolicy(
+            "p".to_owned(),
+            ptype.to_owned(),
+            params,
+        ))
+    }
+
+    pub fn add_grouping_policy(&mut self, params: Vec<String>) -> &mut Self {
+        self.add_named_grouping_policy("g", params)
+    }
+
+    pub fn add_named_grouping_policy(
+        &mut self,
+        ptype: &str,
+        params: Vec<String>,
+    ) -> &mut Self {
+        self.push(EventData::AddPolicy(
+            "g".to_owned(),
+            ptype.to_owned(),
+            params,
+        ))
+    }
+
+    pub fn remove_policy(&mut self, params: Vec<String>) -> &mut Self {
+        self.remove_named_policy("p", params)
+    }
+
+    pub fn remove_named_policy(
+        &mut self,
+        ptype: &str,
+        params: Vec<String>,
+    ) -> &mut Self {
+        self.push(EventData::RemovePolicy(
+            "p".to_owned(),
+            ptype.to_owned(),
+            params,
+        ))
+    }
+
+    pub fn remove_grouping_policy(&mut self, params: Vec<String>) -> &mut Self {
+        self.remove_named_grouping_policy("g", params)
+    }
+
+    pub fn remove_named_grouping_policy(
+        &mut self,
+        ptype: &str,
+        params: Vec<String>,
+    ) -> &mut Self {
+        self.push(EventData::RemovePolicy(
+            "g".to_owned(),
+            ptype.to_owned(),
+            params,
+        ))
+    }
+
+    pub fn update_policy(
+        &mut self,
+        old: Vec<String>,
+        new: Vec<String>,
+    ) -> &mut Self {
+        self.update_named_policy("p", old, new)
+    }
+
+    pub fn update_named_policy(
+        &mut self,
+        ptype: &str,
+        old: Vec<String>,
+        new: Vec<String>,
+    ) -> &mut Self {
+        self.push(EventData::UpdatePolicy(
+            "p".to_owned(),
+            ptype.to_owned(),
+            old,
+            new,
+        ))
+    }
+
+    pub fn update_grouping_policy(
+        &mut self,
+        old: Vec<String>,
+        new: Vec<String>,
+    ) -> &mut Self {
+        self.update_named_grouping_policy("g", old, new)
+    }
+
+    pub fn update_named_grouping_policy(
+        &mut self,
+        ptype: &str,
+        old: Vec<String>,
+        new: Vec<String>,
+    ) -> &mut Self {
+        self.push(EventData::UpdatePolicy(
+            "g".to_owned(),
+            ptype.to_owned(),
+            old,
+            new,
+        ))
+    }
+
+    fn push(&mut self, op: EventData) -> &mut Self {
+        self.ops.push(op);
+        self
+    }
+
+    /// Discards the buffered changes.
+    pub fn rollback(self) {}
+
+    /// Applies the buffered changes in order.
+    ///
+    /// Changes that have no effect on the model, such as adding a rule that
+    /// already exists, are skipped. When auto save is enabled and the
+    /// adapter fails or refuses one of the remaining changes, the changes
+    /// already saved are reverted on a best effort basis, the model is left
+    /// untouched and the error, or `false`, is returned. Otherwise a single
+    /// [`EventData::Transaction`] is emitted and role links are rebuilt
+    /// once. Returns whether anything changed.
+    pub async fn commit(self) -> Result<bool> {
+        let Transaction { e, ops } = self;
+
+        let mut snapshots: Vec<(&str, &str, Vec<Vec<String>>)> = vec![];
+        for (sec, ptype) in ops.iter().filter_map(target) {
+            if !snapshots.iter().any(|(s, p, _)| *s == sec && *p == ptype) {
+                let policy = e.get_model().get_policy(sec, ptype);
+                snapshots.push((sec, ptype, policy));
+            }
+        }
+
+        let model = e.get_mut_model();
+        let changes: Vec<EventData> = ops
+            .iter()
+            .filter(|op| apply_to_model(model, op))
+            .cloned()
+            .collect();
+        if changes.is_empty() {
+            return Ok(false);
+        }
+
+        if e.has_auto_save_enabled() {
+            let saved = save_changes(e.get_mut_adapter(), &changes).await;
+            if !matches!(saved, Ok(true)) {
+                let model = e.get_mut_model();
+                for (sec, ptype, policy) in snapshots {
+                    let current = model.get_policy(sec, ptype);
+                    model.remove_policies(sec, ptype, current);
+                    model.add_policies(sec, ptype, policy);
+                }
+                return saved;
+            }
+        }
+
+        let has_grouping_change = changes
+            .iter()
+            .any(|op| matches!(target(op), Some(("g", _))));
+
+        #[cfg(any(feature = "watcher", feature = "logging"))]
+        {
+            let event_data = EventData::Transaction(changes);
+            #[cfg(feature = "watcher")]
+            {
+                if e.has_auto_notify_watcher_enabled() {
+                    e.emit(Event::PolicyChange, event_data);
+                }
+            }
+            #[cfg(not(feature = "watcher"))]
+            {
+                e.emit(Event::PolicyChange, event_data);
+            }
+        }
+        #[cfg(feature = "cached")]
+        {
+            e.emit(Event::ClearCache, EventData::ClearCache);
+        }
+        if has_grouping_change && e.has_auto_build_role_links_enabled() {
+            e.build_role_links()?;
+        }
+
+        Ok(true)
+    }
+}
+
+fn target(op: &EventData) -> Option<(&str, &str)> {
+    match op {
+        EventData::AddPolicy(sec, ptype, _)
+        | EventData::RemovePolicy(sec, ptype, _)
+        | EventData::UpdatePolicy(sec, ptype, _, _) => Some((sec, ptype)),
+        _ => None,
+    }
+}
+
+fn apply_to_model(model: &mut dyn Model, op: &EventData) -> bool {
+    match op.clone() {
+        EventData::AddPolicy(sec, ptype, rule) => {
+            model.add_policy(&sec, &ptype, rule)
+        }
+        EventData::RemovePolicy(sec, ptype, rule) => {
+            model.remove_policy(&sec, &ptype, rule)
+        }
+        EventData::UpdatePolicy(sec, ptype, old_rule, new_rule) => {
+            model.update_policy(&sec, &ptype, old_rule, new_rule)
+        }
+        _ => false,
+    }
+}
+
+async fn apply_to_adapter(
+    adapter: &mut dyn Adapter,
+    op: EventData,
+) -> Result<bool> {
+    match op {
+        EventData::AddPolicy(sec, ptype, rule) => {
+            adapter.add_policy(&sec, &ptype, rule).await
+        }
+        EventData::RemovePolicy(sec, ptype, rule) => {
+            adapter.remove_policy(&sec, &ptype, rule).await
+        }
+        EventData::UpdatePolicy(sec, ptype, old_rule, new_rule) => {
+            match adapter.as_updatable_mut() {
+                Some(adapter) => {
+                    adapter
+                        .update_policy(&sec, &ptype, old_rule, new_rule)
+                        .await
+                }
+                None => {
+                    Ok(adapter.remove_policy(&sec, &ptype, old_rule).await?
+                        && adapter.add_policy(&sec, &ptype, new_rule).await?)
+                }
+            }
+        }
+        _ => Ok(false),
+    }
+}
+
+fn inverse(op: &EventData) -> EventData {
+    match op.clone() {
+        EventData::AddPolicy(sec, ptype, rule) => {
+            EventData::RemovePolicy(sec, ptype, rule)
+        }
+        EventData::RemovePolicy(sec, ptype, rule) => {
+            EventData::AddPolicy(sec, ptype, rule)
+        }
+        EventData::UpdatePolicy(sec, ptype, old_rule, new_rule) => {
+            EventData::UpdatePolicy(sec, ptype, new_rule, old_rule)
+        }
+        op => op,
+    }
+}
+
+// saves `changes` one by one, reverting the saved ones on failure
+async fn save_changes(
+    adapter: &mut dyn Adapter,
+    changes: &[EventData],
+) -> Result<bool> {
+    for (i, op) in changes.iter().enumerate() {
+        let saved = apply_to_adapter(adapter, op.clone()).await;
+        if !matches!(saved, Ok(true)) {
+            for op in changes[..i].iter().rev() {
+                let _ = apply_to_adapter(adapter, inverse(op)).await;
+            }
+            return saved;
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "watcher")]
+    use crate::adapter::testing::SampleWatcher;
+    use crate::adapter::testing::{to_owned, PickyAdapter};
+    use crate::prelude::*;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_commit_transaction() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let mut e = Enforcer::new(m, PickyAdapter::refusing("data3"))
+            .await
+            .unwrap();
+        e.add_policy(to_owned(vec!["data1_admin", "data1", "read"]))
+            .await
+            .unwrap();
+        e.add_grouping_policy(to_owned(vec!["alice", "data1_admin"]))
+            .await
+            .unwrap();
+
+        let mut tx = e.begin_transaction();
+        tx.remove_grouping_policy(to_owned(vec!["alice", "data1_admin"]))
+            .add_grouping_policy(to_owned(vec!["bob", "data1_admin"]))
+            .add_policy(to_owned(vec!["data1_admin", "data1", "read"]))
+            .update_policy(
+                to_owned(vec!["data1_admin", "data1", "read"]),
+                to_owned(vec!["data1_admin", "data1", "write"]),
+            );
+        assert!(tx.commit().await.unwrap());
+
+        assert!(!e.enforce(("alice", "data1", "write")).unwrap());
+        assert!(e.enforce(("bob", "data1", "write")).unwrap());
+        assert!(!e.enforce(("bob", "data1", "read")).unwrap());
+        assert_eq!(vec![vec!["data1_admin", "data1", "write"]], e.get_policy());
+
+        let mut tx = e.begin_transaction();
+        tx.remove_grouping_policy(to_owned(vec!["bob", "data1_admin"]));
+        tx.rollback();
+        assert!(e.enforce(("bob", "data1", "write")).unwrap());
+
+        let mut tx = e.begin_transaction();
+        tx.add_policy(to_owned(vec!["data1_admin", "data1", "write"]));
+        assert!(!tx.commit().await.unwrap());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_failed_transaction() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let mut e = Enforcer::new(m, PickyAdapter::refusing("data3"))
+            .await
+            .unwrap();
+        e.add_policy(to_owned(vec!["data1_admin", "data1", "read"]))
+            .await
+            .unwrap();
+        e.add_grouping_policy(to_owned(vec!["alice", "data1_admin"]))
+            .await
+            .unwrap();
+
+        let mut tx = e.begin_transaction();
+        tx.remove_policy(to_owned(vec!["data1_admin", "data1", "read"]))
+            .remove_grouping_policy(to_owned(vec!["alice", "data1_admin"]))
+            .add_policy(to_owned(vec!["data1_admin", "data3", "read"]));
+        assert!(!tx.commit().await.unwrap());
+
+        assert!(e.enforce(("alice", "data1", "read")).unwrap());
+        assert!(!e.enforce(("alice", "data3", "read")).unwrap());
+        assert_eq!(vec![vec!["data1_admin", "data1", "read"]], e.get_policy());
+
+        // the adapter got its removed rules back
+        e.load_policy().await.unwrap();
+        assert_eq!(vec![vec!["data1_admin", "data1", "read"]], e.get_policy());
+        assert_eq!(vec![vec!["alice", "data1_admin"]], e.get_grouping_policy());
+    }
+
+    #[cfg(feature = "watcher")]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(
+        all(feature = "runtime-async-std", not(target_arch = "wasm32")),
+        async_std::test
+    )]
+    #[cfg_attr(
+        all(feature = "runtime-tokio", not(target_arch = "wasm32")),
+        tokio::test
+    )]
+    async fn test_transaction_notifies_once() {
+        let m = DefaultModel::from_file("examples/rbac_model.conf")
+            .await
+            .unwrap();
+
+        let mut e = Enforcer::new(m, PickyAdapter::refusing("data3"))
+            .await
+            .unwrap();
+        let watcher = SampleWatcher::default();
+        let updates = std::sync::Arc::clone(&watcher.0);
+        e.set_watcher(Box::new(watcher));
+
+        let mut tx = e.begin_transaction();
+        tx.add_policy(to_owned(vec!["data1_admin", "data1", "read"]))
+            .add_grouping_policy(to_owned(vec!["alice", "data1_admin"]))
+            .add_policy(to_owned(vec!["data2_admin", "data2", "read"]));
+        assert!(tx.commit().await.unwrap());
+
+        // the adapter refuses the last rule, nothing is sent
+        let mut tx = e.begin_transaction();
+        tx.remove_grouping_policy(to_owned(vec!["alice", "data1_admin"]))
+            .add_policy(to_owned(vec!["data1_admin", "data3", "read"]));
+        assert!(!tx.commit().await.unwrap());
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(1, updates.len());
+        assert!(matches!(
+            &updates[0],
+            EventData::Transaction(changes) if changes.len() == 3
+        ));
+    }
+}
@@ -1,8 +1,6 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 
-use std::borrow::Cow;
-
 macro_rules! regex {
     ($re:expr) => {
         ::regex::Regex::new($re).unwrap()
@@ -10,18 +8,21 @@ macro_rules! regex {
 }
 
 static ESC_A: Lazy<Regex> = Lazy::new(|| regex!(r"\b(r\d*|p\d*)\."));
+static UNESC_A: Lazy<Regex> = Lazy::new(|| regex!(r"\b(r\d*|p\d*)_"));
 #[allow(dead_code)]
 static ESC_G: Lazy<Regex> = Lazy::new(|| {
     regex!(r"\b(g\d*)\(((?:\s*[r|p]\d*\.\w+\s*,\s*){1,2}\s*[r|p]\d*\.\w+\s*)\)")
 });
 static ESC_C: Lazy<Regex> = Lazy::new(|| regex!(r#"(\s*"[^"]*"?|\s*[^,]*)"#));
-pub(crate) static ESC_E: Lazy<Regex> =
-    Lazy::new(|| regex!(r"\beval\(([^)]*)\)"));
 
 pub fn escape_assertion(s: &str) -> String {
     ESC_A.replace_all(s, "${1}_").to_string()
 }
 
+pub fn unescape_assertion(s: &str) -> String {
+    UNESC_A.replace_all(s, "${1}.").to_string()
+}
+
 pub fn remove_comment(s: &str) -> String {
     let s = if let Some(idx) = s.find('#') {
         &s[..idx]
@@ -32,10 +33,6 @@ pub fn remove_comment(s: &str) -> String {
     s.trim_end().to_owned()
 }
 
-pub fn escape_eval(m: &str) -> Cow<'_, str> {
-    ESC_E.replace_all(m, "eval(escape_assertion(${1}))")
-}
-
 pub fn parse_csv_line<'a, S: AsRef<str> + 'a>(line: S) -> Option<Vec<String>> {
     let line = line.as_ref().trim();
     if line.is_empty() || line.starts_with('#') {
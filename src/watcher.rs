@@ -3,6 +3,154 @@ use crate::emitter::EventData;
 pub trait Watcher: Send + Sync {
     fn set_update_callback(&mut self, cb: Box<dyn FnMut(String) + Send + Sync>);
     fn update(&mut self, d: EventData);
+    /// Returns the watcher as a [`WatcherEx`] if it wants to be notified
+    /// through the per-operation callbacks instead of `update`.
+    fn as_watcher_ex_mut(&mut self) -> Option<&mut dyn WatcherEx> {
+        None
+    }
+}
+
+/// WatcherEx is implemented by watchers which publish every kind of policy
+/// change on its own, so peers can apply it with the matching `self_*`
+/// method of [`MgmtApi`](crate::MgmtApi) instead of reloading the policy.
+/// The exception is
+/// [`update_for_remove_filtered_policy`](WatcherEx::update_for_remove_filtered_policy),
+/// which carries the removed rules rather than the filter: peers apply it
+/// with `self_remove_policies`.
+///
+/// Every callback falls back to [`Watcher::update`], as do the changes
+/// without a callback of their own, such as a transaction or a filtered
+/// update, which peers can apply with
+/// [`apply_event`](crate::MgmtApi::apply_event).
+pub trait WatcherEx: Watcher {
+    fn update_for_add_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) {
+        self.update(EventData::AddPolicy(
+            sec.to_owned(),
+            ptype.to_owned(),
+            rule,
+        ));
+    }
+    fn update_for_add_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) {
+        self.update(EventData::AddPolicies(
+            sec.to_owned(),
+            ptype.to_owned(),
+            rules,
+        ));
+    }
+    fn update_for_remove_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) {
+        self.update(EventData::RemovePolicy(
+            sec.to_owned(),
+            ptype.to_owned(),
+            rule,
+        ));
+    }
+    fn update_for_remove_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) {
+        self.update(EventData::RemovePolicies(
+            sec.to_owned(),
+            ptype.to_owned(),
+            rules,
+        ));
+    }
+    /// Receives the rules which matched the filter and got removed, for
+    /// peers to remove with `self_remove_policies`.
+    fn update_for_remove_filtered_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) {
+        self.update(EventData::RemoveFilteredPolicy(
+            sec.to_owned(),
+            ptype.to_owned(),
+            rules,
+        ));
+    }
+    fn update_for_update_policy(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rule: Vec<String>,
+        new_rule: Vec<String>,
+    ) {
+        self.update(EventData::UpdatePolicy(
+            sec.to_owned(),
+            ptype.to_owned(),
+            old_rule,
+            new_rule,
+        ));
+    }
+    fn update_for_update_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        old_rules: Vec<Vec<String>>,
+        new_rules: Vec<Vec<String>>,
+    ) {
+        self.update(EventData::UpdatePolicies(
+            sec.to_owned(),
+            ptype.to_owned(),
+            old_rules,
+            new_rules,
+        ));
+    }
+    fn update_for_save_policy(&mut self, rules: Vec<Vec<String>>) {
+        self.update(EventData::SavePolicy(rules));
+    }
+}
+
+/// Hands `d` to the watcher, through the callback of a [`WatcherEx`]
+/// matching the event when there is one.
+pub(crate) fn notify_watcher(w: &mut dyn Watcher, d: EventData) {
+    let w = match w.as_watcher_ex_mut() {
+        Some(w) => w,
+        None => return w.update(d),
+    };
+
+    match d {
+        EventData::AddPolicy(sec, ptype, rule) => {
+            w.update_for_add_policy(&sec, &ptype, rule)
+        }
+        EventData::AddPolicies(sec, ptype, rules) => {
+            w.update_for_add_policies(&sec, &ptype, rules)
+        }
+        EventData::RemovePolicy(sec, ptype, rule) => {
+            w.update_for_remove_policy(&sec, &ptype, rule)
+        }
+        EventData::RemovePolicies(sec, ptype, rules) => {
+            w.update_for_remove_policies(&sec, &ptype, rules)
+        }
+        EventData::RemoveFilteredPolicy(sec, ptype, rules) => {
+            w.update_for_remove_filtered_policy(&sec, &ptype, rules)
+        }
+        EventData::UpdatePolicy(sec, ptype, old_rule, new_rule) => {
+            w.update_for_update_policy(&sec, &ptype, old_rule, new_rule)
+        }
+        EventData::UpdatePolicies(sec, ptype, old_rules, new_rules) => {
+            w.update_for_update_policies(&sec, &ptype, old_rules, new_rules)
+        }
+        EventData::SavePolicy(rules) => w.update_for_save_policy(rules),
+        d => w.update(d),
+    }
 }
 
 #[cfg(test)]
@@ -11,6 +159,9 @@ mod tests {
     use crate::prelude::*;
     use std::sync::{Arc, Mutex};
 
+    #[cfg(not(target_arch = "wasm32"))]
+    use crate::adapter::file_adapter::TempPolicy;
+
     // Sample watcher implementation for testing
     struct SampleWatcher {
         callback: Option<Box<dyn FnMut(String) + Send + Sync>>,
@@ -47,12 +198,10 @@ mod tests {
         tokio::test
     )]
     async fn test_set_watcher() {
-        let mut e = Enforcer::new(
-            "examples/rbac_model.conf",
-            "examples/rbac_policy.csv",
-        )
-        .await
-        .unwrap();
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let mut e = Enforcer::new("examples/rbac_model.conf", policy.adapter())
+            .await
+            .unwrap();
 
         let sample_watcher = SampleWatcher::new();
         e.set_watcher(Box::new(sample_watcher));
@@ -71,12 +220,10 @@ mod tests {
         tokio::test
     )]
     async fn test_self_modify() {
-        let mut e = Enforcer::new(
-            "examples/rbac_model.conf",
-            "examples/rbac_policy.csv",
-        )
-        .await
-        .unwrap();
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let mut e = Enforcer::new("examples/rbac_model.conf", policy.adapter())
+            .await
+            .unwrap();
 
         let sample_watcher = SampleWatcher::new();
         e.set_watcher(Box::new(sample_watcher));
@@ -116,23 +263,27 @@ mod tests {
             }));
         }
 
-        // Note: casbin-rs doesn't have self_add_policy, using add_policy instead
-        // This test demonstrates the watcher callback functionality
+        // self_add_policy applies a peer's change without notifying back
         let result = e
-            .add_policy(vec![
-                "eva".to_string(),
-                "data".to_string(),
-                "write".to_string(),
-            ])
+            .self_add_policy(
+                "p",
+                "p",
+                vec![
+                    "eva".to_string(),
+                    "data".to_string(),
+                    "write".to_string(),
+                ],
+            )
             .await;
         assert!(result.unwrap());
+        assert!(e.enforce(("eva", "data", "write")).unwrap());
 
-        // In casbin-rs, watcher is called for all policy changes
         let called2_value = *called2.lock().unwrap();
         assert_eq!(
-            called2_value, 1,
-            "callback should be called for policy changes"
+            called2_value, -1,
+            "callback should not be called for self operations"
         );
+        assert!(e.has_auto_notify_watcher_enabled());
     }
 
     #[test]
@@ -197,6 +348,47 @@ mod tests {
                 callback(event_str);
             }
         }
+
+        fn as_watcher_ex_mut(&mut self) -> Option<&mut dyn WatcherEx> {
+            Some(self)
+        }
+    }
+
+    impl WatcherEx for SampleWatcherEx {
+        fn update_for_add_policy(
+            &mut self,
+            sec: &str,
+            ptype: &str,
+            rule: Vec<String>,
+        ) {
+            self.update_calls.lock().unwrap().push(format!(
+                "update_for_add_policy {}::{} {}",
+                sec,
+                ptype,
+                rule.join(", ")
+            ));
+        }
+
+        fn update_for_remove_filtered_policy(
+            &mut self,
+            sec: &str,
+            ptype: &str,
+            rules: Vec<Vec<String>>,
+        ) {
+            self.update_calls.lock().unwrap().push(format!(
+                "update_for_remove_filtered_policy {}::{} {}",
+                sec,
+                ptype,
+                rules.len()
+            ));
+        }
+
+        fn update_for_save_policy(&mut self, rules: Vec<Vec<String>>) {
+            self.update_calls
+                .lock()
+                .unwrap()
+                .push(format!("update_for_save_policy {}", rules.len()));
+        }
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -209,12 +401,10 @@ mod tests {
         tokio::test
     )]
     async fn test_set_watcher_ex() {
-        let mut e = Enforcer::new(
-            "examples/rbac_model.conf",
-            "examples/rbac_policy.csv",
-        )
-        .await
-        .unwrap();
+        let policy = TempPolicy::copy("examples/rbac_policy.csv");
+        let mut e = Enforcer::new("examples/rbac_model.conf", policy.adapter())
+            .await
+            .unwrap();
 
         let sample_watcher_ex = SampleWatcherEx::new();
         let update_calls = Arc::clone(&sample_watcher_ex.update_calls);
@@ -309,37 +499,16 @@ mod tests {
         let calls = update_calls.lock().unwrap();
         assert!(!calls.is_empty(), "Watcher should have been called");
 
-        // Verify some specific operation types were captured
-        let call_types: Vec<String> = calls
+        // Overridden callbacks are used, the others fall back to update()
+        assert!(calls[0].starts_with("update_for_save_policy"));
+        assert_eq!("update_for_add_policy p::p admin, data1, read", calls[1]);
+        assert!(calls[2].contains("Type: RemovePolicy"));
+        assert!(calls
             .iter()
-            .filter_map(|call| {
-                if call.contains("SavePolicy") {
-                    Some("SavePolicy".to_string())
-                } else if call.contains("AddPolicy") {
-                    Some("AddPolicy".to_string())
-                } else if call.contains("RemovePolicy") {
-                    Some("RemovePolicy".to_string())
-                } else if call.contains("AddPolicies") {
-                    Some("AddPolicies".to_string())
-                } else if call.contains("RemovePolicies") {
-                    Some("RemovePolicies".to_string())
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        assert!(
-            call_types.contains(&"SavePolicy".to_string()),
-            "Should capture SavePolicy calls"
-        );
-        assert!(
-            call_types.contains(&"AddPolicy".to_string()),
-            "Should capture AddPolicy calls"
-        );
-        assert!(
-            call_types.contains(&"RemovePolicy".to_string()),
-            "Should capture RemovePolicy calls"
-        );
+            .any(|call| call.starts_with("update_for_remove_filtered_policy")));
+        assert!(calls.iter().any(|call| call.contains("Type: AddPolicies")));
+        assert!(calls
+            .iter()
+            .any(|call| call.contains("Type: RemovePolicies")));
     }
 }